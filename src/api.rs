@@ -1,188 +1,352 @@
 use std::{
     fs::File,
-    io::{Read, Write},
-    path::{Path, PathBuf},
-    sync::LazyLock,
+    io::Read,
+    path::{Component, Path, PathBuf},
 };
 
+use serde::Serialize;
+
 use super::{Error, Result};
-use crate::utils::{self, yesno};
+use crate::{
+    crypto::{self, CryptoBackend},
+    transaction::Transaction,
+    utils, Store,
+};
 
-pub static PASS_DIR_ROOT: LazyLock<PathBuf> = LazyLock::new(|| {
-    PathBuf::from(std::env::var("HOME").expect("env variable `HOME` should be set"))
-        .join(".password-store")
-});
+/// Path of `name` (pass name with extension, or subfolder) in its store.
+/// The first component selects a mounted store, if one is mounted with that name.
+#[must_use]
+pub fn store_path(store: &Store, name: &str) -> PathBuf {
+    let (first, rest) = name.split_once('/').unwrap_or((name, ""));
+    match store.config().mounts.get(first) {
+        Some(root) if rest.is_empty() => root.clone(),
+        Some(root) => root.join(rest),
+        None => store.root().join(name),
+    }
+}
 
-#[derive(Debug)]
+/// Root of the store containing `path`, [`Store::root`] or root of a mounted store.
 #[must_use]
-pub struct PassFile {
-    pass_name: String,
-    temp_path: tempfile::TempPath,
-    /// Changes should be added to git.
-    maybe_modified: bool,
-    commit_msg: Option<String>,
-}
-impl PassFile {
-    /// # Safety
-    /// You must drop `EncryptedFile`.
-    pub unsafe fn open(pass_name: String) -> Result<Self> {
-        check_uninitialized_store()?;
-        let content = crate::utils::read_to_vec(get_readonly_pass_file(pass_name.clone())?)?;
-        let mut gpg = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
-        let content = {
-            let mut buf = Vec::new();
-            gpg.decrypt(&content, &mut buf)?;
-            buf
-        };
-        PassFile::new(pass_name, &content)
-    }
-    /// # Safety
-    /// You must drop `EncryptedFile`.
-    #[allow(clippy::missing_panics_doc/* Reason: get_root() is not filesystem root */)]
-    pub unsafe fn create(pass_name: String, force: bool) -> Result<Self> {
-        check_uninitialized_store()?;
-        let path = PASS_DIR_ROOT.join(pass_name.clone() + ".gpg");
-        std::fs::create_dir_all(path.parent().unwrap())?;
-        if force {
-            File::create(&path)?;
+pub fn store_root<'a>(store: &'a Store, path: &Path) -> &'a Path {
+    store
+        .config()
+        .mounts
+        .values()
+        .find(|root| path.starts_with(root))
+        .map_or(store.root(), PathBuf::as_path)
+}
+
+/// Name of file or directory at `path` as seen from the main store,
+/// so names in mounted stores start with the mount name.
+#[must_use]
+pub fn name_of(store: &Store, path: &Path) -> Option<String> {
+    let mounted = store.config().mounts.iter().find_map(|(name, root)| {
+        let relative = path.strip_prefix(root).ok()?;
+        Some(if relative.as_os_str().is_empty() {
+            PathBuf::from(name)
         } else {
-            File::create_new(&path).or_else(|err| {
-                if err.kind() == std::io::ErrorKind::AlreadyExists {
-                    print!("An entry already exists for {path:?}. Overwrite it? ");
-                    if utils::yesno(false)? {
-                        Ok(File::create(&path)?)
-                    } else {
-                        Err(err)
-                    }
-                } else {
-                    Err(err)
-                }
-            })?;
-        };
-        let mut temp = PassFile::new(pass_name, &[])?;
-        temp.maybe_modified = true;
-        Ok(temp)
-    }
-    /// # Warning
-    /// `modified` by default set to true.
-    fn new(pass_name: String, content: &[u8]) -> Result<Self> {
-        let mut temp_file = utils::create_temp_file()?;
-        temp_file.write_all(content)?;
-        Ok(Self {
-            pass_name,
-            temp_path: temp_file.into_temp_path(),
-            maybe_modified: false,
-            commit_msg: None,
+            Path::new(name).join(relative)
         })
+    });
+    let name = match mounted {
+        Some(name) => name,
+        None => path.strip_prefix(store.root()).ok()?.to_owned(),
+    };
+    name.to_str().map(str::to_owned)
+}
+
+/// Write recipients file of `backend` for `subfolder` (or remove it, if `recipients` is `[""]`)
+/// and reencrypt all pass files under `subfolder` whose recipients changed.
+pub fn init(
+    store: &Store,
+    subfolder: &str,
+    recipients: &[String],
+    backend: &dyn CryptoBackend,
+) -> Result<()> {
+    init_with_message(store, subfolder, recipients, backend, None)
+}
+
+/// Like [`init`], but commit with `message`, if it's given.
+///
+/// Recipients file and reencrypted pass files are changed all at once
+/// with one commit, on error nothing is changed.
+pub fn init_with_message(
+    store: &Store,
+    subfolder: &str,
+    recipients: &[String],
+    backend: &dyn CryptoBackend,
+    message: Option<&str>,
+) -> Result<()> {
+    check_pass_name(subfolder)?;
+    let subfolder_path = store_path(store, subfolder);
+    let root = store_root(store, &subfolder_path);
+    if subfolder_path.exists() && !subfolder_path.is_dir() {
+        return Err(Error::NotADirectory {
+            path: subfolder_path,
+        });
     }
-    /// # Warning
-    /// - You can see all changes only after `flush()` or `drop()`.
-    /// - Current changes don't affect old pass file.
-    /// # Note
-    /// If function return error, [`PathFile`] stay unchanged.
-    pub fn copy(&mut self, new_name: String, force: bool) -> std::io::Result<()> {
-        let new_path = get_pass_path(&new_name);
-        let user_agreement = || -> std::io::Result<bool> {
-            print!("An entry already exists for {new_path:?}. Overwrite it? ");
-            yesno(false)
+    let recipients_path = subfolder_path.join(backend.recipients_file());
+    let path_note = if subfolder.is_empty() {
+        String::new()
+    } else {
+        format!(" ({subfolder})")
+    };
+
+    let mut transaction = Transaction::new(root, store.interrupt())?;
+    let mut changed = false;
+    let (commit_msg, done_msg, effective) = if recipients == [""] {
+        if !recipients_path.is_file() {
+            return Err(Error::GpgIdDoesNotExist {
+                path: recipients_path,
+            });
+        }
+        transaction.remove(store, &recipients_path)?;
+        changed = true;
+        // pass files use recipients of parent folder again
+        let effective = match subfolder_path.parent() {
+            Some(parent) if subfolder_path != root => {
+                match find_recipients_file(store, parent.to_owned()) {
+                    Ok((_, backend, recipients)) => Some((backend, recipients)),
+                    Err(Error::PasswordStoreUninitialized) => None,
+                    Err(err) => return Err(err),
+                }
+            }
+            _ => None,
         };
-        if force || !new_path.exists() || user_agreement()? {
-            self.pass_name = new_name;
-            self.maybe_modified = true;
+        (
+            format!("Deinitialize {recipients_path:?}{path_note}."),
+            format!("Removed {recipients_path:?}"),
+            effective,
+        )
+    } else {
+        backend.resolve_recipients(recipients)?;
+        let recipients_print = recipients.join(", ");
+        let mut content = recipients.join("\n");
+        content.push('\n');
+        if std::fs::read_to_string(&recipients_path).ok() != Some(content.clone()) {
+            transaction.write_file(store, recipients_path.clone(), content.as_bytes())?;
+            changed = true;
         }
-        Ok(())
+        // directory is encrypted by one backend
+        for other in store.backends().all() {
+            let other_path = subfolder_path.join(other.recipients_file());
+            if other_path != recipients_path && other_path.is_file() {
+                transaction.remove(store, &other_path)?;
+                changed = true;
+            }
+        }
+        (
+            format!("Set {} to {recipients_print}{path_note}.", backend.name()),
+            format!("Password store initialized for {recipients_print}{path_note}"),
+            Some((backend, recipients.to_owned())),
+        )
+    };
+
+    let reencrypted = if subfolder_path.is_dir() {
+        reencrypt_path(
+            store,
+            &mut transaction,
+            &subfolder_path,
+            effective.as_ref(),
+            changed,
+        )?
+    } else {
+        0
+    };
+    if !transaction.is_empty() {
+        transaction.commit(store, message.unwrap_or(&commit_msg))?;
+    }
+    println!("{done_msg}");
+    if reencrypted > 0 {
+        println!("Reencrypted {reencrypted} password(s)");
     }
-    /// # Warning
-    /// You must specify commit message before running this method.
-    /// # Note
-    /// If function return error, `PathFile` stay unchanged.
-    pub fn rename(&mut self, new_name: String, force: bool) -> Result<()> {
-        let new_path = get_pass_path(&new_name);
-        let user_agreement = || -> std::io::Result<bool> {
-            print!("An entry already exists for {new_path:?}. Overwrite it? ");
-            yesno(false)
+    Ok(())
+}
+
+/// Stage reencryption of pass files under `root`, that use recipients of `root`
+/// and are not encrypted exactly for `effective` recipients
+/// (`None` if there are no recipients for them).
+/// Subfolders with own recipients file and mounted stores are skipped,
+/// their recipients don't change.
+/// Pass files are moved to backend of `effective`, if they are encrypted by another one.
+/// If backend can't tell recipients of pass file, it's reencrypted only when
+/// `recipients_changed`.
+///
+/// # Returns
+/// Number of reencrypted pass files.
+fn reencrypt_path(
+    store: &Store,
+    transaction: &mut Transaction,
+    root: &Path,
+    effective: Option<&(&dyn CryptoBackend, Vec<String>)>,
+    recipients_changed: bool,
+) -> Result<usize> {
+    let has_own_recipients = |dir: &Path| {
+        store
+            .backends()
+            .all()
+            .into_iter()
+            .any(|x| dir.join(x.recipients_file()).is_file())
+    };
+    let mut reencrypted = 0;
+    let entries = walkdir::WalkDir::new(root)
+        .follow_links(true)
+        .into_iter()
+        .filter_entry(|x| {
+            x.depth() == 0
+                || !x.file_type().is_dir()
+                || (x.file_name() != ".git"
+                    && !has_own_recipients(x.path())
+                    && !store.config().mounts.values().any(|root| root == x.path()))
+        });
+    for entry in entries {
+        let entry = entry.map_err(std::io::Error::from)?;
+        let path = entry.path();
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let (Some(pass_name), Some(old_backend)) =
+            (pass_name_of(store, path), store.backends().for_path(path))
+        else {
+            continue;
+        };
+
+        let Some(&(backend, ref recipients)) = effective else {
+            return Err(Error::PasswordStoreUninitialized);
         };
-        if force || !new_path.exists() || user_agreement()? {
-            let prev_pass_name = self.pass_name.clone();
-            self.pass_name = new_name;
-            self.maybe_modified = true;
-            self.flush()?;
-            std::fs::remove_file(get_pass_path(&prev_pass_name))?;
+        let ciphertext = utils::read_to_vec(File::open(path)?)?;
+        let same_backend = old_backend.extension() == backend.extension();
+        if same_backend
+            && !crypto::needs_reencryption(backend, &ciphertext, recipients)?
+                .unwrap_or(recipients_changed)
+        {
+            continue;
         }
-        Ok(())
+        let plaintext = old_backend
+            .decrypt(&ciphertext)
+            .map_err(|source| Error::ReadPass {
+                pass_name,
+                source: Box::new(source),
+            })?;
+        let new_path = path.with_extension(backend.extension());
+        transaction.write_file(store, new_path, &backend.encrypt(recipients, &plaintext)?)?;
+        if !same_backend {
+            transaction.remove(store, path)?;
+        }
+        reencrypted += 1;
     }
+    Ok(reencrypted)
+}
+
+/// Decrypt pass file content in memory, without any temp files.
+pub fn decrypt(store: &Store, pass_name: &str) -> Result<Vec<u8>> {
+    check_pass_name(pass_name)?;
+    check_uninitialized_store(store)?;
+    let path = get_pass_path(store, &pass_name);
+    let content = utils::read_to_vec(get_readonly_pass_file(store, pass_name.to_owned())?)?;
+    store
+        .backends()
+        .for_path(&path)
+        .unwrap_or(store.backends().openpgp())
+        .decrypt(&content)
+}
+
+/// Directory or pass file of the store.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "name", rename_all = "lowercase")]
+pub enum StoreItem {
+    /// Path relative to the store root.
+    Directory(String),
+    /// Pass name.
+    Pass(String),
+}
+
+impl StoreItem {
     #[must_use]
-    pub fn get_path_to_unencrypted(&mut self) -> &Path {
-        self.maybe_modified = true;
-        &self.temp_path
-    }
-    pub fn content_writer(&mut self) -> std::io::Result<impl Write + '_> {
-        self.maybe_modified = true;
-        File::create(&self.temp_path)
-    }
-    pub fn content_reader(&self) -> std::io::Result<impl Read + '_> {
-        File::open(&self.temp_path)
-    }
-    pub fn set_commit_msg(&mut self, msg: String) {
-        self.commit_msg = Some(msg);
-    }
-    /// Write all content from temp file to encrypted file.
-    #[allow(clippy::missing_panics_doc/* Reason: get_pass_path() is not filesystem root */)]
-    pub fn flush(&mut self) -> Result<()> {
-        if self.maybe_modified {
-            let final_version = crate::utils::read_to_vec(File::open(&*self.temp_path)?)?;
-            let path = get_pass_path(&self.pass_name);
-
-            std::fs::create_dir_all(path.parent().unwrap())?;
-            let mut pass_file = File::create(path)?;
-            let encrypted = encrypt(&self.pass_name, &final_version)?;
-            pass_file.write_all(&encrypted)?;
-
-            if let Some(ref commit_msg) = self.commit_msg {
-                let result = crate::git::commit_file(
-                    PASS_DIR_ROOT.as_os_str(),
-                    &(self.pass_name.clone() + ".gpg"),
-                    commit_msg,
-                );
-                if let Err(err) = result {
-                    match err {
-                        Error::PassStoreShouldBeGitRepo => (),
-                        err => return Err(err),
-                    }
-                }
-            } else {
-                eprintln!("NOTE: not added to git because `commit_msg` not specified.");
-            }
-            self.maybe_modified = false;
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Directory(name) | Self::Pass(name) => name,
         }
-        Ok(())
     }
 }
 
-impl Drop for PassFile {
-    fn drop(&mut self) {
-        let error_msg = format!(
-            "Can't delete temp file with path {:?} and then encrypt it's content to {:?}. Save and delete it manually",
-            &*self.temp_path, self.pass_name
-        );
-        self.flush().expect(&error_msg);
+/// Directories and pass files under `root`, depth-first and sorted by name,
+/// so every directory goes before its content.
+/// Hidden files and directories (like `.git`) and symlink loops are skipped.
+/// Walk of [`Store::root`] includes mounted stores, they hide folders with the same name.
+pub fn walk(store: &Store, root: &Path) -> Result<Vec<StoreItem>> {
+    let mounts = &store.config().mounts;
+    let mut items = walk_dir(store, root)?;
+    if root != store.root() || mounts.is_empty() {
+        return Ok(items);
     }
+    items.retain(|x| {
+        let first = x.name().split('/').next().unwrap_or_default();
+        let is_pass_file = matches!(x, StoreItem::Pass(name) if name == first);
+        is_pass_file || !mounts.contains_key(first)
+    });
+    for mount_root in mounts.values() {
+        items.extend(walk_dir(store, mount_root)?);
+    }
+    // stable, so directory goes before pass file with the same name
+    items.sort_by(|a, b| a.name().split('/').cmp(b.name().split('/')));
+    Ok(items)
 }
 
-pub fn init(subfolder: String, recipients: Vec<String>) {
-    dbg!(subfolder, recipients);
-    todo!()
+fn walk_dir(store: &Store, root: &Path) -> Result<Vec<StoreItem>> {
+    let entries = walkdir::WalkDir::new(root)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|x| x.depth() == 0 || !x.file_name().to_string_lossy().starts_with('.'));
+    let mut items = Vec::new();
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            // symlink to parent directory
+            Err(err) if err.loop_ancestor().is_some() => continue,
+            Err(err) => return Err(std::io::Error::from(err).into()),
+        };
+        if entry.file_type().is_dir() {
+            if let Some(name) = name_of(store, entry.path()).filter(|x| !x.is_empty()) {
+                items.push(StoreItem::Directory(name));
+            }
+        } else if let Some(pass_name) = pass_name_of(store, entry.path()) {
+            items.push(StoreItem::Pass(pass_name));
+        }
+    }
+    Ok(items)
+}
+
+/// All pass names under `root` in sorted order.
+/// Hidden files and directories (like `.git`) are skipped.
+pub fn get_pass_names(store: &Store, root: &Path) -> Result<Vec<String>> {
+    let pass_names = walk(store, root)?
+        .into_iter()
+        .filter_map(|x| match x {
+            StoreItem::Pass(pass_name) => Some(pass_name),
+            StoreItem::Directory(_) => None,
+        })
+        .collect();
+    Ok(pass_names)
+}
+
+/// Pass name of pass file at `path`, if it's in the store and has extension of some backend.
+#[must_use]
+pub fn pass_name_of(store: &Store, path: &Path) -> Option<String> {
+    store.backends().for_path(path)?;
+    name_of(store, &path.with_extension(""))
 }
 
-fn get_readonly_pass_file(pass_name: String) -> Result<File> {
-    check_uninitialized_store()?;
-    let path = get_pass_path(&pass_name);
+fn get_readonly_pass_file(store: &Store, pass_name: String) -> Result<File> {
+    check_uninitialized_store(store)?;
+    let path = get_pass_path(store, &pass_name);
 
     File::open(&path).map_err(|err| {
         let err = err.into();
         if let Error::IO(err) = err {
             if err.kind() == std::io::ErrorKind::NotFound {
-                Error::PassDoesNotExist { pass_name }
+                Error::PassDoesNotExist { pass_name, path }
             } else {
                 err.into()
             }
@@ -191,46 +355,234 @@ fn get_readonly_pass_file(pass_name: String) -> Result<File> {
         }
     })
 }
-pub fn get_pass_path(pass_name: &impl ToString) -> PathBuf {
-    PASS_DIR_ROOT.join(pass_name.to_string() + ".gpg")
+/// Path of existing pass file with extension of any backend,
+/// or path with extension of backend of its recipients (default one, if there are none).
+pub fn get_pass_path(store: &Store, pass_name: &impl ToString) -> PathBuf {
+    let pass_name = pass_name.to_string();
+    let path_with = |backend: &dyn CryptoBackend| {
+        store_path(store, &format!("{pass_name}.{}", backend.extension()))
+    };
+    store
+        .backends()
+        .all()
+        .into_iter()
+        .map(path_with)
+        .find(|x| x.exists())
+        .or_else(|| {
+            (!pass_name.is_empty())
+                .then(|| get_new_pass_path(store, &pass_name).ok())
+                .flatten()
+        })
+        .unwrap_or_else(|| path_with(store.backends().openpgp()))
+}
+/// Path where `pass_name` is written by backend of its recipients.
+pub fn get_new_pass_path(store: &Store, pass_name: &str) -> Result<PathBuf> {
+    let (backend, _) = get_recipients(store, pass_name)?;
+    Ok(store_path(
+        store,
+        &format!("{pass_name}.{}", backend.extension()),
+    ))
 }
 
-fn get_recipients(pass_name: &str) -> Result<Vec<String>> {
+/// Backend and recipients from the nearest recipients file in the store of `pass_name`.
+fn get_recipients<'a>(
+    store: &'a Store,
+    pass_name: &str,
+) -> Result<(&'a dyn CryptoBackend, Vec<String>)> {
     assert!(!pass_name.is_empty());
-    let mut path: PathBuf = PASS_DIR_ROOT.join(pass_name);
+    let dir = pass_name.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let (_, backend, recipients) = find_recipients_file(store, store_path(store, dir))?;
+    Ok((backend, recipients))
+}
+
+/// Recipients file used for pass files in `subfolder`, with its backend and recipients.
+pub fn get_recipients_file<'a>(
+    store: &'a Store,
+    subfolder: &str,
+) -> Result<(PathBuf, &'a dyn CryptoBackend, Vec<String>)> {
+    find_recipients_file(store, store_path(store, subfolder))
+}
+
+/// The nearest recipients file in `dir` or its parents up to the store root.
+fn find_recipients_file(
+    store: &Store,
+    mut path: PathBuf,
+) -> Result<(PathBuf, &dyn CryptoBackend, Vec<String>)> {
+    let root = store_root(store, &path);
     loop {
-        path = path.parent().unwrap().to_owned();
-        if let Ok(mut file) = File::open(path.join(".gpg-id")) {
-            let mut recipients = String::new();
-            file.read_to_string(&mut recipients)?;
-            let recipients = recipients.lines().map(|x| x.to_owned());
-            break Ok(recipients.collect::<Vec<_>>());
+        for backend in store.backends().all() {
+            let recipients_path = path.join(backend.recipients_file());
+            if let Ok(mut file) = File::open(&recipients_path) {
+                let mut recipients = String::new();
+                file.read_to_string(&mut recipients)?;
+                let recipients = recipients
+                    .lines()
+                    .map(str::trim)
+                    .filter(|x| !x.is_empty() && !x.starts_with('#'))
+                    .map(str::to_owned);
+                return Ok((recipients_path, backend, recipients.collect()));
+            }
         }
 
-        if path == *PASS_DIR_ROOT {
-            break Err(Error::PasswordStoreUninitialized);
+        match path.parent() {
+            Some(parent) if path != root => path = parent.to_owned(),
+            _ => break Err(Error::PasswordStoreUninitialized),
         }
     }
 }
 
-fn encrypt(pass_name: &str, content: &[u8]) -> Result<Vec<u8>> {
-    let mut gpg = gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?;
-    let recipients = get_recipients(pass_name)?;
-    let recipients = gpg
-        .find_keys(recipients)?
-        .collect::<std::result::Result<Vec<_>, _>>()?;
-    let content = {
-        let mut buf = Vec::new();
-        gpg.encrypt(&recipients, content, &mut buf)?;
-        buf
-    };
-    Ok(content)
+/// Encrypt `content` for recipients of `pass_name`.
+pub fn encrypt(store: &Store, pass_name: &str, content: &[u8]) -> Result<Vec<u8>> {
+    let (backend, recipients) = get_recipients(store, pass_name)?;
+    backend.encrypt(&recipients, content)
 }
 
-pub fn check_uninitialized_store() -> Result<()> {
-    if let Ok(Some(_)) = PASS_DIR_ROOT.read_dir().map(|mut x| x.next()) {
+pub fn check_uninitialized_store(store: &Store) -> Result<()> {
+    let has_files = matches!(store.root().read_dir().map(|mut x| x.next()), Ok(Some(_)));
+    if has_files || !store.config().mounts.is_empty() {
         Ok(())
     } else {
         Err(Error::PasswordStoreUninitialized)
     }
 }
+
+/// Pass names and subfolders are relative paths that stay in the store.
+pub fn check_pass_name(pass_name: &str) -> Result<()> {
+    let escapes = Path::new(pass_name).components().any(|x| {
+        matches!(
+            x,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    });
+    if escapes {
+        return Err(Error::InvalidPassName {
+            pass_name: pass_name.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::fixtures::age_store;
+
+    fn ciphertexts(store: &Store) -> BTreeMap<String, Vec<u8>> {
+        get_pass_names(store, store.root())
+            .unwrap()
+            .into_iter()
+            .map(|x| {
+                let content = std::fs::read(get_pass_path(store, &x)).unwrap();
+                (x, content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn init_reencrypts_only_changed_subfolder() {
+        let (_dir, mut store) = age_store();
+        for name in ["email", "team/server", "team/db/main", "other/server"] {
+            store.put(name, name.as_bytes()).unwrap();
+        }
+        store.commit("Add passwords.").unwrap();
+        let before = ciphertexts(&store);
+        let root_recipients = std::fs::read_to_string(store.root().join(".age-recipients"))
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        let colleague = age::x25519::Identity::generate();
+        let mut team_recipients = root_recipients.clone();
+        team_recipients.push(colleague.to_public().to_string());
+        let age = store.backends().age();
+
+        init(&store, "team", &team_recipients, age).unwrap();
+        let after = ciphertexts(&store);
+        for name in ["email", "other/server"] {
+            assert_eq!(before[name], after[name], "{name}");
+        }
+        for name in ["team/server", "team/db/main"] {
+            assert_ne!(before[name], after[name], "{name}");
+            assert_eq!(
+                age::decrypt(&colleague, &after[name]).unwrap(),
+                name.as_bytes()
+            );
+        }
+
+        // same recipients, nothing to reencrypt
+        init(&store, "team", &team_recipients, age).unwrap();
+        assert_eq!(ciphertexts(&store), after);
+
+        // empty id removes recipients file, subfolder uses root one again
+        init(&store, "team", &[String::new()], age).unwrap();
+        assert!(!store.root().join("team/.age-recipients").exists());
+        let removed = ciphertexts(&store);
+        assert_eq!(removed["email"], before["email"]);
+        assert!(age::decrypt(&colleague, &removed["team/server"]).is_err());
+        assert_eq!(store.get("team/server").unwrap(), b"team/server");
+    }
+
+    #[test]
+    fn init_skips_subfolders_with_own_recipients() {
+        let (_dir, mut store) = age_store();
+        let root_recipients = std::fs::read_to_string(store.root().join(".age-recipients"))
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        init(&store, "team/db", &root_recipients, store.backends().age()).unwrap();
+        for name in ["team/server", "team/db/main"] {
+            store.put(name, name.as_bytes()).unwrap();
+        }
+        store.commit("Add passwords.").unwrap();
+        let before = ciphertexts(&store);
+
+        let colleague = age::x25519::Identity::generate();
+        let mut team_recipients = root_recipients.clone();
+        team_recipients.push(colleague.to_public().to_string());
+        init(&store, "team", &team_recipients, store.backends().age()).unwrap();
+        let after = ciphertexts(&store);
+        assert_ne!(before["team/server"], after["team/server"]);
+        assert_eq!(before["team/db/main"], after["team/db/main"]);
+    }
+
+    #[test]
+    fn failed_init_changes_nothing() {
+        let (_dir, mut store) = age_store();
+        for name in ["team/server", "team/db/main"] {
+            store.put(name, name.as_bytes()).unwrap();
+        }
+        store.commit("Add passwords.").unwrap();
+        // encrypted for someone else, so it can't be reencrypted
+        let stranger = age::x25519::Identity::generate();
+        let ciphertext = store
+            .backends()
+            .age()
+            .encrypt(&[stranger.to_public().to_string()], b"foreign")
+            .unwrap();
+        std::fs::write(store.root().join("team/foreign.age"), ciphertext).unwrap();
+        let before = ciphertexts(&store);
+
+        let colleague = age::x25519::Identity::generate();
+        let recipients = [colleague.to_public().to_string()];
+        let result = init(&store, "team", &recipients, store.backends().age());
+        assert!(
+            matches!(result, Err(Error::ReadPass { ref pass_name, .. }) if pass_name == "team/foreign")
+        );
+        assert!(!store.root().join("team/.age-recipients").exists());
+        assert_eq!(ciphertexts(&store), before);
+    }
+
+    #[test]
+    fn missing_pass_has_extension_of_recipients() {
+        let (_dir, store) = age_store();
+        let path = store.root().join("email/work.age");
+        assert_eq!(get_pass_path(&store, &"email/work"), path);
+        assert!(matches!(
+            decrypt(&store, "email/work"),
+            Err(Error::PassDoesNotExist { path: x, .. }) if x == path
+        ));
+    }
+}
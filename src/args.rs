@@ -1,10 +1,22 @@
-use clap::{Parser, Subcommand};
+use std::{ffi::OsString, io::IsTerminal, path::PathBuf};
 
-use crate::{CLIP_TIME, DEFAULT_GENERATED_LENGTH, EDITOR_NAME};
+use clap::{Parser, Subcommand, ValueEnum};
+use spass::policy::CharClass;
+
+const CLIP_HELP: &str =
+    "Put it on the clipboard and clear board after PASSWORD_STORE_CLIP_TIME (45 by default) seconds.";
 
 #[derive(Parser, Debug)]
 #[command(version)]
 pub(crate) struct Args {
+    #[arg(
+        long,
+        global = true,
+        env = "PASSWORD_STORE_DIR",
+        value_name = "path",
+        help = "Password store location [default: ~/.password-store]"
+    )]
+    pub(crate) store: Option<PathBuf>,
     #[command(subcommand)]
     pub(crate) command: Command,
 }
@@ -22,13 +34,24 @@ pub(crate) enum Command {
             default_value = ""
         )]
         subfolder: String,
-        #[arg(required(true))]
+        #[arg(long, help = "Encrypt with age, gpg-ids are age recipients")]
+        age: bool,
+        #[arg(help = "GPG ids to encrypt for [default: `recipients` from config]")]
         gpg_ids: Vec<String>,
     },
+    #[command(about = "Show or change recipients of a folder, reencrypting only it.")]
+    Recipients {
+        #[command(subcommand)]
+        command: RecipientsCommand,
+    },
     #[command(visible_alias = "ls", about = "List passwords.")]
     List {
         #[arg(default_value = "")]
         subfolder: String,
+        #[command(flatten)]
+        format: FormatOptions,
+        #[command(flatten)]
+        tree: TreeArgs,
     },
     #[command(
         visible_alias = "search",
@@ -37,6 +60,15 @@ pub(crate) enum Command {
     Find {
         #[arg(required(true))]
         pass_names: Vec<String>,
+        #[arg(
+            long,
+            help = "Rank pass-names by fuzzy match like fzf, instead of tree of substring matches."
+        )]
+        fuzzy: bool,
+        #[command(flatten)]
+        format: FormatOptions,
+        #[command(flatten)]
+        tree: TreeArgs,
     },
     #[command(about = "Show existing password.")]
     Show {
@@ -45,23 +77,84 @@ pub(crate) enum Command {
             long = "clip",
             short = 'c',
             value_name = "line-number",
-            help = format!("Put it on the clipboard and clear board after {CLIP_TIME} seconds."),
+            conflicts_with_all = ["format", "json"],
+            help = CLIP_HELP,
         )]
         copy_line: Option<Option<usize>>,
-        // TODO: QRCode
-        //
-        // #[arg(
-        //     long("qrcode"),
-        //     short('q'),
-        //     value_name = "line-number",
-        //     help = format!("Put it on the clipboard and clear board after {CLIP_TIME} seconds."),
-        // )]
-        // qrcode_line: Option<Option<usize>>,
+        #[arg(
+            long = "qrcode",
+            short = 'q',
+            value_name = "line-number",
+            conflicts_with_all = ["copy_line", "format", "json"],
+            help = "Display it as a QR code."
+        )]
+        qrcode_line: Option<Option<usize>>,
+        #[arg(
+            long,
+            value_name = "name",
+            help = "Use value of `name: value` field instead of line (`password` is the first line)."
+        )]
+        field: Option<String>,
+        #[arg(
+            long,
+            value_enum,
+            requires_all = ["qrcode_line", "output"],
+            help = "Write QR code to image file instead of terminal."
+        )]
+        qrcode_format: Option<QrCodeFormat>,
+        #[arg(
+            long,
+            short,
+            value_name = "path",
+            requires = "qrcode_format",
+            help = "Where to write QR code image."
+        )]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        format: FormatOptions,
+    },
+    #[command(about = "Select password interactively with fuzzy search and show it.")]
+    Pick {
+        #[arg(default_value = "", help = "Initial search query.")]
+        query: String,
+        #[arg(long, short, help = CLIP_HELP)]
+        clip: bool,
+        #[arg(long, short, help = "Use OTP code instead of password.")]
+        otp: bool,
+    },
+    #[command(about = "Set `field: value` line of existing password, other lines are kept.")]
+    Set {
+        pass_name: String,
+        field: String,
+        value: String,
     },
     #[command(about = "Search for password files containing search-string when decrypted.")]
     Grep {
         search_string: String,
-        grep_options: Vec<String>,
+        #[command(flatten)]
+        options: GrepOptions,
+    },
+    #[command(
+        about = "Import passwords exported from other password managers, in one commit. Existing passwords are skipped."
+    )]
+    Import {
+        #[arg(value_enum)]
+        format: ImportFormat,
+        file: PathBuf,
+        #[command(flatten)]
+        options: ImportOptions,
+    },
+    #[command(about = "Export passwords for other password managers.")]
+    Export {
+        #[command(flatten)]
+        options: ExportOptions,
+    },
+    #[command(
+        about = "Report weak, reused and old passwords. Exits with failure if any are found."
+    )]
+    Audit {
+        #[command(flatten)]
+        options: AuditOptions,
     },
     #[command(visible_alias = "add", about = "Insert new password.")]
     Insert {
@@ -82,13 +175,15 @@ pub(crate) enum Command {
         force: bool,
         pass_name: String,
     },
-    #[command(about = format!("Insert a new password or edit an existing password using {}.", *EDITOR_NAME))]
+    #[command(
+        about = "Insert a new password or edit an existing password using editor from config or $EDITOR."
+    )]
     Edit { pass_name: String },
     #[command(about = "Generate a new password.")]
     Generate {
         #[arg(long, short)]
         no_symbols: bool,
-        #[arg(long, short, help = format!("Put it on the clipboard and clear board after {CLIP_TIME} seconds."))]
+        #[arg(long, short, help = CLIP_HELP)]
         clip: bool,
         #[arg(
             long,
@@ -105,8 +200,16 @@ pub(crate) enum Command {
         )]
         force: bool,
         pass_name: String,
-        #[arg(long, short, default_value = DEFAULT_GENERATED_LENGTH.to_string())]
-        length: usize,
+        #[arg(
+            long,
+            short,
+            help = "Password length [default: PASSWORD_STORE_GENERATED_LENGTH or 25]"
+        )]
+        length: Option<usize>,
+        #[command(flatten)]
+        passphrase: PassphraseArgs,
+        #[command(flatten)]
+        policy: PolicyArgs,
     },
     #[command(visible_aliases = ["rm", "delete"], about = "Remove existing password")]
     Remove {
@@ -137,7 +240,446 @@ pub(crate) enum Command {
         new_path: String,
     },
     #[command(
-        about = "If the password store is a git repository, execute a git command specified by git-command-args."
+        about = "Generate OTP code from otpauth:// URI stored in pass-name.",
+        args_conflicts_with_subcommands = true,
+        subcommand_negates_reqs = true
+    )]
+    Otp {
+        #[command(subcommand)]
+        command: Option<OtpCommand>,
+        #[arg(long, short, help = CLIP_HELP)]
+        clip: bool,
+        #[arg(required(true))]
+        pass_name: Option<String>,
+    },
+    #[command(about = "Get and set options in spass config files.")]
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    #[command(about = "Mount other stores as top-level folders, in user config.")]
+    Store {
+        #[command(subcommand)]
+        command: StoreCommand,
+    },
+    #[command(
+        about = "If the password store is a git repository, execute a git command specified by git-command-args. If the first argument is name of mounted store, the command runs in that store."
+    )]
+    Git {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        git_command_args: Vec<String>,
+    },
+    #[command(external_subcommand)]
+    Extension(Vec<OsString>),
+}
+
+#[derive(clap::Args, Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct GrepOptions {
+    #[arg(long, short, help = "Ignore case distinctions in patterns and data.")]
+    pub(crate) ignore_case: bool,
+    #[arg(
+        long,
+        short = 'E',
+        help = "Interpret search-string as extended regular expression instead of fixed string."
+    )]
+    pub(crate) extended_regexp: bool,
+    #[arg(long, short = 'v', help = "Select non-matching lines.")]
+    pub(crate) invert_match: bool,
+    #[arg(
+        long,
+        short,
+        help = "Print only a count of selected lines per password file."
+    )]
+    pub(crate) count: bool,
+    #[arg(
+        long,
+        short = 'n',
+        help = "Prefix each line of output with its line number."
+    )]
+    pub(crate) line_number: bool,
+    #[command(flatten)]
+    pub(crate) format: FormatOptions,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct AuditOptions {
+    #[arg(
+        long,
+        default_value_t = 3,
+        value_parser = clap::value_parser!(u8).range(0..=4),
+        help = "Passwords with lower zxcvbn score (0-4) are weak."
+    )]
+    pub(crate) min_score: u8,
+    #[arg(
+        long,
+        value_name = "days",
+        default_value_t = 365,
+        help = "Passwords not changed for more days are old. Age is taken from git log."
+    )]
+    pub(crate) max_age: i64,
+    #[arg(
+        long,
+        value_name = "path",
+        help = "Check passwords against local Pwned Passwords: file of `HASH:COUNT` lines sorted by hash or directory of range files."
+    )]
+    pub(crate) pwned: Option<PathBuf>,
+    #[cfg(feature = "hibp-api")]
+    #[arg(
+        long,
+        value_name = "url",
+        conflicts_with = "pwned",
+        help = "Check passwords against Pwned Passwords range API at url, like a self-hosted mirror. Only first 5 chars of password hashes are sent."
+    )]
+    pub(crate) hibp_url: Option<String>,
+    #[arg(long, short, help = "Also list passwords without issues.")]
+    pub(crate) all: bool,
+    #[command(flatten)]
+    pub(crate) format: FormatOptions,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ImportOptions {
+    #[arg(
+        long,
+        short = 'n',
+        help = "Only print pass names that would be imported."
+    )]
+    pub(crate) dry_run: bool,
+    #[arg(long, short, help = "Overwrite existing passwords.")]
+    pub(crate) force: bool,
+    #[arg(long, value_name = "folder", help = "Import into folder of the store.")]
+    pub(crate) prefix: Option<String>,
+    #[arg(
+        long = "column",
+        value_name = "field=header",
+        value_parser = parse_csv_column,
+        help = "Column of CSV for field (name, folder, password, login, url, otp, notes), found by header by default."
+    )]
+    pub(crate) columns: Vec<(CsvField, String)>,
+    #[arg(long, value_name = "path", help = "Keyfile of KeePass database.")]
+    pub(crate) keyfile: Option<PathBuf>,
+    #[arg(
+        long,
+        requires = "keyfile",
+        help = "Ask password of KeePass database also with keyfile."
+    )]
+    pub(crate) ask_password: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[allow(clippy::doc_markdown/* Reason: doc comments are help text, not markdown */)]
+pub(crate) enum ImportFormat {
+    /// KeePass database
+    Kdbx,
+    /// Unencrypted XML export of KeePass
+    KeepassXml,
+    /// Unencrypted JSON export of Bitwarden
+    Bitwarden,
+    /// Unencrypted 1PUX export of 1Password
+    #[value(name = "1pux")]
+    Onepux,
+    /// CSV with header, like exports of Chrome and Firefox
+    Csv,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ExportOptions {
+    #[arg(long, short = 'F', value_enum)]
+    pub(crate) format: ExportFormat,
+    #[arg(
+        long,
+        short,
+        value_name = "path",
+        help = "Write to file instead of stdout."
+    )]
+    pub(crate) output: Option<PathBuf>,
+    #[arg(
+        long,
+        short,
+        value_name = "folder",
+        help = "Export only folder or password."
+    )]
+    pub(crate) subfolder: Option<String>,
+    #[arg(
+        long = "recipient",
+        short,
+        value_name = "id",
+        help = "Encrypt output to OpenPGP key or age recipient (age1...)."
+    )]
+    pub(crate) recipients: Vec<String>,
+    #[arg(
+        long,
+        short,
+        conflicts_with = "recipients",
+        help = "Encrypt output with age passphrase. KeePass databases are always encrypted with passphrase."
+    )]
+    pub(crate) passphrase: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[allow(clippy::doc_markdown/* Reason: doc comments are help text, not markdown */)]
+pub(crate) enum ExportFormat {
+    /// CSV with a column for every field, can be imported back
+    Csv,
+    /// Pass names with content, like `show --json`
+    Json,
+    /// Unencrypted JSON export of Bitwarden
+    Bitwarden,
+    /// KeePass database
+    Kdbx,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum CsvField {
+    Name,
+    /// Folders separated by `/`
+    Folder,
+    Password,
+    Login,
+    Url,
+    Otp,
+    Notes,
+}
+
+fn parse_csv_column(value: &str) -> Result<(CsvField, String), String> {
+    let (field, header) = value
+        .split_once('=')
+        .ok_or("expected field=header".to_owned())?;
+    Ok((CsvField::from_str(field, true)?, header.to_owned()))
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct FormatOptions {
+    #[arg(
+        long,
+        value_enum,
+        help = "Output format [default: tree for list and find, plain otherwise]"
+    )]
+    format: Option<OutputFormat>,
+    #[arg(long, conflicts_with = "format", help = "Same as --format=json.")]
+    json: bool,
+}
+
+impl FormatOptions {
+    pub(crate) fn get(&self, default: OutputFormat) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.format.unwrap_or(default)
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    /// One pass name per line, or content as is.
+    Plain,
+    /// Pass names, directories and fields as JSON.
+    Json,
+    /// Tree of the store, like `pass`. Same as plain for show and grep.
+    Tree,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct PassphraseArgs {
+    #[arg(
+        long,
+        short,
+        value_name = "count",
+        conflicts_with_all = ["length", "no_symbols", "require", "exclude", "charset"],
+        help = "Generate passphrase of count random words instead of characters."
+    )]
+    pub(crate) words: Option<usize>,
+    #[arg(
+        long,
+        requires = "words",
+        help = "Between words [default: generator.separator or -]"
+    )]
+    pub(crate) separator: Option<String>,
+    #[arg(
+        long,
+        requires = "words",
+        help = "Uppercase the first letter of every word."
+    )]
+    pub(crate) capitalize: bool,
+    #[arg(
+        long,
+        requires = "words",
+        help = "Append a random digit to a random word."
+    )]
+    pub(crate) number: bool,
+    #[arg(
+        long,
+        value_name = "path",
+        requires = "words",
+        help = "Words file, one per line or EFF diceware format [default: generator.wordlist or embedded list]"
+    )]
+    pub(crate) wordlist: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct PolicyArgs {
+    #[arg(
+        long,
+        value_name = "classes",
+        value_delimiter = ',',
+        help = "Include at least one character of every class: upper, lower, digit, symbol."
+    )]
+    pub(crate) require: Vec<CharClass>,
+    #[arg(long, value_name = "chars", help = "Never use these characters.")]
+    pub(crate) exclude: Option<String>,
+    #[arg(
+        long,
+        value_name = "spec",
+        conflicts_with = "no_symbols",
+        help = "Characters to use in `tr` syntax, like `[:alnum:]_-` [default: PASSWORD_STORE_CHARACTER_SET]"
+    )]
+    pub(crate) charset: Option<String>,
+    #[arg(long, help = "Ignore `.spass-policy` files.")]
+    pub(crate) no_policy: bool,
+}
+
+/// Options of built-in tree renderer, external programs ignore them.
+#[derive(clap::Args, Debug)]
+pub(crate) struct TreeArgs {
+    #[arg(
+        long,
+        short = 'L',
+        value_name = "depth",
+        help = "Descend only depth directories deep."
+    )]
+    pub(crate) level: Option<usize>,
+    #[arg(long, short, help = "Show hidden files and directories.")]
+    pub(crate) all: bool,
+    #[arg(long, help = "List directories before passwords.")]
+    pub(crate) dirs_first: bool,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "when",
+        default_value = "auto",
+        help = "Colorize directories, symlinks and fuzzy matches."
+    )]
+    pub(crate) color: ColorWhen,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub(crate) enum ColorWhen {
+    /// If stdout is a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorWhen {
+    pub(crate) fn enabled(self) -> bool {
+        match self {
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+            ColorWhen::Auto => {
+                std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub(crate) enum QrCodeFormat {
+    Png,
+    Svg,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum OtpCommand {
+    #[command(visible_alias = "add", about = "Insert new otpauth:// URI.")]
+    Insert {
+        #[arg(long, short, help = "Echo the URI back to the console during entry.")]
+        echo: bool,
+        #[arg(
+            long,
+            short,
+            conflicts_with = "append",
+            help = "Don't prompt before overwriting existing password."
+        )]
+        force: bool,
+        #[arg(
+            long,
+            short,
+            help = "Add URI to existing password instead of creating new one."
+        )]
+        append: bool,
+        pass_name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum ConfigCommand {
+    #[command(about = "Print effective value of key, like `generator.length`.")]
+    Get { key: String },
+    #[command(about = "Write key to user config, or to store config with --local.")]
+    Set {
+        #[arg(long, help = "Use `.spass.toml` in password store.")]
+        local: bool,
+        key: String,
+        value: String,
+    },
+    #[command(visible_alias = "ls", about = "Print all effective values.")]
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum RecipientsCommand {
+    #[command(
+        visible_alias = "ls",
+        about = "Print effective recipients of folder or password, with their keys."
+    )]
+    List {
+        #[arg(
+            long = "path",
+            short = 'p',
+            value_name = "subfolder",
+            default_value = ""
+        )]
+        subfolder: String,
+        #[command(flatten)]
+        format: FormatOptions,
+    },
+    #[command(about = "Add recipients to folder, it gets own recipients file if needed.")]
+    Add {
+        #[arg(
+            long = "path",
+            short = 'p',
+            value_name = "subfolder",
+            default_value = ""
+        )]
+        subfolder: String,
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    #[command(
+        visible_alias = "rm",
+        about = "Remove recipients from folder, it gets own recipients file if needed."
     )]
-    Git { git_command_args: Vec<String> },
+    Remove {
+        #[arg(
+            long = "path",
+            short = 'p',
+            value_name = "subfolder",
+            default_value = ""
+        )]
+        subfolder: String,
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum StoreCommand {
+    #[command(about = "Mount store at path as folder name, it keeps its own git repository.")]
+    Add { name: String, path: PathBuf },
+    #[command(visible_alias = "rm", about = "Unmount store, its files are kept.")]
+    Remove { name: String },
+    #[command(visible_alias = "ls", about = "Print mounted stores.")]
+    List,
 }
@@ -0,0 +1,375 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use spass::{api, git, Entry, Error, Result, Store};
+
+use crate::{
+    args::{AuditOptions, OutputFormat},
+    hibp::Pwned,
+    output,
+};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Issue {
+    /// zxcvbn score is below `--min-score`.
+    Weak,
+    /// The same password is in other entries.
+    Reused,
+    /// Password is in Pwned Passwords from `--pwned` or `--hibp-url`.
+    Pwned,
+    /// Not changed for more than `--max-age` days.
+    Old,
+    /// Can't be decrypted or isn't UTF-8.
+    Unreadable,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    name: String,
+    /// zxcvbn score from 0 to 4, if entry is readable.
+    score: Option<u8>,
+    reused_with: Vec<String>,
+    /// Time of the last commit that changed the file, if store is a git repository.
+    last_changed: Option<i64>,
+    /// How many times password was seen in breaches, if it was checked.
+    pwned: Option<u64>,
+    issues: Vec<Issue>,
+}
+
+/// Check password of every entry and write report to `out`, only hash of password
+/// is kept after that. Entries without password and symlinks to seen files are skipped.
+/// Entries that can't be read are reported with a warning.
+///
+/// # Returns
+/// Whether some password has issues.
+pub fn audit(out: &mut impl Write, store: &Store, options: &AuditOptions) -> Result<bool> {
+    let changes = last_changes(store)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| i64::try_from(x.as_secs()).unwrap_or(i64::MAX));
+    let pwned_passwords = match &options.pwned {
+        Some(path) => Some(Pwned::open(path)?),
+        #[cfg(feature = "hibp-api")]
+        None => options.hibp_url.as_deref().map(Pwned::api),
+        #[cfg(not(feature = "hibp-api"))]
+        None => None,
+    };
+
+    let mut reports = Vec::new();
+    let mut hashes = HashMap::<_, Vec<usize>>::new();
+    // the same file can be reached through symlinks
+    let mut seen_files = HashSet::new();
+    for pass_name in api::get_pass_names(store, store.root())? {
+        let path = api::get_pass_path(store, &pass_name);
+        if !seen_files.insert(path.canonicalize()?) {
+            continue;
+        }
+        let root = api::store_root(store, &path);
+        let last_changed = path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|x| changes.get(root)?.get(x).copied());
+        let entry = match store.get_entry(&pass_name) {
+            Ok(entry) => entry,
+            // like grep, one unreadable file doesn't stop the audit
+            Err(err) => {
+                eprintln!("Warning: can't read {pass_name}: {err}");
+                reports.push(Report {
+                    name: pass_name,
+                    score: None,
+                    reused_with: Vec::new(),
+                    last_changed,
+                    pwned: None,
+                    issues: vec![Issue::Unreadable],
+                });
+                continue;
+            }
+        };
+        let password = entry.password();
+        if password.is_empty() {
+            continue;
+        }
+        let strength = score(&pass_name, &entry);
+        let pwned = pwned_passwords
+            .as_ref()
+            .map(|x| x.count(password))
+            .transpose()?;
+        hashes
+            .entry(Sha256::digest(password.as_bytes()))
+            .or_default()
+            .push(reports.len());
+        let mut issues = Vec::new();
+        if strength < options.min_score {
+            issues.push(Issue::Weak);
+        }
+        if pwned.is_some_and(|x| x > 0) {
+            issues.push(Issue::Pwned);
+        }
+        if last_changed.is_some_and(|x| (now - x) / SECONDS_PER_DAY > options.max_age) {
+            issues.push(Issue::Old);
+        }
+        reports.push(Report {
+            name: pass_name,
+            score: Some(strength),
+            reused_with: Vec::new(),
+            last_changed,
+            pwned,
+            issues,
+        });
+    }
+    for indices in hashes.values().filter(|x| x.len() > 1) {
+        for &i in indices {
+            reports[i].reused_with = indices
+                .iter()
+                .filter(|x| **x != i)
+                .map(|x| reports[*x].name.clone())
+                .collect();
+            let issues = &mut reports[i].issues;
+            // keep order of `Issue`
+            issues.insert(
+                usize::from(issues.first() == Some(&Issue::Weak)),
+                Issue::Reused,
+            );
+        }
+    }
+
+    let total = reports.len();
+    if !options.all {
+        reports.retain(|x| !x.issues.is_empty());
+    }
+    let has_issues = reports.iter().any(|x| !x.issues.is_empty());
+    if options.format.get(OutputFormat::Plain) == OutputFormat::Json {
+        output::write_json(out, &reports)?;
+    } else {
+        print_table(out, &reports, now)?;
+        let with_issues = reports.iter().filter(|x| !x.issues.is_empty()).count();
+        writeln!(out, "{with_issues} of {total} passwords have issues.")?;
+    }
+    Ok(has_issues)
+}
+
+/// Time of the last change of every file, by root of its store.
+/// Stores that are not git repositories are skipped.
+fn last_changes(store: &Store) -> Result<HashMap<&Path, HashMap<PathBuf, i64>>> {
+    // mounted stores have their own repositories
+    let mut changes = HashMap::new();
+    let roots = store.config().mounts.values().map(|x| x.as_path());
+    for root in std::iter::once(store.root()).chain(roots) {
+        match git::last_changes(root.as_os_str()) {
+            Ok(x) => {
+                changes.insert(root, x);
+            }
+            Err(Error::PassStoreShouldBeGitRepo) => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(changes)
+}
+
+/// zxcvbn score, parts of pass name and fields (like login) are known to attacker.
+fn score(pass_name: &str, entry: &Entry) -> u8 {
+    let user_inputs = pass_name
+        .split(|x: char| !x.is_alphanumeric())
+        .chain(entry.fields().map(|(_, value)| value))
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>();
+    zxcvbn::zxcvbn(entry.password(), &user_inputs)
+        .score()
+        .into()
+}
+
+fn print_table(out: &mut impl Write, reports: &[Report], now: i64) -> Result<()> {
+    let width = reports
+        .iter()
+        .map(|x| x.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("NAME".len());
+    writeln!(out, "{:width$}  SCORE  AGE     ISSUES", "NAME")?;
+    for report in reports {
+        let age = report.last_changed.map_or("?".to_owned(), |x| {
+            format!("{}d", (now - x) / SECONDS_PER_DAY)
+        });
+        let mut issues = report
+            .issues
+            .iter()
+            .map(|x| match x {
+                Issue::Weak => "weak".to_owned(),
+                Issue::Reused => format!("reused with {}", report.reused_with.join(", ")),
+                Issue::Pwned => format!("pwned {} times", report.pwned.unwrap_or(0)),
+                Issue::Old => "old".to_owned(),
+                Issue::Unreadable => "unreadable".to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        if issues.is_empty() {
+            issues.push('-');
+        }
+        let score = report.score.map_or("?".to_owned(), |x| x.to_string());
+        writeln!(out, "{:width$}  {score}/4    {age:7} {issues}", report.name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::fixtures::{age_store, git_init};
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        options: AuditOptions,
+    }
+
+    const STRONG: &str = "correct-Horse-battery-staple-9182!";
+
+    fn store() -> (tempfile::TempDir, Store) {
+        let (dir, mut store) = age_store();
+        git_init(store.root());
+        store.put("email/work", b"hunter2\nlogin: me\n").unwrap();
+        store.put("bank", b"hunter2\n").unwrap();
+        store
+            .put("strong", format!("{STRONG}\n").as_bytes())
+            .unwrap();
+        store.put("no-password", b"\nlogin: me\n").unwrap();
+        store.commit("Add passwords.").unwrap();
+        (dir, store)
+    }
+
+    /// Reports from `spass audit --json args` by name, and whether there are issues.
+    fn audit_json(store: &Store, args: &[&str]) -> (HashMap<String, serde_json::Value>, bool) {
+        let cli = Cli::parse_from(["audit", "--json"].iter().chain(args));
+        let mut out = Vec::new();
+        let has_issues = audit(&mut out, store, &cli.options).unwrap();
+        let reports: Vec<serde_json::Value> = serde_json::from_slice(&out).unwrap();
+        let reports = reports
+            .into_iter()
+            .map(|x| (x["name"].as_str().unwrap().to_owned(), x))
+            .collect();
+        (reports, has_issues)
+    }
+
+    #[test]
+    fn weak_and_reused() {
+        let (_dir, store) = store();
+        let (reports, has_issues) = audit_json(&store, &[]);
+        assert!(has_issues);
+        assert_eq!(reports.len(), 2);
+        assert_eq!(
+            reports["bank"]["issues"],
+            serde_json::json!(["weak", "reused"])
+        );
+        assert_eq!(
+            reports["bank"]["reused_with"],
+            serde_json::json!(["email/work"])
+        );
+        assert_eq!(
+            reports["email/work"]["reused_with"],
+            serde_json::json!(["bank"])
+        );
+        assert!(reports["bank"]["score"].as_u64().unwrap() < 3);
+
+        // strong enough for lower limit, still reused
+        let (reports, _) = audit_json(&store, &["--min-score", "0"]);
+        assert_eq!(reports["bank"]["issues"], serde_json::json!(["reused"]));
+    }
+
+    #[test]
+    fn json_shape() {
+        let (_dir, store) = store();
+        let (reports, _) = audit_json(&store, &["--all"]);
+        // entries without password are skipped
+        assert_eq!(reports.len(), 3);
+        let strong = &reports["strong"];
+        let last_changed = strong["last_changed"].as_i64().unwrap();
+        assert_eq!(
+            *strong,
+            serde_json::json!({
+                "name": "strong",
+                "score": 4,
+                "reused_with": [],
+                "last_changed": last_changed,
+                "pwned": null,
+                "issues": [],
+            })
+        );
+    }
+
+    #[test]
+    fn age_from_git() {
+        let (_dir, mut store) = store();
+        // last commit that changed bank.age is old
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(store.root())
+            .args([
+                "commit",
+                "-q",
+                "--amend",
+                "--no-edit",
+                "--date=2000-01-01T00:00:00Z",
+            ])
+            .env("GIT_COMMITTER_DATE", "2000-01-01T00:00:00Z")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        store
+            .put("strong", format!("{STRONG}\n").as_bytes())
+            .unwrap();
+        store.commit("Change strong.").unwrap();
+
+        let (reports, _) = audit_json(&store, &["--all", "--min-score", "0"]);
+        assert_eq!(reports["bank"]["last_changed"], 946_684_800);
+        assert_eq!(
+            reports["bank"]["issues"],
+            serde_json::json!(["reused", "old"])
+        );
+        assert_eq!(reports["strong"]["issues"], serde_json::json!([]));
+        assert!(reports["strong"]["last_changed"].as_i64().unwrap() > 946_684_800);
+
+        // not a git repository, age is unknown
+        std::fs::remove_dir_all(store.root().join(".git")).unwrap();
+        let (reports, _) = audit_json(&store, &["--all"]);
+        assert_eq!(reports["bank"]["last_changed"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn unreadable_entries_are_reported() {
+        let (_dir, store) = store();
+        std::fs::write(store.root().join("broken.age"), b"not age").unwrap();
+        let (reports, has_issues) = audit_json(&store, &["--min-score", "0"]);
+        assert!(has_issues);
+        assert_eq!(
+            reports["broken"]["issues"],
+            serde_json::json!(["unreadable"])
+        );
+        assert_eq!(reports["broken"]["score"], serde_json::Value::Null);
+        assert!(reports.contains_key("bank"));
+    }
+
+    #[test]
+    fn table() {
+        let (_dir, store) = store();
+        let cli = Cli::parse_from(["audit", "--min-score", "0"]);
+        let mut out = Vec::new();
+        audit(&mut out, &store, &cli.options).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "NAME        SCORE  AGE     ISSUES");
+        assert!(lines[1].starts_with("bank        "));
+        assert!(lines[1].ends_with("0d      reused with email/work"));
+        assert_eq!(lines[3], "2 of 3 passwords have issues.");
+    }
+}
@@ -0,0 +1,747 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{generate::expand_charset, Error, Result};
+
+/// Name of per-store config file in the store root.
+pub const STORE_CONFIG_NAME: &str = ".spass.toml";
+
+/// X selection for clipboard operations, see `PASSWORD_STORE_X_SELECTION`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    Clipboard,
+    Primary,
+    Secondary,
+}
+
+/// Program used by `list` and `find`, built-in renderer by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeBackend {
+    /// Try eza, tree and then built-in renderer, for output exactly like pass
+    Auto,
+    Eza,
+    Tree,
+    /// Built-in renderer, doesn't need any program
+    #[default]
+    Builtin,
+}
+
+/// Implementation of `OpenPGP` for directories with `.gpg-id`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenPgpBackend {
+    /// Requires `gpgme` feature
+    Gpgme,
+    /// Requires `sequoia` feature
+    Sequoia,
+}
+
+impl OpenPgpBackend {
+    /// Backend is enabled in this build.
+    #[must_use]
+    pub fn is_available(self) -> bool {
+        match self {
+            OpenPgpBackend::Gpgme => cfg!(feature = "gpgme"),
+            OpenPgpBackend::Sequoia => cfg!(feature = "sequoia"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Seconds before clipboard is cleared.
+    pub clip_time: u64,
+    pub generated_length: usize,
+    /// In `tr` syntax, see [`expand_charset`].
+    pub character_set: String,
+    /// In `tr` syntax, see [`expand_charset`].
+    pub character_set_no_symbols: String,
+    /// Between words of generated passphrase.
+    pub passphrase_separator: String,
+    /// Wordlist file for passphrases, `None` means embedded list.
+    /// Can't be set in store config, like other local files.
+    pub wordlist: Option<PathBuf>,
+    /// Additional options for places where `gpg` binary is used (e.g. git diff).
+    pub gpg_opts: Vec<String>,
+    pub umask: u32,
+    pub x_selection: Selection,
+    /// Can't be set in store config, so store content can't run programs.
+    pub editor: String,
+    pub tree_backend: TreeBackend,
+    /// Commit every change to the store, if it's a git repository.
+    pub git_auto_commit: bool,
+    /// `None` means use `pass.signcommits` from git config.
+    /// Can't be set in store config, so store content can't make spass sign with user keys.
+    pub git_sign_commits: Option<bool>,
+    /// Used by `init` when gpg-ids are not specified.
+    pub default_recipients: Vec<String>,
+    /// Identities file for age backend.
+    /// Can't be set in store config, so store content can't point spass at local files.
+    pub age_identities: PathBuf,
+    pub openpgp_backend: OpenPgpBackend,
+    /// Key file or directory with key files (`.asc`, `.pgp`, `.key`) for sequoia backend.
+    /// Can't be set in store config, like other local files.
+    pub openpgp_keyring: PathBuf,
+    /// Run `.extensions/*.bash` of the store and `spass-*` programs for unknown commands.
+    ///
+    /// Extensions of the store are run as they are: unlike pass with
+    /// `PASSWORD_STORE_SIGNING_KEY`, their signatures are not checked,
+    /// so enable them only for stores whose content you trust.
+    /// Can be set only in user config or with `PASSWORD_STORE_ENABLE_EXTENSIONS`,
+    /// so store content can't enable them.
+    pub enable_extensions: bool,
+    /// Stores mounted as top-level folders, name to root.
+    /// Can be set only in user config, so store content can't redirect writes.
+    pub mounts: BTreeMap<String, PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            clip_time: 45,
+            generated_length: 25,
+            character_set: "[:punct:][:alnum:]".to_owned(),
+            character_set_no_symbols: "[:alnum:]".to_owned(),
+            passphrase_separator: "-".to_owned(),
+            wordlist: None,
+            gpg_opts: Vec::new(),
+            umask: 0o077,
+            x_selection: Selection::Clipboard,
+            editor: "vi".to_owned(),
+            tree_backend: TreeBackend::default(),
+            git_auto_commit: true,
+            git_sign_commits: None,
+            default_recipients: Vec::new(),
+            age_identities: std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".passage").join("identities"))
+                .unwrap_or_default(),
+            openpgp_backend: if cfg!(feature = "gpgme") {
+                OpenPgpBackend::Gpgme
+            } else {
+                OpenPgpBackend::Sequoia
+            },
+            openpgp_keyring: std::env::var_os("GNUPGHOME")
+                .filter(|x| !x.is_empty())
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".gnupg")))
+                .unwrap_or_default(),
+            enable_extensions: false,
+            mounts: BTreeMap::new(),
+        }
+    }
+}
+
+/// Content of `config.toml` and `.spass.toml`.
+/// Every field is optional, so files can override each other.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clip_time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree_backend: Option<TreeBackend>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipients: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable_extensions: Option<bool>,
+    #[serde(default)]
+    pub generator: GeneratorConfig,
+    #[serde(default)]
+    pub git: GitConfig,
+    #[serde(default)]
+    pub age: AgeConfig,
+    #[serde(default)]
+    pub openpgp: OpenPgpConfig,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mounts: BTreeMap<String, PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratorConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub character_set: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub character_set_no_symbols: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separator: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wordlist: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GitConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_commit: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sign_commits: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgeConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identities: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpenPgpConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend: Option<OpenPgpBackend>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyring: Option<PathBuf>,
+}
+
+impl Config {
+    /// Config of store at `root` from all sources, from lowest to highest priority:
+    /// defaults, user config, store config, environment.
+    pub fn load(root: &Path) -> Result<Self> {
+        let mut config = Self::default();
+        if let Some(path) = user_config_path() {
+            config.apply_file(&path)?;
+        }
+        config.apply_file(&store_config_path(root))?;
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    /// Check values that can't be checked by their types,
+    /// config from [`Config::load`] is always valid.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |key: &str, reason: &str| Error::InvalidConfigValue {
+            key: key.to_owned(),
+            reason: reason.to_owned(),
+        };
+        if self.generated_length == 0 {
+            return Err(invalid("generator.length", "should be positive"));
+        }
+        expand_charset(&self.character_set)
+            .map_err(|reason| invalid("generator.character_set", reason))?;
+        expand_charset(&self.character_set_no_symbols)
+            .map_err(|reason| invalid("generator.character_set_no_symbols", reason))?;
+        if !self.openpgp_backend.is_available() {
+            return Err(invalid("openpgp.backend", "is not enabled in this build"));
+        }
+        if self.umask > 0o777 {
+            return Err(invalid("umask", "should be octal umask"));
+        }
+        Ok(())
+    }
+
+    /// Override values with ones from config file at `path`, if it exists.
+    pub fn apply_file(&mut self, path: &Path) -> Result<()> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let file =
+            toml::from_str::<FileConfig>(&content).map_err(|err| Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason: err.to_string(),
+            })?;
+        check_file_config(path, &file)
+            .and_then(|()| self.apply_file_config(file))
+            .map_err(|reason| Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason,
+            })
+    }
+
+    fn apply_file_config(&mut self, file: FileConfig) -> std::result::Result<(), String> {
+        if let Some(clip_time) = file.clip_time {
+            if clip_time == 0 {
+                return Err("clip_time should be positive".to_owned());
+            }
+            self.clip_time = clip_time;
+        }
+        if let Some(editor) = file.editor {
+            self.editor = editor;
+        }
+        if let Some(tree_backend) = file.tree_backend {
+            self.tree_backend = tree_backend;
+        }
+        if let Some(recipients) = file.recipients {
+            self.default_recipients = recipients;
+        }
+        if let Some(enable_extensions) = file.enable_extensions {
+            self.enable_extensions = enable_extensions;
+        }
+        if let Some(length) = file.generator.length {
+            if length == 0 {
+                return Err("generator.length should be positive".to_owned());
+            }
+            self.generated_length = length;
+        }
+        if let Some(character_set) = file.generator.character_set {
+            expand_charset(&character_set)
+                .map_err(|reason| format!("generator.character_set {reason}"))?;
+            self.character_set = character_set;
+        }
+        if let Some(character_set) = file.generator.character_set_no_symbols {
+            expand_charset(&character_set)
+                .map_err(|reason| format!("generator.character_set_no_symbols {reason}"))?;
+            self.character_set_no_symbols = character_set;
+        }
+        if let Some(separator) = file.generator.separator {
+            self.passphrase_separator = separator;
+        }
+        if file.generator.wordlist.is_some() {
+            self.wordlist = file.generator.wordlist;
+        }
+        if let Some(auto_commit) = file.git.auto_commit {
+            self.git_auto_commit = auto_commit;
+        }
+        if file.git.sign_commits.is_some() {
+            self.git_sign_commits = file.git.sign_commits;
+        }
+        if let Some(identities) = file.age.identities {
+            self.age_identities = identities;
+        }
+        if let Some(backend) = file.openpgp.backend {
+            if !backend.is_available() {
+                return Err(format!(
+                    "openpgp.backend {backend:?} is not enabled in this build"
+                ));
+            }
+            self.openpgp_backend = backend;
+        }
+        if let Some(keyring) = file.openpgp.keyring {
+            self.openpgp_keyring = keyring;
+        }
+        for (name, root) in file.mounts {
+            check_mount_name(&name).map_err(|reason| format!("mounts.{name} {reason}"))?;
+            if !root.is_absolute() {
+                return Err(format!("mounts.{name} should be absolute path"));
+            }
+            self.mounts.insert(name, root);
+        }
+        Ok(())
+    }
+
+    /// Override values with ones from standard pass environment variables.
+    pub fn apply_env(&mut self) -> Result<()> {
+        self.apply_vars(|name| std::env::var_os(name))
+    }
+
+    /// Like [`Config::apply_env`], but variables are from `get_var`.
+    fn apply_vars(&mut self, get_var: impl Fn(&str) -> Option<OsString>) -> Result<()> {
+        let env_var = |name| env_var(name, get_var(name));
+        if let Some(value) = env_var("PASSWORD_STORE_CLIP_TIME")? {
+            self.clip_time = parse_positive("PASSWORD_STORE_CLIP_TIME", &value)?;
+        }
+        if let Some(value) = env_var("PASSWORD_STORE_GENERATED_LENGTH")? {
+            self.generated_length = parse_positive("PASSWORD_STORE_GENERATED_LENGTH", &value)?;
+        }
+        if let Some(value) = env_var("PASSWORD_STORE_CHARACTER_SET")? {
+            self.character_set = parse_charset("PASSWORD_STORE_CHARACTER_SET", value)?;
+        }
+        if let Some(value) = env_var("PASSWORD_STORE_CHARACTER_SET_NO_SYMBOLS")? {
+            self.character_set_no_symbols =
+                parse_charset("PASSWORD_STORE_CHARACTER_SET_NO_SYMBOLS", value)?;
+        }
+        if let Some(value) = env_var("PASSWORD_STORE_GPG_OPTS")? {
+            // pass doesn't quote it too
+            self.gpg_opts = value.split_whitespace().map(str::to_owned).collect();
+        }
+        if let Some(value) = env_var("PASSWORD_STORE_UMASK")? {
+            self.umask = u32::from_str_radix(&value, 8)
+                .ok()
+                .filter(|x| *x <= 0o777)
+                .ok_or_else(|| invalid("PASSWORD_STORE_UMASK", &value, "should be octal umask"))?;
+        }
+        if let Some(value) = env_var("PASSWORD_STORE_X_SELECTION")? {
+            self.x_selection = match value.as_str() {
+                "clipboard" => Selection::Clipboard,
+                "primary" => Selection::Primary,
+                "secondary" => Selection::Secondary,
+                _ => {
+                    return Err(invalid(
+                        "PASSWORD_STORE_X_SELECTION",
+                        &value,
+                        "should be one of clipboard, primary, secondary",
+                    ))
+                }
+            };
+        }
+        if let Some(value) = env_var("PASSWORD_STORE_ENABLE_EXTENSIONS")? {
+            self.enable_extensions = parse_bool("PASSWORD_STORE_ENABLE_EXTENSIONS", &value)?;
+        }
+        if let Some(value) = env_var("PASSAGE_IDENTITIES_FILE")? {
+            self.age_identities = PathBuf::from(value);
+        }
+        if let Some(value) = env_var("EDITOR")? {
+            self.editor = value;
+        }
+        Ok(())
+    }
+
+    /// Values that can be set in config files, as [`FileConfig`].
+    #[must_use]
+    pub fn to_file_config(&self) -> FileConfig {
+        FileConfig {
+            clip_time: Some(self.clip_time),
+            editor: Some(self.editor.clone()),
+            tree_backend: Some(self.tree_backend),
+            recipients: Some(self.default_recipients.clone()),
+            enable_extensions: Some(self.enable_extensions),
+            generator: GeneratorConfig {
+                length: Some(self.generated_length),
+                character_set: Some(self.character_set.clone()),
+                character_set_no_symbols: Some(self.character_set_no_symbols.clone()),
+                separator: Some(self.passphrase_separator.clone()),
+                wordlist: self.wordlist.clone(),
+            },
+            git: GitConfig {
+                auto_commit: Some(self.git_auto_commit),
+                sign_commits: self.git_sign_commits,
+            },
+            age: AgeConfig {
+                identities: Some(self.age_identities.clone()),
+            },
+            openpgp: OpenPgpConfig {
+                backend: Some(self.openpgp_backend),
+                keyring: Some(self.openpgp_keyring.clone()),
+            },
+            mounts: self.mounts.clone(),
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/spass/config.toml` or `~/.config/spass/config.toml`.
+#[must_use]
+pub fn user_config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|config_dir| config_dir.join("spass").join("config.toml"))
+}
+
+/// Config file of store at `root`.
+#[must_use]
+pub fn store_config_path(root: &Path) -> PathBuf {
+    root.join(STORE_CONFIG_NAME)
+}
+
+/// Effective `config` as `(key, value)` pairs, keys are dotted like `git.auto_commit`.
+#[allow(clippy::missing_panics_doc/* Reason: `FileConfig` is always serializable */)]
+#[must_use]
+pub fn list(config: &Config) -> Vec<(String, String)> {
+    let table = toml::Table::try_from(config.to_file_config()).unwrap();
+    let mut pairs = Vec::new();
+    flatten_table(&table, "", &mut pairs);
+    pairs
+}
+
+fn flatten_table(table: &toml::Table, prefix: &str, pairs: &mut Vec<(String, String)>) {
+    for (key, value) in table {
+        let key = prefix.to_owned() + key;
+        if let toml::Value::Table(table) = value {
+            flatten_table(table, &(key + "."), pairs);
+        } else {
+            pairs.push((key, value.to_string()));
+        }
+    }
+}
+
+/// Set dotted `key` to `value` in config file at `path`, keeping its formatting.
+/// `value` is parsed as TOML value, if it's not valid TOML it's used as string.
+/// File isn't changed, if result is not valid config.
+pub fn set(path: &Path, key: &str, value: &str) -> Result<()> {
+    let value = value
+        .parse::<toml_edit::Value>()
+        .unwrap_or_else(|_| value.into());
+    edit(path, key, |document| {
+        let segments = key.split('.').collect::<Vec<_>>();
+        let (last, parents) = segments
+            .split_last()
+            .ok_or_else(|| "empty key".to_owned())?;
+        let mut table = document.as_table_mut();
+        for segment in parents {
+            table = table
+                .entry(segment)
+                .or_insert_with(toml_edit::table)
+                .as_table_mut()
+                .ok_or_else(|| format!("{segment} is not a table"))?;
+        }
+        table.insert(last, toml_edit::value(value));
+        Ok(())
+    })
+}
+
+/// Mount store at `root` as `name` in config file at `path`,
+/// or unmount it, if `root` is `None`.
+pub fn set_mount(path: &Path, name: &str, root: Option<&Path>) -> Result<()> {
+    let key = format!("mounts.{name}");
+    check_mount_name(name).map_err(|reason| Error::InvalidConfigValue {
+        key: key.clone(),
+        reason: reason.to_owned(),
+    })?;
+    edit(path, &key, |document| {
+        let mounts = document
+            .entry("mounts")
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+            .ok_or_else(|| "mounts is not a table".to_owned())?;
+        match root {
+            Some(root) => {
+                let root = root
+                    .to_str()
+                    .ok_or_else(|| "path should be valid unicode".to_owned())?;
+                mounts.insert(name, toml_edit::value(root));
+            }
+            None => {
+                mounts
+                    .remove(name)
+                    .ok_or_else(|| "store is not mounted".to_owned())?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Change config file at `path` with `change`, keeping its formatting.
+/// File isn't changed, if result is not valid config.
+/// Errors are reported for `key`.
+fn edit(
+    path: &Path,
+    key: &str,
+    change: impl FnOnce(&mut toml_edit::DocumentMut) -> std::result::Result<(), String>,
+) -> Result<()> {
+    let invalid_value = |reason: String| Error::InvalidConfigValue {
+        key: key.to_owned(),
+        reason,
+    };
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let mut document =
+        content
+            .parse::<toml_edit::DocumentMut>()
+            .map_err(|err| Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason: err.to_string(),
+            })?;
+    change(&mut document).map_err(invalid_value)?;
+
+    let new_content = document.to_string();
+    let file = toml::from_str::<FileConfig>(&new_content)
+        .map_err(|err| invalid_value(err.message().to_owned()))?;
+    check_file_config(path, &file)
+        .and_then(|()| Config::default().apply_file_config(file))
+        .map_err(invalid_value)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, new_content)?;
+    Ok(())
+}
+
+/// Store config comes with store content, so it can't set keys that run
+/// programs, sign with user keys, redirect writes to other stores
+/// or point spass at local files.
+fn check_file_config(path: &Path, file: &FileConfig) -> std::result::Result<(), String> {
+    if path.file_name() != Some(STORE_CONFIG_NAME.as_ref()) {
+        return Ok(());
+    }
+    let user_only = [
+        ("editor", file.editor.is_some()),
+        ("enable_extensions", file.enable_extensions.is_some()),
+        ("git.sign_commits", file.git.sign_commits.is_some()),
+        ("mounts", !file.mounts.is_empty()),
+        ("age.identities", file.age.identities.is_some()),
+        ("openpgp.keyring", file.openpgp.keyring.is_some()),
+        ("generator.wordlist", file.generator.wordlist.is_some()),
+    ];
+    match user_only.iter().find(|(_, is_set)| *is_set) {
+        Some((key, _)) => Err(format!("{key} can be set only in user config")),
+        None => Ok(()),
+    }
+}
+
+/// Mount name is a single pass name component.
+fn check_mount_name(name: &str) -> std::result::Result<(), &'static str> {
+    if name.is_empty() || name.starts_with('.') || name.contains('/') {
+        Err("should be a folder name without `/` and not starting with `.`")
+    } else {
+        Ok(())
+    }
+}
+
+/// Empty variable is the same as unset one.
+fn env_var(name: &'static str, value: Option<OsString>) -> Result<Option<String>> {
+    match value.map(OsString::into_string) {
+        Some(Ok(value)) if value.is_empty() => Ok(None),
+        Some(Ok(value)) => Ok(Some(value)),
+        None => Ok(None),
+        Some(Err(value)) => Err(invalid(
+            name,
+            &value.to_string_lossy(),
+            "should be valid unicode",
+        )),
+    }
+}
+
+fn parse_positive<T: std::str::FromStr + Default + PartialOrd>(
+    name: &'static str,
+    value: &str,
+) -> Result<T> {
+    value
+        .parse()
+        .ok()
+        .filter(|x| *x > T::default())
+        .ok_or_else(|| invalid(name, value, "should be positive integer"))
+}
+
+fn parse_bool(name: &'static str, value: &str) -> Result<bool> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(invalid(name, value, "should be true or false")),
+    }
+}
+
+fn parse_charset(name: &'static str, value: String) -> Result<String> {
+    match expand_charset(&value) {
+        Ok(_) => Ok(value),
+        Err(reason) => Err(invalid(name, &value, reason)),
+    }
+}
+
+fn invalid(name: &'static str, value: &str, reason: &str) -> Error {
+    Error::InvalidEnvVar {
+        name,
+        value: value.to_owned(),
+        reason: reason.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_config_rejects_user_only_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let store_config = dir.path().join(STORE_CONFIG_NAME);
+        let user_config = dir.path().join("config.toml");
+        for content in [
+            "editor = \"sh\"",
+            "enable_extensions = true",
+            "[git]\nsign_commits = true",
+            "[mounts]\nwork = \"/tmp/work\"",
+            "[age]\nidentities = \"/home/user/.ssh/id_ed25519\"",
+            "[openpgp]\nkeyring = \"/home/user/.gnupg\"",
+            "[generator]\nwordlist = \"/etc/passwd\"",
+        ] {
+            std::fs::write(&store_config, content).unwrap();
+            std::fs::write(&user_config, content).unwrap();
+            let err = Config::default().apply_file(&store_config).unwrap_err();
+            assert!(err.to_string().contains("only in user config"), "{err}");
+            Config::default().apply_file(&user_config).unwrap();
+        }
+
+        std::fs::remove_file(&store_config).unwrap();
+        assert!(set(&store_config, "editor", "sh").is_err());
+        assert!(!store_config.exists());
+        set(&store_config, "clip_time", "10").unwrap();
+    }
+
+    /// Config with variables from `vars` applied.
+    fn with_vars(vars: &[(&str, &str)]) -> Result<Config> {
+        let mut config = Config::default();
+        config.apply_vars(|name| {
+            vars.iter()
+                .find(|(x, _)| *x == name)
+                .map(|(_, value)| value.into())
+        })?;
+        Ok(config)
+    }
+
+    #[test]
+    fn env_values_are_parsed() {
+        let config = with_vars(&[
+            ("PASSWORD_STORE_CLIP_TIME", "10"),
+            ("PASSWORD_STORE_GENERATED_LENGTH", "32"),
+            ("PASSWORD_STORE_CHARACTER_SET", "a-z"),
+            ("PASSWORD_STORE_GPG_OPTS", "--armor  --no-tty"),
+            ("PASSWORD_STORE_UMASK", "027"),
+            ("PASSWORD_STORE_X_SELECTION", "primary"),
+            ("PASSWORD_STORE_ENABLE_EXTENSIONS", "true"),
+            // empty is the same as unset
+            ("PASSWORD_STORE_CHARACTER_SET_NO_SYMBOLS", ""),
+        ])
+        .unwrap();
+        assert_eq!(config.clip_time, 10);
+        assert_eq!(config.generated_length, 32);
+        assert_eq!(config.character_set, "a-z");
+        assert_eq!(
+            config.character_set_no_symbols,
+            Config::default().character_set_no_symbols
+        );
+        assert_eq!(config.gpg_opts, ["--armor", "--no-tty"]);
+        assert_eq!(config.umask, 0o027);
+        assert_eq!(config.x_selection, Selection::Primary);
+        assert!(config.enable_extensions);
+    }
+
+    #[test]
+    fn invalid_env_values_are_errors() {
+        for (name, value) in [
+            ("PASSWORD_STORE_CLIP_TIME", "0"),
+            ("PASSWORD_STORE_CLIP_TIME", "-5"),
+            ("PASSWORD_STORE_CLIP_TIME", "ten"),
+            ("PASSWORD_STORE_GENERATED_LENGTH", "0"),
+            ("PASSWORD_STORE_GENERATED_LENGTH", "1.5"),
+            ("PASSWORD_STORE_CHARACTER_SET", "z-a"),
+            ("PASSWORD_STORE_UMASK", "999"),
+            ("PASSWORD_STORE_UMASK", "01000"),
+            ("PASSWORD_STORE_X_SELECTION", "middle"),
+            ("PASSWORD_STORE_ENABLE_EXTENSIONS", "yes"),
+            ("PASSWORD_STORE_ENABLE_EXTENSIONS", "1"),
+        ] {
+            let err = with_vars(&[(name, value)]).unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidEnvVar { name: x, value: ref y, .. } if x == name && y == value),
+                "{name}={value}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn env_overrides_store_and_user_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let user_config = dir.path().join("config.toml");
+        let store_config = dir.path().join(STORE_CONFIG_NAME);
+        std::fs::write(
+            &user_config,
+            "clip_time = 10\n[generator]\nlength = 12\nseparator = \" \"",
+        )
+        .unwrap();
+        std::fs::write(&store_config, "clip_time = 20\n[generator]\nlength = 16").unwrap();
+        let mut config = Config::default();
+        config.apply_file(&user_config).unwrap();
+        config.apply_file(&store_config).unwrap();
+        config
+            .apply_vars(|name| (name == "PASSWORD_STORE_CLIP_TIME").then(|| "30".into()))
+            .unwrap();
+        assert_eq!(config.clip_time, 30);
+        assert_eq!(config.generated_length, 16);
+        assert_eq!(config.passphrase_separator, " ");
+    }
+}
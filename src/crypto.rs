@@ -0,0 +1,160 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::{Config, OpenPgpBackend},
+    Result,
+};
+
+mod age;
+#[cfg(feature = "gpgme")]
+mod gpg;
+#[cfg(feature = "sequoia")]
+mod sequoia;
+
+#[cfg(not(any(feature = "gpgme", feature = "sequoia")))]
+compile_error!("at least one of `gpgme` and `sequoia` features is required");
+
+pub use self::age::Age;
+#[cfg(feature = "gpgme")]
+pub use self::gpg::Gpg;
+#[cfg(feature = "sequoia")]
+pub use self::sequoia::Sequoia;
+
+/// Key that a recipient from recipients file is resolved to.
+#[derive(Debug, Clone, Serialize)]
+pub struct Recipient {
+    /// As written in recipients file.
+    pub id: String,
+    /// Ids of keys, that content is encrypted to for this recipient.
+    pub key_ids: Vec<String>,
+    /// Owner of the key, if backend knows it.
+    pub name: Option<String>,
+    /// Fingerprint of the primary key, if backend has one.
+    pub fingerprint: Option<String>,
+    /// Unix time when the key expires, `None` if it doesn't or backend doesn't know.
+    pub expires: Option<i64>,
+}
+
+/// Encryption of pass files.
+///
+/// Every backend has its own recipients file (like `.gpg-id`) and extension
+/// of pass files, so stores can mix backends in different directories.
+pub trait CryptoBackend: Sync + std::fmt::Debug {
+    /// Human readable name of recipients, for messages.
+    fn name(&self) -> &'static str;
+    /// Name of file with recipients, one per line.
+    fn recipients_file(&self) -> &'static str;
+    /// Extension of pass files, without dot.
+    fn extension(&self) -> &'static str;
+    /// Find keys of `recipients`.
+    /// Fails if some recipient has no key.
+    fn resolve_recipients(&self, recipients: &[String]) -> Result<Vec<Recipient>>;
+    fn encrypt(&self, recipients: &[String], plaintext: &[u8]) -> Result<Vec<u8>>;
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>>;
+    /// Key ids `ciphertext` is encrypted to, or `None` if it can't be known.
+    fn encrypted_to(&self, ciphertext: &[u8]) -> Result<Option<HashSet<String>>>;
+}
+
+/// Backends of a store, configured by its [`Config`].
+#[derive(Debug)]
+pub struct Backends {
+    openpgp: Box<dyn CryptoBackend>,
+    age: Age,
+}
+
+impl Backends {
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        Self {
+            openpgp: openpgp(config),
+            age: Age::new(config.age_identities.clone()),
+        }
+    }
+    /// Backend for `.gpg-id`, see [`Config::openpgp_backend`].
+    #[must_use]
+    pub fn openpgp(&self) -> &dyn CryptoBackend {
+        self.openpgp.as_ref()
+    }
+    #[must_use]
+    pub fn age(&self) -> &Age {
+        &self.age
+    }
+    /// All backends, the first one is default.
+    #[must_use]
+    pub fn all(&self) -> [&dyn CryptoBackend; 2] {
+        [self.openpgp(), &self.age]
+    }
+    /// Backend of pass file at `path`, by its extension.
+    #[must_use]
+    pub fn for_path(&self, path: &Path) -> Option<&dyn CryptoBackend> {
+        let extension = path.extension()?;
+        self.all().into_iter().find(|x| extension == x.extension())
+    }
+}
+
+/// Backend for `.gpg-id`, see [`Config::openpgp_backend`].
+#[must_use]
+pub fn openpgp(config: &Config) -> Box<dyn CryptoBackend> {
+    match config.openpgp_backend {
+        #[cfg(feature = "gpgme")]
+        OpenPgpBackend::Gpgme => Box::new(Gpg),
+        #[cfg(feature = "sequoia")]
+        OpenPgpBackend::Sequoia => Box::new(Sequoia::new(config.openpgp_keyring.clone())),
+        #[allow(unreachable_patterns/* Reason: reachable when one of features is disabled */)]
+        _ => unreachable!("openpgp backend is validated on config load"),
+    }
+}
+
+/// Armored detached `OpenPGP` signature of `data` by `signer` or default key.
+pub fn sign_detached(config: &Config, data: &[u8], signer: Option<&str>) -> Result<String> {
+    match config.openpgp_backend {
+        #[cfg(feature = "gpgme")]
+        OpenPgpBackend::Gpgme => gpg::sign_detached(data, signer),
+        #[cfg(feature = "sequoia")]
+        OpenPgpBackend::Sequoia => {
+            Sequoia::new(config.openpgp_keyring.clone()).sign_detached(data, signer)
+        }
+        #[allow(unreachable_patterns/* Reason: reachable when one of features is disabled */)]
+        _ => unreachable!("openpgp backend is validated on config load"),
+    }
+}
+
+/// `ciphertext` is not encrypted exactly for `recipients`,
+/// or `None` if backend can't tell it.
+pub fn needs_reencryption(
+    backend: &dyn CryptoBackend,
+    ciphertext: &[u8],
+    recipients: &[String],
+) -> Result<Option<bool>> {
+    let Some(key_ids) = backend.encrypted_to(ciphertext)? else {
+        return Ok(None);
+    };
+    Ok(Some(!is_encrypted_for(
+        &backend.resolve_recipients(recipients)?,
+        &key_ids,
+    )))
+}
+
+fn unix_time(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(x) => i64::try_from(x.as_secs()).unwrap_or(i64::MAX),
+        Err(err) => i64::try_from(err.duration().as_secs()).map_or(i64::MIN, |x| -x),
+    }
+}
+
+/// Every recipient has a key among `key_ids` and every id from
+/// `key_ids` belongs to one of recipients.
+fn is_encrypted_for(recipients: &[Recipient], key_ids: &HashSet<String>) -> bool {
+    recipients
+        .iter()
+        .all(|recipient| recipient.key_ids.iter().any(|x| key_ids.contains(x)))
+        && key_ids
+            .iter()
+            .all(|id| recipients.iter().any(|x| x.key_ids.contains(id)))
+}
@@ -0,0 +1,94 @@
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use super::{CryptoBackend, Recipient};
+use crate::{Error, Result};
+
+/// [age](https://age-encryption.org) with X25519 keys, compatible with passage.
+#[derive(Debug, Clone)]
+pub struct Age {
+    identities: PathBuf,
+}
+
+impl Age {
+    /// Decrypt with identities from `identities` file,
+    /// see [`Config::age_identities`](crate::config::Config::age_identities).
+    #[must_use]
+    pub fn new(identities: PathBuf) -> Self {
+        Self { identities }
+    }
+    fn parse_recipients(recipients: &[String]) -> Result<Vec<age::x25519::Recipient>> {
+        recipients
+            .iter()
+            .map(|recipient| {
+                recipient
+                    .parse::<age::x25519::Recipient>()
+                    .map_err(|reason| Error::InvalidAgeRecipient {
+                        recipient: recipient.clone(),
+                        reason,
+                    })
+            })
+            .collect()
+    }
+    fn identities(&self) -> Result<Vec<Box<dyn age::Identity>>> {
+        let path = &self.identities;
+        let file = age::IdentityFile::from_file(path.to_string_lossy().into_owned()).map_err(
+            |source| Error::AgeIdentities {
+                path: path.clone(),
+                source,
+            },
+        )?;
+        Ok(file.into_identities()?)
+    }
+}
+
+impl CryptoBackend for Age {
+    fn name(&self) -> &'static str {
+        "age recipients"
+    }
+    fn recipients_file(&self) -> &'static str {
+        ".age-recipients"
+    }
+    fn extension(&self) -> &'static str {
+        "age"
+    }
+    fn resolve_recipients(&self, recipients: &[String]) -> Result<Vec<Recipient>> {
+        let resolved = Self::parse_recipients(recipients)?
+            .into_iter()
+            .zip(recipients)
+            .map(|(key, recipient)| Recipient {
+                id: recipient.clone(),
+                key_ids: vec![key.to_string()],
+                name: None,
+                fingerprint: None,
+                expires: None,
+            })
+            .collect();
+        Ok(resolved)
+    }
+    fn encrypt(&self, recipients: &[String], plaintext: &[u8]) -> Result<Vec<u8>> {
+        let recipients = Self::parse_recipients(recipients)?;
+        let encryptor =
+            age::Encryptor::with_recipients(recipients.iter().map(|x| x as &dyn age::Recipient))?;
+        let mut buf = Vec::new();
+        let mut writer = encryptor.wrap_output(&mut buf)?;
+        writer.write_all(plaintext)?;
+        writer.finish()?;
+        Ok(buf)
+    }
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let identities = self.identities()?;
+        let mut reader = age::Decryptor::new_buffered(ciphertext)?
+            .decrypt(identities.iter().map(|x| x.as_ref()))?;
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+    /// X25519 recipients are anonymous, so it's unknown.
+    fn encrypted_to(&self, _ciphertext: &[u8]) -> Result<Option<HashSet<String>>> {
+        Ok(None)
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+
+use super::{unix_time, CryptoBackend, Recipient};
+use crate::{Error, Result};
+
+/// `OpenPGP` through gpgme, compatible with pass.
+#[derive(Debug, Clone, Copy)]
+pub struct Gpg;
+
+impl Gpg {
+    fn context() -> Result<gpgme::Context> {
+        Ok(gpgme::Context::from_protocol(gpgme::Protocol::OpenPgp)?)
+    }
+    fn find_keys(gpg: &mut gpgme::Context, recipients: &[String]) -> Result<Vec<gpgme::Key>> {
+        let keys = gpg
+            .find_keys(recipients)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(keys)
+    }
+}
+
+/// See [`super::sign_detached`].
+pub(super) fn sign_detached(data: &[u8], signer: Option<&str>) -> Result<String> {
+    let mut gpg = Gpg::context()?;
+    gpg.set_armor(true);
+    if let Some(signer) = signer {
+        let key = gpg.get_secret_key(signer)?;
+        gpg.add_signer(&key)?;
+    }
+    let mut signature = Vec::new();
+    gpg.sign_detached(data, &mut signature)?;
+    Ok(String::from_utf8(signature)?)
+}
+
+impl CryptoBackend for Gpg {
+    fn name(&self) -> &'static str {
+        "GPG id"
+    }
+    fn recipients_file(&self) -> &'static str {
+        ".gpg-id"
+    }
+    fn extension(&self) -> &'static str {
+        "gpg"
+    }
+    fn resolve_recipients(&self, recipients: &[String]) -> Result<Vec<Recipient>> {
+        let mut gpg = Self::context()?;
+        let mut resolved = Vec::new();
+        for recipient in recipients {
+            let keys = Self::find_keys(&mut gpg, std::slice::from_ref(recipient))?;
+            if keys.is_empty() {
+                return Err(Error::RecipientKeyNotFound {
+                    recipient: recipient.clone(),
+                });
+            }
+            resolved.extend(keys.iter().map(|key| {
+                Recipient {
+                    id: recipient.clone(),
+                    key_ids: key
+                        .subkeys()
+                        .filter(|x| x.can_encrypt())
+                        .filter_map(|x| x.id().ok().map(str::to_owned))
+                        .collect(),
+                    name: key
+                        .user_ids()
+                        .next()
+                        .and_then(|x| x.id().ok().map(str::to_owned)),
+                    fingerprint: key.fingerprint().ok().map(str::to_owned),
+                    expires: key
+                        .primary_key()
+                        .and_then(|x| x.expiration_time())
+                        .map(unix_time),
+                }
+            }));
+        }
+        Ok(resolved)
+    }
+    fn encrypt(&self, recipients: &[String], plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut gpg = Self::context()?;
+        let keys = Self::find_keys(&mut gpg, recipients)?;
+        let mut buf = Vec::new();
+        gpg.encrypt(&keys, plaintext, &mut buf)?;
+        Ok(buf)
+    }
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        Self::context()?.decrypt(ciphertext, &mut buf)?;
+        Ok(buf)
+    }
+    fn encrypted_to(&self, ciphertext: &[u8]) -> Result<Option<HashSet<String>>> {
+        Ok(pkesk_key_ids(ciphertext))
+    }
+}
+
+/// Key ids from public-key encrypted session key packets at the start of binary
+/// `OpenPGP` message, so recipients are known without decryption.
+/// `None` for armored message, anonymous recipient or symmetric encryption.
+fn pkesk_key_ids(mut message: &[u8]) -> Option<HashSet<String>> {
+    const PKESK: u8 = 1;
+    const SKESK: u8 = 3;
+    const MARKER: u8 = 10;
+    let mut key_ids = HashSet::new();
+    loop {
+        let (tag, body, rest) = next_packet(message)?;
+        match tag {
+            PKESK => {
+                key_ids.insert(pkesk_key_id(body)?);
+            }
+            MARKER => (),
+            SKESK => return None,
+            // encrypted data follows session keys
+            _ => return Some(key_ids),
+        }
+        message = rest;
+    }
+}
+
+/// Tag, body and the rest after the first packet of `data`.
+/// `None` for invalid or partial length packet, session keys are never partial.
+fn next_packet(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&header, data) = data.split_first()?;
+    if header & 0x80 == 0 {
+        return None;
+    }
+    let be = |bytes: &[u8]| bytes.iter().fold(0, |len, &x| len << 8 | usize::from(x));
+    let (tag, len, data) = if header & 0x40 == 0 {
+        // old format
+        let size = match header & 0x03 {
+            0 => 1,
+            1 => 2,
+            2 => 4,
+            _ => return None,
+        };
+        let len = be(data.get(..size)?);
+        ((header >> 2) & 0x0f, len, &data[size..])
+    } else {
+        match *data.first()? {
+            x @ 0..=191 => (header & 0x3f, usize::from(x), &data[1..]),
+            x @ 192..=223 => {
+                let len = (usize::from(x) - 192) << 8 | usize::from(*data.get(1)?);
+                (header & 0x3f, len + 192, &data[2..])
+            }
+            255 => (header & 0x3f, be(data.get(1..5)?), &data[5..]),
+            _ => return None,
+        }
+    };
+    let body = data.get(..len)?;
+    Some((tag, body, &data[len..]))
+}
+
+/// Key id of the recipient of PKESK packet with `body`, like gpgme prints it.
+fn pkesk_key_id(body: &[u8]) -> Option<String> {
+    let key_id = match *body.first()? {
+        3 => body.get(1..9)?,
+        6 => {
+            let recipient = body.get(2..2 + usize::from(*body.get(1)?))?;
+            match recipient.split_first()? {
+                (4, fingerprint) if fingerprint.len() == 20 => &fingerprint[12..],
+                (6, fingerprint) if fingerprint.len() == 32 => &fingerprint[..8],
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    // wildcard key id of anonymous recipient
+    if key_id.iter().all(|x| *x == 0) {
+        return None;
+    }
+    Some(data_encoding::HEXUPPER.encode(key_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_ID: [u8; 8] = [0x2d, 0xee, 0xb9, 0xbd, 0x87, 0x8c, 0x95, 0x88];
+
+    /// New format packet with `tag` and `body`.
+    fn packet(tag: u8, body: &[u8]) -> Vec<u8> {
+        let len = u8::try_from(body.len()).unwrap();
+        [&[0xc0 | tag, len], body].concat()
+    }
+
+    fn v3_pkesk(key_id: &[u8]) -> Vec<u8> {
+        // version, key id, algorithm, truncated session key
+        [&[3], key_id, &[1, 0x0c, 0x00]].concat()
+    }
+
+    fn ids(ids: &[&str]) -> HashSet<String> {
+        ids.iter().map(|x| (*x).to_owned()).collect()
+    }
+
+    #[test]
+    fn key_ids_of_gpg_message() {
+        // like gpg writes it: old format with 2 bytes length
+        let mut body = v3_pkesk(&KEY_ID);
+        body.resize(0x18c, 0xaa);
+        let second = [0x71, 0x6c, 0x4e, 0x38, 0x0d, 0xbf, 0xeb, 0xd6];
+        let message = [
+            &[0x85, 0x01, 0x8c][..],
+            &body,
+            &packet(1, &v3_pkesk(&second)),
+            &[0xd2, 0xff, 0, 0, 0, 3, 1, 0xbb, 0xcc],
+        ]
+        .concat();
+        assert_eq!(
+            pkesk_key_ids(&message),
+            Some(ids(&["2DEEB9BD878C9588", "716C4E380DBFEBD6"]))
+        );
+    }
+
+    #[test]
+    fn key_id_of_v6_pkesk() {
+        let fingerprint = [[0x11; 12].as_slice(), &KEY_ID].concat();
+        let body = [&[6, 21, 4][..], &fingerprint, &[1, 0x0c]].concat();
+        let message = [packet(1, &body), packet(18, &[1])].concat();
+        assert_eq!(pkesk_key_ids(&message), Some(ids(&["2DEEB9BD878C9588"])));
+    }
+
+    #[test]
+    fn unknown_recipients() {
+        let seipd = packet(18, &[1]);
+        let anonymous = [packet(1, &v3_pkesk(&[0; 8])), seipd.clone()].concat();
+        assert_eq!(pkesk_key_ids(&anonymous), None);
+        let symmetric = [
+            packet(1, &v3_pkesk(&KEY_ID)),
+            packet(3, &[4, 9, 3]),
+            seipd.clone(),
+        ]
+        .concat();
+        assert_eq!(pkesk_key_ids(&symmetric), None);
+        assert_eq!(pkesk_key_ids(b"-----BEGIN PGP MESSAGE-----\n"), None);
+        let truncated = [packet(1, &v3_pkesk(&KEY_ID))[..6].to_vec(), seipd].concat();
+        assert_eq!(pkesk_key_ids(&truncated), None);
+    }
+}
@@ -0,0 +1,601 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, OnceLock, PoisonError},
+};
+
+use sequoia_openpgp::{
+    cert::{prelude::ValidErasedKeyAmalgamation, CertParser},
+    crypto::{KeyPair, SessionKey},
+    packet::{key, Key, PKESK, SKESK},
+    parse::{
+        stream::{DecryptionHelper, DecryptorBuilder, MessageStructure, VerificationHelper},
+        PacketParser, PacketParserResult, Parse,
+    },
+    policy::StandardPolicy,
+    serialize::stream::{Armorer, Encryptor, LiteralWriter, Message, Signer},
+    types::SymmetricAlgorithm,
+    Cert, Fingerprint, KeyHandle, KeyID, Packet,
+};
+
+use super::{unix_time, CryptoBackend, Recipient};
+use crate::{Error, Result};
+
+static POLICY: StandardPolicy = StandardPolicy::new();
+/// Extensions of key files in keyring directory.
+const KEY_EXTENSIONS: [&str; 3] = ["asc", "pgp", "key"];
+
+type EncryptionKey<'a> = ValidErasedKeyAmalgamation<'a, key::PublicParts>;
+
+/// `OpenPGP` in pure Rust, reads and writes the same files as gpgme backend.
+///
+/// Keys are read from keyring: a key file or a directory with key files
+/// (`.asc`, `.pgp` or `.key`, like keys exported to `GNUPGHOME`).
+/// Passphrases of secret keys are asked on terminal, once per key.
+pub struct Sequoia {
+    keyring: PathBuf,
+    /// Keyring is read on first use.
+    certs: OnceLock<Vec<Cert>>,
+    /// Key pairs unlocked with passphrase, by fingerprint of key.
+    unlocked: Mutex<HashMap<Fingerprint, KeyPair>>,
+    /// Asks passphrase, replaced in tests.
+    prompt: fn(&str) -> std::io::Result<String>,
+}
+
+impl std::fmt::Debug for Sequoia {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sequoia")
+            .field("keyring", &self.keyring)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Sequoia {
+    /// See [`Config::openpgp_keyring`](crate::config::Config::openpgp_keyring).
+    #[must_use]
+    pub fn new(keyring: PathBuf) -> Self {
+        Self {
+            keyring,
+            certs: OnceLock::new(),
+            unlocked: Mutex::new(HashMap::new()),
+            prompt: |x| rpassword::prompt_password(x),
+        }
+    }
+    /// Certificates of keyring, it is read once.
+    fn keyring(&self) -> Result<&[Cert]> {
+        if let Some(certs) = self.certs.get() {
+            return Ok(certs);
+        }
+        let certs = self.read_keyring()?;
+        Ok(self.certs.get_or_init(|| certs))
+    }
+    /// All certificates from keyring, public and secret parts are merged.
+    /// In directory only files with [`KEY_EXTENSIONS`] are read,
+    /// any of them that isn't a key is an error.
+    fn read_keyring(&self) -> Result<Vec<Cert>> {
+        let path = &self.keyring;
+        let mut certs = HashMap::<Fingerprint, Cert>::new();
+        let mut read = |path: &Path| -> Result<()> {
+            let keyring_error = |err: anyhow::Error| Error::Keyring {
+                path: path.to_owned(),
+                reason: format!("{err:#}"),
+            };
+            for cert in CertParser::from_file(path).map_err(keyring_error)? {
+                let cert = cert.map_err(keyring_error)?;
+                let cert = match certs.remove(&cert.fingerprint()) {
+                    Some(existing) => existing
+                        .merge_public_and_secret(cert)
+                        .map_err(keyring_error)?,
+                    None => cert,
+                };
+                certs.insert(cert.fingerprint(), cert);
+            }
+            Ok(())
+        };
+        if path.is_dir() {
+            let mut paths = std::fs::read_dir(path)?
+                .map(|x| x.map(|x| x.path()))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            paths.sort();
+            // other files (like `pubring.kbx` of gpg) are not keys
+            let is_key_file = |path: &&PathBuf| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .and_then(|x| x.to_str())
+                        .is_some_and(|x| KEY_EXTENSIONS.contains(&x))
+            };
+            for path in paths.iter().filter(is_key_file) {
+                read(path)?;
+            }
+        } else {
+            read(path)?;
+        }
+        let mut certs = certs.into_values().collect::<Vec<_>>();
+        certs.sort_by_key(|x| x.fingerprint());
+        Ok(certs)
+    }
+    /// Certificates for `recipient` with their encryption keys,
+    /// like gpg finds them: by key id, fingerprint or part of user id.
+    fn find_certs<'a>(
+        certs: &'a [Cert],
+        recipient: &str,
+    ) -> Result<Vec<(&'a Cert, Vec<EncryptionKey<'a>>)>> {
+        let handle = recipient.trim_start_matches("0x").parse::<KeyHandle>().ok();
+        let recipient_lowercase = recipient.to_lowercase();
+        let found = certs
+            .iter()
+            .filter(|cert| {
+                let matches_handle = handle.as_ref().is_some_and(|handle| {
+                    cert.keys().any(|x| x.key().key_handle().aliases(handle))
+                });
+                matches_handle
+                    || cert.userids().any(|x| {
+                        String::from_utf8_lossy(x.userid().value())
+                            .to_lowercase()
+                            .contains(&recipient_lowercase)
+                    })
+            })
+            .map(|cert| (cert, encryption_keys(cert)))
+            .filter(|(_, keys)| !keys.is_empty())
+            .collect::<Vec<_>>();
+        if found.is_empty() {
+            return Err(Error::RecipientKeyNotFound {
+                recipient: recipient.to_owned(),
+            });
+        }
+        Ok(found)
+    }
+}
+
+fn encryption_keys(cert: &Cert) -> Vec<EncryptionKey<'_>> {
+    cert.keys()
+        .with_policy(&POLICY, None)
+        .supported()
+        .alive()
+        .revoked(false)
+        .for_storage_encryption()
+        .for_transport_encryption()
+        .collect()
+}
+
+fn name(cert: &Cert) -> Option<String> {
+    cert.userids()
+        .next()
+        .map(|x| String::from_utf8_lossy(x.userid().value()).into_owned())
+}
+
+type SecretKey = Key<key::SecretParts, key::UnspecifiedRole>;
+
+impl Sequoia {
+    fn unlocked(&self) -> MutexGuard<'_, HashMap<Fingerprint, KeyPair>> {
+        self.unlocked.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+    /// Key pair of `key` without asking passphrase:
+    /// its secret isn't encrypted or it was unlocked before.
+    fn cached_keypair(&self, key: &SecretKey) -> Option<KeyPair> {
+        if key.has_unencrypted_secret() {
+            return key.clone().into_keypair().ok();
+        }
+        self.unlocked().get(&key.fingerprint()).cloned()
+    }
+    /// Ask passphrase for `key`, if its secret is encrypted and it isn't unlocked yet.
+    fn unlock(&self, cert: &Cert, key: SecretKey) -> Result<KeyPair> {
+        if let Some(keypair) = self.cached_keypair(&key) {
+            return Ok(keypair);
+        }
+        let passphrase = (self.prompt)(&format!(
+            "Passphrase for {} ({}): ",
+            name(cert).unwrap_or_default(),
+            key.keyid()
+        ))?;
+        let keypair = key
+            .decrypt_secret(&passphrase.into())
+            .and_then(Key::into_keypair)
+            .map_err(openpgp_error)?;
+        self.unlocked()
+            .insert(keypair.public().fingerprint(), keypair.clone());
+        Ok(keypair)
+    }
+}
+
+/// Errors of this crate keep their type, others are described by `reason`.
+fn openpgp_error(err: anyhow::Error) -> Error {
+    match err.downcast::<Error>() {
+        Ok(err) => err,
+        Err(err) => Error::OpenPgp {
+            reason: format!("{err:#}"),
+        },
+    }
+}
+
+impl Sequoia {
+    /// See [`super::sign_detached`].
+    pub(super) fn sign_detached(&self, data: &[u8], signer: Option<&str>) -> Result<String> {
+        let certs = self.keyring()?;
+        let signer_lowercase = signer.map(str::to_lowercase);
+        let (cert, key) = certs
+            .iter()
+            .filter(|cert| {
+                signer_lowercase.as_ref().is_none_or(|signer| {
+                    cert.fingerprint().to_hex().to_lowercase().ends_with(signer)
+                        || cert.userids().any(|x| {
+                            String::from_utf8_lossy(x.userid().value())
+                                .to_lowercase()
+                                .contains(signer)
+                        })
+                })
+            })
+            .find_map(|cert| {
+                cert.keys()
+                    .with_policy(&POLICY, None)
+                    .supported()
+                    .alive()
+                    .revoked(false)
+                    .for_signing()
+                    .secret()
+                    .next()
+                    .map(|x| (cert, x.key().clone().role_into_unspecified()))
+            })
+            .ok_or(Error::SecretKeyNotFound)?;
+
+        let mut signature = Vec::new();
+        let message = Message::new(&mut signature);
+        let message = Armorer::new(message)
+            .kind(sequoia_openpgp::armor::Kind::Signature)
+            .build()
+            .map_err(openpgp_error)?;
+        let mut message = Signer::new(message, self.unlock(cert, key)?)
+            .and_then(|x| x.detached().build())
+            .map_err(openpgp_error)?;
+        message.write_all(data)?;
+        message.finalize().map_err(openpgp_error)?;
+        Ok(String::from_utf8(signature)?)
+    }
+}
+
+impl CryptoBackend for Sequoia {
+    fn name(&self) -> &'static str {
+        "GPG id"
+    }
+    fn recipients_file(&self) -> &'static str {
+        ".gpg-id"
+    }
+    fn extension(&self) -> &'static str {
+        "gpg"
+    }
+    fn resolve_recipients(&self, recipients: &[String]) -> Result<Vec<Recipient>> {
+        let certs = self.keyring()?;
+        let mut resolved = Vec::new();
+        for recipient in recipients {
+            for (cert, keys) in Self::find_certs(certs, recipient)? {
+                resolved.push(Recipient {
+                    id: recipient.clone(),
+                    key_ids: keys.iter().map(|x| x.key().keyid().to_hex()).collect(),
+                    name: name(cert),
+                    fingerprint: Some(cert.fingerprint().to_hex()),
+                    expires: cert
+                        .with_policy(&POLICY, None)
+                        .ok()
+                        .and_then(|x| x.primary_key().key_expiration_time())
+                        .map(unix_time),
+                });
+            }
+        }
+        Ok(resolved)
+    }
+    /// `SEIPDv2` is used only if all recipients support it, so files for keys
+    /// made by gpg stay readable by gpg.
+    fn encrypt(&self, recipients: &[String], plaintext: &[u8]) -> Result<Vec<u8>> {
+        let certs = self.keyring()?;
+        let mut keys = Vec::new();
+        for recipient in recipients {
+            for (_, cert_keys) in Self::find_certs(certs, recipient)? {
+                keys.extend(cert_keys);
+            }
+        }
+        let mut buf = Vec::new();
+        let message = Message::new(&mut buf);
+        let message = Encryptor::for_recipients(message, keys)
+            .build()
+            .and_then(|x| LiteralWriter::new(x).build());
+        let mut message = message.map_err(openpgp_error)?;
+        message.write_all(plaintext)?;
+        message.finalize().map_err(openpgp_error)?;
+        Ok(buf)
+    }
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let helper = Helper {
+            sequoia: self,
+            certs: self.keyring()?,
+        };
+        let mut decryptor = DecryptorBuilder::from_bytes(ciphertext)
+            .and_then(|x| x.with_policy(&POLICY, None, helper))
+            .map_err(openpgp_error)?;
+        let mut buf = Vec::new();
+        decryptor.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+    /// Reads recipients from packets, so secret key is not needed.
+    fn encrypted_to(&self, ciphertext: &[u8]) -> Result<Option<HashSet<String>>> {
+        let mut key_ids = HashSet::new();
+        let mut ppr = PacketParser::from_bytes(ciphertext).map_err(openpgp_error)?;
+        while let PacketParserResult::Some(pp) = ppr {
+            if let Packet::PKESK(pkesk) = &pp.packet {
+                match pkesk.recipient() {
+                    Some(handle) => key_ids.insert(KeyID::from(handle).to_hex()),
+                    // anonymous recipient
+                    None => return Ok(None),
+                };
+            }
+            ppr = pp.next().map_err(openpgp_error)?.1;
+        }
+        Ok(Some(key_ids))
+    }
+}
+
+/// Decrypts with secret keys from keyring, signatures are not checked.
+struct Helper<'a> {
+    sequoia: &'a Sequoia,
+    certs: &'a [Cert],
+}
+
+impl DecryptionHelper for Helper<'_> {
+    fn decrypt(
+        &mut self,
+        pkesks: &[PKESK],
+        _skesks: &[SKESK],
+        sym_algo: Option<SymmetricAlgorithm>,
+        decrypt: &mut dyn FnMut(Option<SymmetricAlgorithm>, &SessionKey) -> bool,
+    ) -> sequoia_openpgp::Result<Option<Cert>> {
+        let mut candidates = Vec::new();
+        for pkesk in pkesks {
+            for cert in self.certs {
+                let keys = cert.keys().secret().filter(|x| {
+                    pkesk
+                        .recipient()
+                        .is_none_or(|handle| x.key().key_handle().aliases(handle))
+                });
+                for key in keys {
+                    candidates.push((pkesk, cert, key.key().clone().role_into_unspecified()));
+                }
+            }
+        }
+        let mut try_keypair = |pkesk: &PKESK, mut keypair: KeyPair| {
+            pkesk
+                .decrypt(&mut keypair, sym_algo)
+                .is_some_and(|(algo, session_key)| decrypt(algo, &session_key))
+        };
+        // keys that don't need passphrase are tried first
+        for (pkesk, cert, key) in &candidates {
+            if let Some(keypair) = self.sequoia.cached_keypair(key) {
+                if try_keypair(pkesk, keypair) {
+                    return Ok(Some((*cert).clone()));
+                }
+            }
+        }
+        for (pkesk, cert, key) in candidates {
+            if self.sequoia.cached_keypair(&key).is_some() {
+                continue;
+            }
+            let keyid = key.keyid();
+            match self.sequoia.unlock(cert, key) {
+                Ok(keypair) => {
+                    if try_keypair(pkesk, keypair) {
+                        return Ok(Some(cert.clone()));
+                    }
+                }
+                Err(err) => eprintln!("Warning: can't unlock key {keyid}: {err}"),
+            }
+        }
+        Err(Error::SecretKeyNotFound.into())
+    }
+}
+
+impl VerificationHelper for Helper<'_> {
+    fn get_certs(&mut self, _ids: &[KeyHandle]) -> sequoia_openpgp::Result<Vec<Cert>> {
+        Ok(Vec::new())
+    }
+    fn check(&mut self, _structure: MessageStructure) -> sequoia_openpgp::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        process::{Command, Stdio},
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use sequoia_openpgp::{cert::CertBuilder, serialize::SerializeInto};
+
+    use super::*;
+
+    /// Keyring directory with a new key without passphrase for every user id.
+    fn keyring(user_ids: &[&str]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (i, user_id) in user_ids.iter().enumerate() {
+            let (cert, _) = CertBuilder::general_purpose(Some(*user_id))
+                .generate()
+                .unwrap();
+            let key = cert.as_tsk().armored().to_vec().unwrap();
+            std::fs::write(dir.path().join(format!("{i}.asc")), key).unwrap();
+        }
+        // like other files of gpg
+        std::fs::write(dir.path().join("pubring.kbx"), "not a key").unwrap();
+        dir
+    }
+
+    fn key_ids(sequoia: &Sequoia, recipients: &[String]) -> HashSet<String> {
+        let resolved = sequoia.resolve_recipients(recipients).unwrap();
+        resolved.into_iter().flat_map(|x| x.key_ids).collect()
+    }
+
+    #[test]
+    fn encrypt_decrypt() {
+        let dir = keyring(&["Alice <alice@example.com>", "Bob <bob@example.com>"]);
+        let sequoia = Sequoia::new(dir.path().to_owned());
+        let recipients = ["alice@example.com".to_owned(), "Bob".to_owned()];
+        let ciphertext = sequoia.encrypt(&recipients, b"secret\n").unwrap();
+        assert_eq!(sequoia.decrypt(&ciphertext).unwrap(), b"secret\n");
+        assert_eq!(
+            sequoia.encrypted_to(&ciphertext).unwrap(),
+            Some(key_ids(&sequoia, &recipients))
+        );
+
+        // only Bob is left, keyring is read again by a new instance
+        std::fs::remove_file(dir.path().join("0.asc")).unwrap();
+        let sequoia = Sequoia::new(dir.path().to_owned());
+        assert_eq!(sequoia.decrypt(&ciphertext).unwrap(), b"secret\n");
+        let missing = sequoia.encrypt(&recipients, b"secret\n");
+        assert!(matches!(missing, Err(Error::RecipientKeyNotFound { .. })));
+    }
+
+    #[test]
+    fn decrypt_skips_keys_that_cant_be_unlocked() {
+        static PROMPTS: AtomicUsize = AtomicUsize::new(0);
+        let dir = tempfile::tempdir().unwrap();
+        for user_id in ["Alice", "Bob"] {
+            let (cert, _) = CertBuilder::general_purpose(Some(user_id))
+                .set_password(Some(user_id.to_lowercase().into()))
+                .generate()
+                .unwrap();
+            let key = cert.as_tsk().armored().to_vec().unwrap();
+            std::fs::write(dir.path().join(format!("{user_id}.asc")), key).unwrap();
+        }
+        let mut sequoia = Sequoia::new(dir.path().to_owned());
+        // only passphrase of Bob is known
+        sequoia.prompt = |prompt| {
+            PROMPTS.fetch_add(1, Ordering::SeqCst);
+            let passphrase = if prompt.contains("Bob") {
+                "bob"
+            } else {
+                "wrong"
+            };
+            Ok(passphrase.to_owned())
+        };
+        // key of Alice is tried first, its passphrase is wrong
+        let recipients = ["Alice".to_owned(), "Bob".to_owned()];
+        let ciphertext = sequoia.encrypt(&recipients, b"secret\n").unwrap();
+        assert_eq!(sequoia.decrypt(&ciphertext).unwrap(), b"secret\n");
+        assert_eq!(PROMPTS.load(Ordering::SeqCst), 2);
+
+        // unlocked key of Bob is used without asking
+        assert_eq!(sequoia.decrypt(&ciphertext).unwrap(), b"secret\n");
+        assert_eq!(PROMPTS.load(Ordering::SeqCst), 2);
+
+        let ciphertext = sequoia.encrypt(&recipients[..1], b"secret\n").unwrap();
+        let result = sequoia.decrypt(&ciphertext);
+        assert!(
+            matches!(result, Err(Error::SecretKeyNotFound)),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn decrypt_without_secret_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, _) = CertBuilder::general_purpose(Some("Alice"))
+            .generate()
+            .unwrap();
+        let path = dir.path().join("alice.asc");
+        std::fs::write(&path, cert.armored().to_vec().unwrap()).unwrap();
+        let sequoia = Sequoia::new(path);
+        let ciphertext = sequoia.encrypt(&["Alice".to_owned()], b"secret\n").unwrap();
+        let result = sequoia.decrypt(&ciphertext);
+        assert!(
+            matches!(result, Err(Error::SecretKeyNotFound)),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn invalid_key_files_are_errors() {
+        let dir = keyring(&["Alice"]);
+        let broken = dir.path().join("broken.asc");
+        std::fs::write(&broken, "not a key").unwrap();
+        let result = Sequoia::new(dir.path().to_owned()).resolve_recipients(&["Alice".to_owned()]);
+        assert!(
+            matches!(&result, Err(Error::Keyring { path, .. }) if *path == broken),
+            "{result:?}"
+        );
+
+        let missing = dir.path().join("missing.asc");
+        let result = Sequoia::new(missing.clone()).decrypt(b"");
+        assert!(
+            matches!(&result, Err(Error::Keyring { path, .. }) if *path == missing),
+            "{result:?}"
+        );
+    }
+
+    /// Run gpg with `args` in `home` and `stdin`, passphrases are empty.
+    fn gpg(home: &Path, args: &[&str], stdin: &[u8]) -> Vec<u8> {
+        let mut child = Command::new("gpg")
+            .arg("--homedir")
+            .arg(home)
+            .args(["--batch", "--yes", "--pinentry-mode", "loopback"])
+            .args(["--passphrase", "", "--trust-model", "always"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(stdin).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "gpg {args:?}");
+        output.stdout
+    }
+
+    #[test]
+    fn gpg_round_trip() {
+        if Command::new("gpg").arg("--version").output().is_err() {
+            eprintln!("gpg is not installed, test skipped");
+            return;
+        }
+        let home = tempfile::tempdir().unwrap();
+        let user_id = "Carol <carol@example.com>";
+        gpg(
+            home.path(),
+            &[
+                "--quick-generate-key",
+                user_id,
+                "future-default",
+                "default",
+                "never",
+            ],
+            b"",
+        );
+        let key = gpg(
+            home.path(),
+            &["--armor", "--export-secret-keys", user_id],
+            b"",
+        );
+        // keyring is GNUPGHOME with exported keys
+        std::fs::write(home.path().join("carol.asc"), key).unwrap();
+        let sequoia = Sequoia::new(home.path().to_owned());
+        let recipients = ["carol@example.com".to_owned()];
+
+        let ciphertext = sequoia.encrypt(&recipients, b"secret\n").unwrap();
+        assert_eq!(gpg(home.path(), &["--decrypt"], &ciphertext), b"secret\n");
+
+        let ciphertext = gpg(
+            home.path(),
+            &["--encrypt", "--recipient", "carol@example.com"],
+            b"secret\n",
+        );
+        assert_eq!(sequoia.decrypt(&ciphertext).unwrap(), b"secret\n");
+        assert_eq!(
+            sequoia.encrypted_to(&ciphertext).unwrap(),
+            Some(key_ids(&sequoia, &recipients))
+        );
+
+        let _ = Command::new("gpgconf")
+            .arg("--homedir")
+            .arg(home.path())
+            .args(["--kill", "gpg-agent"])
+            .status();
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt::Display;
+
+use crate::{Error, Result};
+
+/// Field name of the first line.
+pub const PASSWORD_FIELD: &str = "password";
+/// Field name of a line with bare `otpauth://` URI, like pass-otp writes it.
+pub const OTPAUTH_FIELD: &str = "otpauth";
+
+/// Content of pass file in pass convention: the first line is password,
+/// following `key: value` lines are fields and other lines are notes.
+///
+/// Lines are kept as is, so unchanged entry is written back byte for byte.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Entry {
+    lines: Vec<String>,
+    trailing_newline: bool,
+}
+
+impl Entry {
+    #[must_use]
+    pub fn parse(content: &str) -> Self {
+        Self {
+            lines: content.lines().map(str::to_owned).collect(),
+            trailing_newline: content.ends_with('\n') || content.is_empty(),
+        }
+    }
+    #[must_use]
+    pub fn password(&self) -> &str {
+        self.lines.first().map_or("", String::as_str)
+    }
+    pub fn set_password(&mut self, password: &str) {
+        match self.lines.first_mut() {
+            Some(line) => password.clone_into(line),
+            None => self.lines.push(password.to_owned()),
+        }
+    }
+    /// `(key, value)` of all fields in order, without password.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().skip(1).filter_map(|x| parse_field(x))
+    }
+    /// Value of first field named `key` (case insensitive).
+    /// [`PASSWORD_FIELD`] is the first line, if there is no such field.
+    #[must_use]
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields()
+            .find(|(x, _)| x.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value)
+            .or_else(|| {
+                (key.eq_ignore_ascii_case(PASSWORD_FIELD) && !self.lines.is_empty())
+                    .then(|| self.password())
+            })
+    }
+    /// Set value of first field named `key`, or add new field at the end.
+    /// [`PASSWORD_FIELD`] sets the first line, if there is no such field.
+    /// `value` can't have line breaks, they would change other fields and notes.
+    pub fn set_field(&mut self, key: &str, value: &str) -> Result<()> {
+        if value.contains(['\n', '\r']) {
+            return Err(Error::MultilineField {
+                field: key.to_owned(),
+            });
+        }
+        let line = self
+            .lines
+            .iter_mut()
+            .skip(1)
+            .find(|x| parse_field(x).is_some_and(|(x, _)| x.eq_ignore_ascii_case(key)));
+        match line {
+            // keep bare URI format
+            Some(line) if line.starts_with("otpauth://") => value.clone_into(line),
+            Some(line) => {
+                let key = parse_field(line).map_or(key, |(x, _)| x);
+                *line = format!("{key}: {value}");
+            }
+            None if key.eq_ignore_ascii_case(PASSWORD_FIELD) => self.set_password(value),
+            None => {
+                if self.lines.is_empty() {
+                    self.lines.push(String::new());
+                }
+                self.lines.push(format!("{key}: {value}"));
+            }
+        }
+        Ok(())
+    }
+    /// Lines after password that are not fields.
+    pub fn notes(&self) -> impl Iterator<Item = &str> {
+        self.lines
+            .iter()
+            .skip(1)
+            .filter(|x| parse_field(x).is_none())
+            .map(String::as_str)
+    }
+}
+
+impl Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{line}")?;
+        }
+        if self.trailing_newline && !self.lines.is_empty() {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// `key: value` line, key is not empty and has no spaces.
+/// URL like `https://` is not a field, but bare `otpauth://` URI is [`OTPAUTH_FIELD`].
+fn parse_field(line: &str) -> Option<(&str, &str)> {
+    if line.starts_with("otpauth://") {
+        return Some((OTPAUTH_FIELD, line.trim_end()));
+    }
+    let (key, value) = line.split_once(':')?;
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+    if !(value.is_empty() || value.starts_with(char::is_whitespace)) {
+        return None;
+    }
+    Some((key, value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str =
+        "secret\nlogin: me\nhttps://example.com\nURL: https://example.com\nnote: \n\nfree text\n";
+
+    #[test]
+    fn parse_password_fields_and_notes() {
+        let entry = Entry::parse(CONTENT);
+        assert_eq!(entry.password(), "secret");
+        assert_eq!(
+            entry.fields().collect::<Vec<_>>(),
+            [
+                ("login", "me"),
+                ("URL", "https://example.com"),
+                ("note", "")
+            ]
+        );
+        assert_eq!(
+            entry.notes().collect::<Vec<_>>(),
+            ["https://example.com", "", "free text"]
+        );
+
+        let empty = Entry::parse("");
+        assert_eq!(empty.password(), "");
+        assert_eq!(empty.fields().count(), 0);
+        assert_eq!(empty.field(PASSWORD_FIELD), None);
+    }
+
+    #[test]
+    fn field_is_case_insensitive() {
+        let entry = Entry::parse(CONTENT);
+        assert_eq!(entry.field("url"), Some("https://example.com"));
+        assert_eq!(entry.field("LOGIN"), Some("me"));
+        assert_eq!(entry.field("Password"), Some("secret"));
+        assert_eq!(entry.field("email"), None);
+        // field named password goes before the first line
+        let entry = Entry::parse("secret\npassword: other\n");
+        assert_eq!(entry.field(PASSWORD_FIELD), Some("other"));
+    }
+
+    #[test]
+    fn set_existing_field() {
+        let mut entry = Entry::parse(CONTENT);
+        entry.set_field("url", "https://example.org").unwrap();
+        entry.set_field("password", "new").unwrap();
+        assert_eq!(
+            entry.to_string(),
+            "new\nlogin: me\nhttps://example.com\nURL: https://example.org\nnote: \n\nfree text\n"
+        );
+    }
+
+    #[test]
+    fn set_new_field() {
+        let mut entry = Entry::parse("secret");
+        entry.set_field("login", "me").unwrap();
+        assert_eq!(entry.to_string(), "secret\nlogin: me");
+
+        let mut empty = Entry::parse("");
+        empty.set_field("login", "me").unwrap();
+        assert_eq!(empty.password(), "");
+        assert_eq!(empty.to_string(), "\nlogin: me\n");
+        let mut empty = Entry::default();
+        empty.set_field(PASSWORD_FIELD, "secret").unwrap();
+        assert_eq!(empty.field(PASSWORD_FIELD), Some("secret"));
+    }
+
+    #[test]
+    fn set_bare_otpauth_uri() {
+        let mut entry = Entry::parse("secret\notpauth://totp/old?secret=AAAA\n");
+        entry
+            .set_field(OTPAUTH_FIELD, "otpauth://totp/new?secret=BBBB")
+            .unwrap();
+        assert_eq!(
+            entry.to_string(),
+            "secret\notpauth://totp/new?secret=BBBB\n"
+        );
+    }
+
+    #[test]
+    fn multiline_values_are_rejected() {
+        let mut entry = Entry::parse(CONTENT);
+        for value in ["a\nb: c", "a\r\nb", "a\n"] {
+            assert!(matches!(
+                entry.set_field("login", value),
+                Err(Error::MultilineField { .. })
+            ));
+        }
+        assert_eq!(entry.to_string(), CONTENT);
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for content in [
+            "",
+            "secret",
+            "secret\n",
+            CONTENT,
+            "secret\n\n\nnote  \n",
+            "  spaces  \nkey:value\n",
+        ] {
+            assert_eq!(Entry::parse(content).to_string(), content, "{content:?}");
+        }
+    }
+}
@@ -0,0 +1,441 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::{OpenOptions, Permissions},
+    io::{stdout, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+};
+
+use serde::Serialize;
+use spass::{crypto, Error, Result, Store};
+
+use crate::{
+    args::{ExportFormat, ExportOptions},
+    item::Item,
+    output::ShowJson,
+};
+
+/// Write `pass_names` in format of other password managers, to stdout or file.
+/// Output is encrypted to recipients or with age passphrase, if they are given.
+pub fn export(store: &Store, pass_names: &[String], options: &ExportOptions) -> Result<()> {
+    let backend = recipients_backend(store, &options.recipients)?;
+    let entries = pass_names
+        .iter()
+        .map(|x| {
+            store.get_entry(x).map_err(|err| Error::ReadPass {
+                pass_name: x.clone(),
+                source: Box::new(err),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let items = pass_names
+        .iter()
+        .zip(&entries)
+        .map(|(name, entry)| Item::from_entry(name, entry))
+        .collect::<Vec<_>>();
+
+    let mut data = match options.format {
+        ExportFormat::Csv => csv(&items)?,
+        ExportFormat::Json => {
+            let json = pass_names
+                .iter()
+                .zip(&entries)
+                .map(|(name, entry)| ShowJson::new(name, entry))
+                .collect::<Vec<_>>();
+            to_json(&json)?
+        }
+        ExportFormat::Bitwarden => to_json(&bitwarden(&items))?,
+        ExportFormat::Kdbx => kdbx(&items, &ask_passphrase("the KeePass database")?)?,
+    };
+    if let Some(backend) = backend {
+        data = backend.encrypt(&options.recipients, &data)?;
+    } else if options.passphrase && !matches!(options.format, ExportFormat::Kdbx) {
+        let passphrase = ask_passphrase("the export")?;
+        let encryptor = age::Encryptor::with_user_passphrase(passphrase.into());
+        let mut encrypted = Vec::new();
+        let mut writer = encryptor.wrap_output(&mut encrypted)?;
+        writer.write_all(&data)?;
+        writer.finish()?;
+        data = encrypted;
+    }
+
+    match &options.output {
+        Some(path) => {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(path)?;
+            // only the owner can read passwords, mode is set only for new file
+            file.set_permissions(Permissions::from_mode(0o600))?;
+            file.write_all(&data)?;
+        }
+        None => stdout().write_all(&data)?,
+    }
+    eprintln!("Exported {} passwords.", items.len());
+    Ok(())
+}
+
+/// Backend for `recipients`, `None` if there are none.
+/// Recipients should be all age (`age1...`) or all `OpenPGP`, output is encrypted once.
+fn recipients_backend<'a>(
+    store: &'a Store,
+    recipients: &[String],
+) -> Result<Option<&'a dyn crypto::CryptoBackend>> {
+    let (age, openpgp) = recipients
+        .iter()
+        .partition::<Vec<_>, _>(|x| x.starts_with("age1"));
+    match (age.first(), openpgp.first()) {
+        (Some(age), Some(openpgp)) => Err(Error::MixedRecipients {
+            age: (*age).clone(),
+            openpgp: (*openpgp).clone(),
+        }),
+        (Some(_), None) => Ok(Some(store.backends().age())),
+        (None, Some(_)) => Ok(Some(store.backends().openpgp())),
+        (None, None) => Ok(None),
+    }
+}
+
+fn ask_passphrase(what: &str) -> Result<String> {
+    let passphrase = rpassword::prompt_password(format!("Enter passphrase for {what}: "))?;
+    if passphrase != rpassword::prompt_password(format!("Retype passphrase for {what}: "))? {
+        return Err(Error::PasswordsDontMatch);
+    }
+    Ok(passphrase)
+}
+
+fn to_json(value: &impl Serialize) -> Result<Vec<u8>> {
+    let mut json = serde_json::to_vec_pretty(value).map_err(std::io::Error::from)?;
+    json.push(b'\n');
+    Ok(json)
+}
+
+/// Columns for [`Item`], then a column for every other field.
+/// It's read back by `import csv`.
+fn csv(items: &[Item]) -> Result<Vec<u8>> {
+    let fields = items
+        .iter()
+        .flat_map(|x| x.fields.iter().map(|(key, _)| key.as_str()))
+        .collect::<BTreeSet<_>>();
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let header = ["folder", "name", "password", "login", "url", "otp", "notes"];
+    writer
+        .write_record(header.into_iter().chain(fields.iter().copied()))
+        .map_err(std::io::Error::from)?;
+    for item in items {
+        let folder = item.folders.join("/");
+        let record = [
+            &folder,
+            &item.name,
+            &item.password,
+            &item.login,
+            &item.url,
+            &item.otp,
+            &item.notes,
+        ]
+        .into_iter()
+        .map(String::as_str)
+        .chain(fields.iter().map(|field| {
+            item.fields
+                .iter()
+                .find(|(key, _)| key == field)
+                .map_or("", |(_, value)| value.as_str())
+        }));
+        writer.write_record(record).map_err(std::io::Error::from)?;
+    }
+    Ok(writer.into_inner().map_err(|err| err.into_error())?)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BitwardenExport<'a> {
+    encrypted: bool,
+    folders: Vec<BitwardenFolder>,
+    items: Vec<BitwardenItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct BitwardenFolder {
+    id: String,
+    name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BitwardenItem<'a> {
+    id: String,
+    folder_id: Option<String>,
+    /// 1 for login.
+    #[serde(rename = "type")]
+    kind: u8,
+    name: &'a str,
+    notes: Option<&'a str>,
+    favorite: bool,
+    login: BitwardenLogin<'a>,
+    fields: Vec<BitwardenField<'a>>,
+}
+
+#[derive(Serialize)]
+struct BitwardenLogin<'a> {
+    username: Option<&'a str>,
+    password: &'a str,
+    totp: Option<&'a str>,
+    uris: Vec<BitwardenUri<'a>>,
+}
+
+#[derive(Serialize)]
+struct BitwardenUri<'a> {
+    uri: &'a str,
+}
+
+#[derive(Serialize)]
+struct BitwardenField<'a> {
+    name: &'a str,
+    value: &'a str,
+    /// 0 for text.
+    #[serde(rename = "type")]
+    kind: u8,
+}
+
+/// Unencrypted JSON export, like of Bitwarden itself.
+/// Nested folders are named like `a/b`.
+fn bitwarden(items: &[Item]) -> BitwardenExport<'_> {
+    let mut folders = HashMap::new();
+    for item in items.iter().filter(|x| !x.folders.is_empty()) {
+        folders
+            .entry(item.folders.join("/"))
+            .or_insert_with(random_uuid);
+    }
+    let mut export = BitwardenExport {
+        encrypted: false,
+        folders: folders
+            .iter()
+            .map(|(name, id)| BitwardenFolder {
+                id: id.clone(),
+                name: name.clone(),
+            })
+            .collect(),
+        items: items
+            .iter()
+            .map(|x| BitwardenItem {
+                id: random_uuid(),
+                folder_id: folders.get(&x.folders.join("/")).cloned(),
+                kind: 1,
+                name: &x.name,
+                notes: non_empty(&x.notes),
+                favorite: false,
+                login: BitwardenLogin {
+                    username: non_empty(&x.login),
+                    password: &x.password,
+                    totp: non_empty(&x.otp),
+                    uris: non_empty(&x.url)
+                        .map(|uri| BitwardenUri { uri })
+                        .into_iter()
+                        .collect(),
+                },
+                fields: x
+                    .fields
+                    .iter()
+                    .map(|(name, value)| BitwardenField {
+                        name,
+                        value,
+                        kind: 0,
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+    export.folders.sort_by(|a, b| a.name.cmp(&b.name));
+    export
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    (!value.is_empty()).then_some(value)
+}
+
+/// Random version 4 UUID for ids of Bitwarden.
+fn random_uuid() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = data_encoding::HEXLOWER.encode(&bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// `KeePass` database with a group for every folder, encrypted with `passphrase`.
+fn kdbx(items: &[Item], passphrase: &str) -> Result<Vec<u8>> {
+    let mut database = keepass::Database::new();
+    let mut groups = HashMap::new();
+    for item in items {
+        let mut group_id = database.root().id();
+        for depth in 1..=item.folders.len() {
+            let path = &item.folders[..depth];
+            if let Some(id) = groups.get(path) {
+                group_id = *id;
+                continue;
+            }
+            let mut parent = database
+                .group_mut(group_id)
+                .expect("parent group is created before");
+            let mut group = parent.add_group();
+            group.name.clone_from(&path[depth - 1]);
+            group_id = group.id();
+            groups.insert(path.to_vec(), group_id);
+        }
+        let mut group = database
+            .group_mut(group_id)
+            .expect("group is created before");
+        let mut entry = group.add_entry();
+        entry.set_unprotected(keepass::db::fields::TITLE, &item.name);
+        entry.set_protected(keepass::db::fields::PASSWORD, &item.password);
+        for (key, value) in [
+            (keepass::db::fields::USERNAME, &item.login),
+            (keepass::db::fields::URL, &item.url),
+            (keepass::db::fields::NOTES, &item.notes),
+            (keepass::db::fields::OTP, &item.otp),
+        ]
+        .into_iter()
+        .chain(item.fields.iter().map(|(key, value)| (key.as_str(), value)))
+        {
+            if !value.is_empty() {
+                entry.set_unprotected(key, value);
+            }
+        }
+    }
+    let mut data = Vec::new();
+    database.save(
+        &mut data,
+        keepass::DatabaseKey::new().with_password(passphrase),
+    )?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use spass::Entry;
+
+    use super::*;
+    use crate::{fixtures::age_store, import};
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        options: ExportOptions,
+    }
+
+    const ENTRIES: [(&str, &str); 3] = [
+        (
+            "email/work",
+            "secret\nlogin: me@example.com\nurl: https://mail.example\nPIN: 1234\nfirst note\nsecond note\n",
+        ),
+        (
+            "bank",
+            "hunter2, \"quoted\"\notpauth://totp/bank?secret=JBSWY3DPEHPK3PXP\n",
+        ),
+        ("team/db/main", "db-pass\nusername: admin\n"),
+    ];
+
+    fn items() -> Vec<Item> {
+        ENTRIES
+            .iter()
+            .map(|(name, content)| Item::from_entry(name, &Entry::parse(content)))
+            .collect()
+    }
+
+    #[test]
+    fn csv_is_imported_back() {
+        let items = items();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.csv");
+        std::fs::write(&path, csv(&items).unwrap()).unwrap();
+        let imported = import::read_csv(&path, &[]).unwrap();
+        assert_eq!(imported.len(), items.len());
+        for (item, imported) in items.iter().zip(&imported) {
+            assert_eq!(imported.pass_name(None), item.pass_name(None));
+            assert_eq!(imported.entry().to_string(), item.entry().to_string());
+        }
+    }
+
+    #[test]
+    fn bitwarden_is_imported_back() {
+        let items = items();
+        let export = bitwarden(&items);
+        let json = serde_json::to_value(&export).unwrap();
+        assert_eq!(json["encrypted"], false);
+        assert_eq!(
+            json["folders"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| x["name"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            ["email", "team/db"]
+        );
+        let work = &json["items"][0];
+        let email_id = &json["folders"][0]["id"];
+        assert_eq!(work["folderId"], *email_id);
+        assert_eq!(work["type"], 1);
+        assert_eq!(work["login"]["username"], "me@example.com");
+        assert_eq!(work["login"]["uris"][0]["uri"], "https://mail.example");
+        assert_eq!(work["fields"][0]["name"], "PIN");
+        assert_eq!(json["items"][1]["folderId"], serde_json::Value::Null);
+        assert_eq!(
+            json["items"][1]["login"]["totp"],
+            "otpauth://totp/bank?secret=JBSWY3DPEHPK3PXP"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.json");
+        std::fs::write(&path, to_json(&export).unwrap()).unwrap();
+        let imported = import::read_bitwarden(&path).unwrap();
+        let names = imported
+            .iter()
+            .map(|x| x.pass_name(None))
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["email/work", "bank", "team/db/main"]);
+        for (item, imported) in items.iter().zip(&imported) {
+            assert_eq!(imported.entry().to_string(), item.entry().to_string());
+        }
+    }
+
+    #[test]
+    fn mixed_recipients_are_rejected() {
+        let (_dir, store) = age_store();
+        let recipients = [
+            age::x25519::Identity::generate().to_public().to_string(),
+            "ABCD1234".to_owned(),
+        ];
+        assert!(matches!(
+            recipients_backend(&store, &recipients),
+            Err(Error::MixedRecipients { .. })
+        ));
+        assert!(recipients_backend(&store, &recipients[..1])
+            .unwrap()
+            .is_some_and(|x| x.extension() == "age"));
+        assert!(recipients_backend(&store, &[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn existing_output_is_made_private() {
+        let (dir, mut store) = age_store();
+        store.put("email", b"secret\n").unwrap();
+        store.commit("Add email.").unwrap();
+        let path = dir.path().join("export.csv");
+        std::fs::write(&path, b"old").unwrap();
+        std::fs::set_permissions(&path, Permissions::from_mode(0o644)).unwrap();
+
+        let cli = Cli::parse_from(["export", "-F", "csv", "-o", path.to_str().unwrap()]);
+        export(&store, &["email".to_owned()], &cli.options).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(std::fs::read_to_string(&path).unwrap().contains("secret"));
+    }
+}
@@ -0,0 +1,111 @@
+# Prelude for `.extensions/<name>.bash`, defines what pass extensions use from pass.
+# Arguments: git auto commit, git sign commits (`true`, `false` or empty),
+# extension script, then arguments of the extension. $0 is the spass executable.
+
+SPASS="$0"
+GIT_AUTO_COMMIT="$1"
+GIT_SIGN_COMMITS="$2"
+EXTENSION="$3"
+shift 3
+
+set -o pipefail
+
+GPG_OPTS=( $PASSWORD_STORE_GPG_OPTS "--quiet" "--yes" "--compress-algo=none" "--no-encrypt-to" )
+GPG="gpg"
+export GPG_TTY="${GPG_TTY:-$(tty 2>/dev/null)}"
+command -v gpg2 &>/dev/null && GPG="gpg2"
+[[ -n $GPG_AGENT_INFO || $GPG == "gpg2" ]] && GPG_OPTS+=( "--batch" "--use-agent" )
+
+CLIP_TIME="$PASSWORD_STORE_CLIP_TIME"
+GENERATED_LENGTH="$PASSWORD_STORE_GENERATED_LENGTH"
+CHARACTER_SET="$PASSWORD_STORE_CHARACTER_SET"
+CHARACTER_SET_NO_SYMBOLS="$PASSWORD_STORE_CHARACTER_SET_NO_SYMBOLS"
+
+die() {
+	echo "$@" >&2
+	exit 1
+}
+
+yesno() {
+	[[ -t 0 ]] || return 0
+	local response
+	read -r -p "$1 [y/N] " response
+	[[ $response == [yY] ]] || exit 1
+}
+
+check_sneaky_paths() {
+	local path
+	for path in "$@"; do
+		[[ $path =~ /\.\.$ || $path =~ ^\.\./ || $path =~ /\.\./ || $path =~ ^\.\.$ ]] && die "Error: You've attempted to pass a sneaky path to pass. Go home."
+	done
+}
+
+set_git() {
+	INNER_GIT_DIR="${1%/*}"
+	while [[ ! -d $INNER_GIT_DIR && ${INNER_GIT_DIR%/*}/ == "${PREFIX%/}/"* ]]; do
+		INNER_GIT_DIR="${INNER_GIT_DIR%/*}"
+	done
+	[[ $(git -C "$INNER_GIT_DIR" rev-parse --is-inside-work-tree 2>/dev/null) == true ]] || INNER_GIT_DIR=""
+}
+
+git_add_file() {
+	[[ -n $INNER_GIT_DIR ]] || return
+	git -C "$INNER_GIT_DIR" add "$1" || return
+	[[ -n $(git -C "$INNER_GIT_DIR" status --porcelain "$1") ]] || return
+	git_commit "$2"
+}
+
+git_commit() {
+	[[ -n $INNER_GIT_DIR && $GIT_AUTO_COMMIT == true ]] || return
+	local sign="$GIT_SIGN_COMMITS"
+	[[ -n $sign ]] || sign="$(git -C "$INNER_GIT_DIR" config --bool --get pass.signcommits)"
+	if [[ $sign == true ]]; then
+		git -C "$INNER_GIT_DIR" commit -S -m "$1"
+	else
+		git -C "$INNER_GIT_DIR" commit -m "$1"
+	fi
+}
+
+set_gpg_recipients() {
+	GPG_RECIPIENT_ARGS=( )
+	GPG_RECIPIENTS=( )
+
+	local current="$PREFIX/$1"
+	while [[ $current != "$PREFIX" && ! -f $current/.gpg-id ]]; do
+		current="${current%/*}"
+	done
+	current="$current/.gpg-id"
+
+	if [[ ! -f $current ]]; then
+		die "Error: You must run: $PROGRAM init your-gpg-id before you may use the password store."
+	fi
+
+	local gpg_id
+	while read -r gpg_id; do
+		gpg_id="${gpg_id%%#*}"
+		[[ -n $gpg_id ]] || continue
+		GPG_RECIPIENT_ARGS+=( "-r" "$gpg_id" )
+		GPG_RECIPIENTS+=( "$gpg_id" )
+	done < "$current"
+}
+
+# commands of pass, run by spass with the same store
+cmd_show() { "$SPASS" show "$@"; }
+cmd_find() { "$SPASS" find "$@"; }
+cmd_grep() { "$SPASS" grep "$@"; }
+cmd_insert() { "$SPASS" insert "$@"; }
+cmd_edit() { "$SPASS" edit "$@"; }
+cmd_generate() { "$SPASS" generate "$@"; }
+cmd_delete() { "$SPASS" rm "$@"; }
+cmd_git() { "$SPASS" git "$@"; }
+cmd_copy_move() {
+	local command="$1"
+	shift
+	case "$command" in
+		move) "$SPASS" mv "$@" ;;
+		*) "$SPASS" cp "$@" ;;
+	esac
+}
+
+set_git "$PREFIX/"
+source "$EXTENSION"
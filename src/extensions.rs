@@ -0,0 +1,106 @@
+use std::{
+    ffi::{OsStr, OsString},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+};
+
+use crate::{utils, Error, Result, Store};
+
+/// Options pass always gives to gpg.
+const DEFAULT_GPG_OPTS: &str = "--quiet --yes --compress-algo=none --no-encrypt-to";
+/// Functions and variables of pass for `.bash` extensions, which source the extension.
+const PRELUDE: &str = include_str!("extensions.bash");
+
+/// Run extension `name` with `args`, like pass does for unknown commands.
+///
+/// Extensions are `.extensions/<name>.bash` in the store, which are sourced by bash
+/// after pass functions they use (`cmd_show`, `die`, `git_add_file`, ...),
+/// and `spass-<name>` executables from PATH.
+pub fn run(store: &Store, name: &OsStr, args: &[OsString]) -> Result<ExitStatus> {
+    let unknown = || Error::UnknownCommand {
+        name: name.to_string_lossy().into_owned(),
+    };
+    if !store.config().enable_extensions {
+        return Err(Error::ExtensionsDisabled {
+            name: name.to_string_lossy().into_owned(),
+        });
+    }
+    let name = name
+        .to_str()
+        .filter(|x| is_valid_name(x))
+        .ok_or_else(unknown)?;
+
+    let extensions_dir = store.root().join(".extensions");
+    let store_extension = extensions_dir.join(format!("{name}.bash"));
+    let mut command = if is_executable(&store_extension) {
+        let config = store.config();
+        let mut command = std::process::Command::new("bash");
+        command
+            .arg("-c")
+            .arg(PRELUDE)
+            .arg(std::env::current_exe()?)
+            .arg(config.git_auto_commit.to_string())
+            .arg(
+                config
+                    .git_sign_commits
+                    .map_or(String::new(), |x| x.to_string()),
+            )
+            .arg(store_extension);
+        command
+    } else if let Some(executable) = find_in_path(&format!("spass-{name}")) {
+        std::process::Command::new(executable)
+    } else {
+        return Err(unknown());
+    };
+
+    let gpg_opts = [DEFAULT_GPG_OPTS]
+        .into_iter()
+        .chain(store.config().gpg_opts.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(" ");
+    Ok(command
+        .args(args)
+        .env("PASSWORD_STORE_DIR", store.root())
+        .env("PREFIX", store.root())
+        .env("EXTENSIONS", extensions_dir)
+        .env("PROGRAM", utils::how_i_invoked())
+        .env("COMMAND", name)
+        .env("GPG", "gpg")
+        .env("GPG_OPTS", &gpg_opts)
+        .env("PASSWORD_STORE_GPG_OPTS", store.config().gpg_opts.join(" "))
+        .env(
+            "PASSWORD_STORE_CLIP_TIME",
+            store.config().clip_time.to_string(),
+        )
+        .env(
+            "PASSWORD_STORE_GENERATED_LENGTH",
+            store.config().generated_length.to_string(),
+        )
+        .env(
+            "PASSWORD_STORE_CHARACTER_SET",
+            &store.config().character_set,
+        )
+        .env(
+            "PASSWORD_STORE_CHARACTER_SET_NO_SYMBOLS",
+            &store.config().character_set_no_symbols,
+        )
+        .spawn()?
+        .wait()?)
+}
+
+/// Don't let `name` escape extensions directory.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains('/')
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|x| x.is_file() && x.permissions().mode() & 0o111 != 0)
+}
+
+fn find_in_path(executable: &str) -> Option<PathBuf> {
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(executable))
+        .find(|path| is_executable(path))
+}
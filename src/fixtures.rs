@@ -0,0 +1,40 @@
+//! Test fixtures, shared by the library and the binary.
+#![allow(dead_code/* Reason: not every fixture is used by both crates */)]
+
+use std::path::Path;
+
+use secrecy::ExposeSecret;
+use spass::{api, config::Config, Store};
+
+/// Store in temporary directory, initialized for new age identity.
+pub fn age_store() -> (tempfile::TempDir, Store) {
+    let dir = tempfile::tempdir().unwrap();
+    let identity = age::x25519::Identity::generate();
+    let identities = dir.path().join("identities");
+    std::fs::write(&identities, identity.to_string().expose_secret()).unwrap();
+    let config = Config {
+        age_identities: identities,
+        ..Config::default()
+    };
+    let store = Store::with_config(dir.path().join("store"), config).unwrap();
+    let recipients = [identity.to_public().to_string()];
+    api::init(&store, "", &recipients, store.backends().age()).unwrap();
+    (dir, store)
+}
+
+/// `git init` with user for commits.
+pub fn git_init(path: &Path) {
+    for args in [
+        &["init", "-q"][..],
+        &["config", "user.name", "spass"],
+        &["config", "user.email", "spass@example.com"],
+    ] {
+        let status = std::process::Command::new("git")
+            .arg("-C")
+            .arg(path)
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+}
@@ -0,0 +1,124 @@
+use std::io::Write;
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use serde::Serialize;
+use spass::Result;
+
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const RESET: &str = "\x1b[0m";
+
+/// Pass name that matches a query, for `find --fuzzy` and `pick`.
+#[derive(Debug, Serialize)]
+pub struct Match<'a> {
+    pub name: &'a str,
+    pub score: i64,
+    /// Indices of matched chars in `name`.
+    pub positions: Vec<usize>,
+}
+
+impl Match<'_> {
+    /// `name` with matched chars between `on` and `off`.
+    pub fn highlight(&self, on: &str, off: &str) -> String {
+        let mut result = String::new();
+        for (i, char) in self.name.chars().enumerate() {
+            if self.positions.contains(&i) {
+                result.push_str(on);
+                result.push(char);
+                result.push_str(off);
+            } else {
+                result.push(char);
+            }
+        }
+        result
+    }
+}
+
+/// `names` that match any of `terms` like in skim/fzf, best first.
+/// Terms are smart case: case sensitive only with uppercase letters.
+/// All names match empty terms, in original order.
+pub fn rank<'a>(names: &'a [String], terms: &[impl AsRef<str>]) -> Vec<Match<'a>> {
+    let skim = SkimMatcherV2::default().smart_case();
+    let mut matches = names
+        .iter()
+        .filter_map(|name| {
+            let (score, positions) = terms
+                .iter()
+                .filter_map(|term| skim.fuzzy_indices(name, term.as_ref()))
+                .max_by_key(|(score, _)| *score)
+                .or(terms.is_empty().then(|| (0, Vec::new())))?;
+            Some(Match {
+                name,
+                score,
+                positions,
+            })
+        })
+        .collect::<Vec<_>>();
+    matches.sort_by_key(|x| std::cmp::Reverse(x.score));
+    matches
+}
+
+/// One match per line, matched chars are highlighted if `color`.
+pub fn print(matches: &[Match], color: bool) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    for x in matches {
+        if color {
+            writeln!(stdout, "{}", x.highlight(MATCH_COLOR, RESET))?;
+        } else {
+            writeln!(stdout, "{}", x.name)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|x| (*x).to_owned()).collect()
+    }
+
+    #[test]
+    fn prefix_beats_scattered_match() {
+        let names = names(&["my/apps/internal/list", "mail/work"]);
+        let ranked = rank(&names, &["mail"]);
+        assert_eq!(
+            ranked.iter().map(|x| x.name).collect::<Vec<_>>(),
+            ["mail/work", "my/apps/internal/list"]
+        );
+        assert!(ranked[0].score > ranked[1].score);
+        assert_eq!(ranked[0].positions, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn non_matching_names_are_dropped() {
+        let names = names(&["email/work", "bank"]);
+        let ranked = rank(&names, &["xyz", "wrk"]);
+        assert_eq!(
+            ranked.iter().map(|x| x.name).collect::<Vec<_>>(),
+            ["email/work"]
+        );
+        assert!(rank(&names, &["zzz"]).is_empty());
+        // smart case
+        assert!(rank(&names, &["Bank"]).is_empty());
+        assert_eq!(rank(&names, &["bank"]).len(), 1);
+    }
+
+    #[test]
+    fn empty_terms_keep_order() {
+        let names = names(&["b", "a"]);
+        let ranked = rank(&names, &[] as &[&str]);
+        assert_eq!(
+            ranked.iter().map(|x| x.name).collect::<Vec<_>>(),
+            ["b", "a"]
+        );
+    }
+
+    #[test]
+    fn highlight_multibyte_names() {
+        let names = names(&["почта/работа"]);
+        let ranked = rank(&names, &["раб"]);
+        assert_eq!(ranked[0].positions, [6, 7, 8]);
+        assert_eq!(ranked[0].highlight("[", "]"), "почта/[р][а][б]ота");
+    }
+}
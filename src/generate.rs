@@ -0,0 +1,353 @@
+use std::{collections::HashSet, path::Path};
+
+use num_bigint::{BigUint, RandBigInt};
+use num_traits::ToPrimitive;
+use rand::prelude::*;
+
+use crate::{config::Config, policy::CharClass, Error, Result};
+
+/// Default wordlist for passphrases, EFF large wordlist with 7776 words.
+const WORDLIST: &str = include_str!("eff_large_wordlist.txt");
+
+/// Password of `length` characters from `chars` with at least one character of every
+/// `require` class. Every such password is equally likely: characters are drawn one by one,
+/// weighted by the number of valid completions, so nothing is rejected and retried.
+#[allow(clippy::missing_panics_doc/* Reason: weights are chosen so index is in bounds */)]
+pub fn generate_constrained(
+    length: usize,
+    chars: &[char],
+    require: &[CharClass],
+) -> Result<String> {
+    let groups = CharClass::ALL.map(|class| {
+        chars
+            .iter()
+            .copied()
+            .filter(|x| CharClass::of(*x) == class)
+            .collect::<Vec<_>>()
+    });
+    let sizes = groups.each_ref().map(Vec::len);
+    let mut missing = class_mask(require);
+    check_satisfiable(length, &sizes, missing, require)?;
+
+    let mut rng = rand::rngs::StdRng::from_entropy();
+    let mut password = String::with_capacity(length);
+    for remaining in (0..length).rev() {
+        let mut x = rng.gen_biguint_below(&completions(&sizes, remaining + 1, missing));
+        for (i, group) in groups.iter().enumerate() {
+            let weight = completions(&sizes, remaining, missing & !(1 << i));
+            let block = &weight * group.len();
+            if x < block {
+                password.push(group[(x / weight).to_usize().unwrap()]);
+                missing &= !(1 << i);
+                break;
+            }
+            x -= block;
+        }
+    }
+    Ok(password)
+}
+
+/// Bits of entropy of [`generate_constrained`] result.
+#[must_use]
+pub fn constrained_entropy(length: usize, chars: &[char], require: &[CharClass]) -> f64 {
+    let sizes =
+        CharClass::ALL.map(|class| chars.iter().filter(|x| CharClass::of(**x) == class).count());
+    log2(&completions(&sizes, length, class_mask(require)))
+}
+
+fn class_mask(classes: &[CharClass]) -> u8 {
+    classes
+        .iter()
+        .fold(0, |mask, class| mask | 1 << class.index())
+}
+
+fn check_satisfiable(
+    length: usize,
+    sizes: &[usize; 4],
+    missing: u8,
+    require: &[CharClass],
+) -> Result<()> {
+    if let Some(class) = require.iter().find(|x| sizes[x.index()] == 0) {
+        return Err(Error::UnsatisfiablePolicy {
+            reason: format!("character set has no {class} characters"),
+        });
+    }
+    if completions(sizes, length, missing) == BigUint::ZERO {
+        return Err(Error::UnsatisfiablePolicy {
+            reason: format!(
+                "length {length} is less than {} required classes",
+                missing.count_ones()
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Number of strings of `length` characters from classes of `sizes`,
+/// that have every class from `missing` mask, by inclusion–exclusion:
+/// sum over subsets S of missing of (-1)^|S| * (total - |S|)^length.
+fn completions(sizes: &[usize; 4], length: usize, missing: u8) -> BigUint {
+    let total: usize = sizes.iter().sum();
+    let mut positive = BigUint::ZERO;
+    let mut negative = BigUint::ZERO;
+    let mut subset = missing;
+    loop {
+        let excluded: usize = (0..4)
+            .filter(|i| subset & (1 << i) != 0)
+            .map(|i| sizes[i])
+            .sum();
+        let count = num_traits::pow(BigUint::from(total - excluded), length);
+        if subset.count_ones().is_multiple_of(2) {
+            positive += count;
+        } else {
+            negative += count;
+        }
+        if subset == 0 {
+            break;
+        }
+        subset = (subset - 1) & missing;
+    }
+    positive - negative
+}
+
+#[allow(clippy::cast_precision_loss/* Reason: only 64 top bits are used */)]
+fn log2(number: &BigUint) -> f64 {
+    let shift = number.bits().saturating_sub(64);
+    (number >> shift).to_f64().unwrap_or(0.0).log2() + shift as f64
+}
+
+/// How [`generate_passphrase`] builds passphrase from words.
+#[derive(Debug, Clone)]
+pub struct PassphraseOptions {
+    pub words: usize,
+    pub separator: String,
+    /// Uppercase the first letter of every word.
+    pub capitalize: bool,
+    /// Append a random digit to a random word.
+    pub number: bool,
+}
+
+/// Wordlist from `path`, or the default one from [`Config::wordlist`]
+/// or embedded list, if there is no path.
+#[allow(clippy::missing_panics_doc/* Reason: embedded wordlist is valid */)]
+pub fn load_wordlist(config: &Config, path: Option<&Path>) -> Result<Vec<String>> {
+    let Some(path) = path.or(config.wordlist.as_deref()) else {
+        return Ok(parse_wordlist(WORDLIST).expect("embedded wordlist should be valid"));
+    };
+    parse_wordlist(&std::fs::read_to_string(path)?).map_err(|reason| Error::InvalidWordlist {
+        path: path.to_owned(),
+        reason,
+    })
+}
+
+/// Unique words, one per line. Dice numbers before word (like in EFF
+/// wordlists: `11111\tabacus`), empty lines and `#` comments are skipped.
+pub fn parse_wordlist(content: &str) -> std::result::Result<Vec<String>, &'static str> {
+    let mut seen = HashSet::new();
+    let mut words = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let word = match line.split_once(char::is_whitespace) {
+            Some((dice, word)) if dice.chars().all(|x| x.is_ascii_digit()) => word.trim(),
+            _ => line,
+        };
+        if seen.insert(word) {
+            words.push(word.to_owned());
+        }
+    }
+    if words.len() < 2 {
+        return Err("wordlist should have at least 2 different words");
+    }
+    Ok(words)
+}
+
+/// Passphrase of random words from `wordlist`, like diceware.
+#[allow(clippy::missing_panics_doc/* Reason: wordlist is never empty after parse_wordlist */)]
+#[must_use]
+pub fn generate_passphrase(wordlist: &[String], options: &PassphraseOptions) -> String {
+    let mut rng = rand::rngs::StdRng::from_entropy();
+    let mut words = (0..options.words)
+        .map(|_| {
+            let word = wordlist.choose(&mut rng).unwrap();
+            if options.capitalize {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|x| x.to_uppercase().chain(chars).collect())
+                    .unwrap_or_default()
+            } else {
+                word.clone()
+            }
+        })
+        .collect::<Vec<String>>();
+    if options.number {
+        if let Some(word) = words.choose_mut(&mut rng) {
+            word.push(char::from(b'0' + rng.gen_range(0..10)));
+        }
+    }
+    words.join(&options.separator)
+}
+
+/// Bits of entropy of [`generate_passphrase`] result with `wordlist_len` words.
+#[allow(clippy::cast_precision_loss/* Reason: lengths are far below 2^52 */)]
+#[must_use]
+pub fn passphrase_entropy(wordlist_len: usize, options: &PassphraseOptions) -> f64 {
+    let mut bits = options.words as f64 * (wordlist_len as f64).log2();
+    if options.number && options.words > 0 {
+        bits += (10.0 * options.words as f64).log2();
+    }
+    bits
+}
+
+/// Expand character set in `tr` syntax, like pass does:
+/// `[:class:]`, ranges `a-z`, escaped `\-` and plain characters.
+///
+/// # Returns
+/// Unique characters in order of first appearance.
+pub fn expand_charset(spec: &str) -> std::result::Result<Vec<char>, &'static str> {
+    let mut chars = Vec::new();
+    let mut push = |c: char| {
+        if !chars.contains(&c) {
+            chars.push(c);
+        }
+    };
+    let mut rest = spec;
+    while let Some(c) = rest.chars().next() {
+        if let Some(class_rest) = rest.strip_prefix("[:") {
+            let (class, tail) = class_rest
+                .split_once(":]")
+                .ok_or("unterminated character class")?;
+            let predicate: fn(&char) -> bool = match class {
+                "alnum" => char::is_ascii_alphanumeric,
+                "alpha" => char::is_ascii_alphabetic,
+                "digit" => char::is_ascii_digit,
+                "lower" => char::is_ascii_lowercase,
+                "upper" => char::is_ascii_uppercase,
+                "punct" => char::is_ascii_punctuation,
+                "graph" => char::is_ascii_graphic,
+                "xdigit" => char::is_ascii_hexdigit,
+                _ => return Err("unknown character class"),
+            };
+            (' '..='~').filter(predicate).for_each(&mut push);
+            rest = tail;
+            continue;
+        }
+
+        let (c, tail) = if c == '\\' {
+            let mut tail = rest[1..].chars();
+            (tail.next().ok_or("trailing backslash")?, tail.as_str())
+        } else {
+            (c, &rest[c.len_utf8()..])
+        };
+        if let Some(range_end) = tail.strip_prefix('-').and_then(|x| x.chars().next()) {
+            if range_end < c {
+                return Err("range end is before range start");
+            }
+            (c..=range_end).for_each(&mut push);
+            rest = &tail[1 + range_end.len_utf8()..];
+        } else {
+            push(c);
+            rest = tail;
+        }
+    }
+    if chars.is_empty() {
+        return Err("character set should not be empty");
+    }
+    Ok(chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_wordlist_is_eff_large() {
+        let wordlist = load_wordlist(&Config::default(), None).unwrap();
+        assert_eq!(wordlist.len(), 7776);
+        assert_eq!(wordlist.first().map(String::as_str), Some("abacus"));
+        assert_eq!(wordlist.last().map(String::as_str), Some("zoom"));
+        let options = PassphraseOptions {
+            words: 6,
+            separator: " ".to_owned(),
+            capitalize: false,
+            number: false,
+        };
+        // 5 dice rolls per word
+        let entropy = passphrase_entropy(wordlist.len(), &options);
+        assert!(
+            (entropy - 6.0 * 5.0 * 6f64.log2()).abs() < 1e-9,
+            "{entropy}"
+        );
+    }
+
+    #[test]
+    fn constrained_has_required_classes() {
+        let chars = expand_charset("[:alnum:]!#").unwrap();
+        let require = [CharClass::Upper, CharClass::Digit, CharClass::Symbol];
+        for length in [3, 4, 16] {
+            for _ in 0..200 {
+                let password = generate_constrained(length, &chars, &require).unwrap();
+                assert_eq!(password.chars().count(), length);
+                assert!(password.chars().all(|x| chars.contains(&x)), "{password}");
+                for class in require {
+                    assert!(
+                        password.chars().any(|x| CharClass::of(x) == class),
+                        "{password} has no {class}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn constrained_is_uniform_over_all_valid_passwords() {
+        let chars = ['a', 'b', 'A', '1'];
+        let require = [CharClass::Upper, CharClass::Digit];
+        let length = 3;
+        let mut valid = HashSet::new();
+        for i in 0..chars.len().pow(3) {
+            let password = [i / 16, i / 4 % 4, i % 4]
+                .map(|x| chars[x])
+                .iter()
+                .collect::<String>();
+            if password.contains('A') && password.contains('1') {
+                valid.insert(password);
+            }
+        }
+        let sizes = [1, 2, 1, 0];
+        let count = completions(&sizes, length, class_mask(&require));
+        assert_eq!(count, BigUint::from(valid.len()));
+        let entropy = constrained_entropy(length, &chars, &require);
+        assert!((entropy - 18f64.log2()).abs() < 1e-9);
+
+        let samples = 200;
+        let mut seen = std::collections::HashMap::<String, usize>::new();
+        for _ in 0..valid.len() * samples {
+            let password = generate_constrained(length, &chars, &require).unwrap();
+            assert!(valid.contains(&password), "{password}");
+            *seen.entry(password).or_default() += 1;
+        }
+        assert_eq!(seen.len(), valid.len());
+        // more than 7 standard deviations from expected count
+        assert!(seen.values().all(|x| x.abs_diff(samples) < 100), "{seen:?}");
+    }
+
+    #[test]
+    fn impossible_constraints_are_errors() {
+        let unsatisfiable =
+            |result: Result<String>| matches!(result, Err(Error::UnsatisfiablePolicy { .. }));
+        let alnum = expand_charset("[:alnum:]").unwrap();
+        let require = [CharClass::Upper, CharClass::Digit];
+        assert!(unsatisfiable(generate_constrained(1, &alnum, &require)));
+        assert!(unsatisfiable(generate_constrained(0, &alnum, &require)));
+        assert!(unsatisfiable(generate_constrained(
+            8,
+            &alnum,
+            &[CharClass::Symbol]
+        )));
+        assert!(generate_constrained(2, &alnum, &require).is_ok());
+        assert_eq!(generate_constrained(0, &alnum, &[]).unwrap(), "");
+    }
+}
@@ -1,40 +1,41 @@
 use std::{
+    collections::HashMap,
     ffi::OsStr,
     io::Write,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Command, ExitStatus},
 };
 
+use git2::{IndexAddOption, Repository};
+
+use crate::{config::Config, transaction::SCRATCH_PREFIX};
+
 type Result<T> = std::result::Result<T, crate::Error>;
 
+/// Open repository that contains `path`, like `git -C path` does.
+fn open(path: &OsStr) -> Result<Repository> {
+    Repository::discover(path).map_err(|err| {
+        if err.code() == git2::ErrorCode::NotFound {
+            crate::Error::PassStoreShouldBeGitRepo
+        } else {
+            err.into()
+        }
+    })
+}
 pub fn verify_git_initialized(path: &OsStr) -> Result<()> {
-    let cmd = Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("rev-parse")
-        .arg("--is-inside-work-tree")
-        .output()?;
-
-    if String::from_utf8_lossy(&cmd.stdout).trim() == "true" {
-        Ok(())
-    } else {
-        Err(crate::Error::PassStoreShouldBeGitRepo)
+    let repo = open(path)?;
+    if repo.is_bare() {
+        return Err(crate::Error::PassStoreShouldBeGitRepo);
     }
+    Ok(())
 }
-pub fn should_sign_commits(path: &OsStr) -> Result<bool> {
-    let cmd = Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("config")
-        .arg("bool")
-        .args(["--get", "pass.signcommits"])
-        .output()?;
-    if String::from_utf8_lossy(&cmd.stdout).trim() == "true" {
-        Ok(true)
-    } else {
-        Ok(false)
+fn should_sign_commits(config: &Config, repo: &Repository) -> Result<bool> {
+    if let Some(sign_commits) = config.git_sign_commits {
+        return Ok(sign_commits);
     }
+    Ok(repo.config()?.get_bool("pass.signcommits").unwrap_or(false))
 }
+/// Run `git` binary, for `spass git ...`.
 pub fn command(path: &OsStr, args: impl IntoIterator<Item = String>) -> Result<ExitStatus> {
     verify_git_initialized(path)?;
     Ok(Command::new("git")
@@ -44,10 +45,15 @@ pub fn command(path: &OsStr, args: impl IntoIterator<Item = String>) -> Result<E
         .spawn()?
         .wait()?)
 }
-pub fn init(path: &OsStr, other_args: impl IntoIterator<Item = String>) -> Result<()> {
+pub fn init(
+    config: &Config,
+    path: &OsStr,
+    other_args: impl IntoIterator<Item = String>,
+) -> Result<()> {
     if verify_git_initialized(path).is_ok() {
         return Err(crate::Error::GitRepoAlreadyInitialized);
     }
+    // `git init` is user facing, so it should accept all options of real git.
     let cmd = Command::new("git")
         .arg("-C")
         .arg(path)
@@ -55,10 +61,11 @@ pub fn init(path: &OsStr, other_args: impl IntoIterator<Item = String>) -> Resul
         .args(other_args)
         .output()?;
     if !cmd.status.success() {
-        eprintln!("{}", String::from_utf8_lossy(&cmd.stderr));
-        return Err(crate::Error::CantInitGitRepo);
+        return Err(crate::Error::CantInitGitRepo {
+            reason: String::from_utf8_lossy(&cmd.stderr).trim().to_owned(),
+        });
     }
-    commit_all(path, "Add current contents of password store.")?;
+    commit_all(config, path, "Add current contents of password store.")?;
     writeln!(
         std::fs::OpenOptions::new()
             .append(true)
@@ -67,97 +74,201 @@ pub fn init(path: &OsStr, other_args: impl IntoIterator<Item = String>) -> Resul
         "*.gpg diff=gpg"
     )?;
     commit_file(
+        config,
         path,
         ".gitattributes",
         "Configure git repository for gpg file diff.",
     )?;
-    Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("config")
-        .arg("--local")
-        .args(["diff.gpg.binary", "true"])
-        .output()?;
-    Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("config")
-        .arg("--local")
-        .args([
-            "diff.gpg.textconv",
-            // TODO: add options from PASSWORD_STORE_GPG_OPTS
-            "gpg -d --quiet --yes --compress-algo=none --no-encrypt-to",
-        ])
-        .output()?;
+    let repo = open(path)?;
+    let mut repo_config = repo.config()?.open_level(git2::ConfigLevel::Local)?;
+    repo_config.set_bool("diff.gpg.binary", true)?;
+    repo_config.set_str(
+        "diff.gpg.textconv",
+        &["gpg -d --quiet --yes --compress-algo=none --no-encrypt-to"]
+            .into_iter()
+            .chain(config.gpg_opts.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" "),
+    )?;
     Ok(())
 }
-pub fn unstage_all(path: &OsStr) -> Result<()> {
-    Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("reset")
-        .output()?;
-    Ok(())
+/// `file_name` relative to repository working directory.
+fn path_in_repo(repo: &Repository, path: &OsStr, file_name: &str) -> Result<PathBuf> {
+    let workdir = repo
+        .workdir()
+        .ok_or(crate::Error::PassStoreShouldBeGitRepo)?
+        .canonicalize()?;
+    let file = Path::new(path).canonicalize()?.join(file_name);
+    Ok(file
+        .strip_prefix(&workdir)
+        .map_or(PathBuf::from(file_name), Path::to_owned))
 }
-fn stage_file(path: &OsStr, file_name: &str) -> Result<()> {
-    Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("add")
-        .arg(file_name)
-        .output()?;
 
-    let output = Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("status")
-        .args(["--porcelain", file_name])
-        .output()?;
-    if String::from_utf8_lossy(&output.stdout).is_empty() {
-        dbg!(
-            String::from_utf8_lossy(&output.stdout),
-            String::from_utf8_lossy(&output.stderr)
-        );
-        return Err(crate::Error::CantStageFile {
-            file_name: file_name.to_owned(),
-        });
+/// Time (seconds since epoch) of the last commit that changed each file under `path`,
+/// with paths relative to `path`. Renamed files are new files.
+pub fn last_changes(path: &OsStr) -> Result<HashMap<PathBuf, i64>> {
+    let repo = open(path)?;
+    let store_in_repo = path_in_repo(&repo, path, "")?;
+    let mut revwalk = repo.revwalk()?;
+    match revwalk.push_head() {
+        Ok(()) => (),
+        Err(err) if err.code() == git2::ErrorCode::UnbornBranch => return Ok(HashMap::new()),
+        Err(err) => return Err(err.into()),
     }
-    Ok(())
+    revwalk.set_sorting(git2::Sort::TIME)?;
+    let mut changes = HashMap::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        let parent_tree = commit.parents().next().map(|x| x.tree()).transpose()?;
+        let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+        for delta in diff.deltas() {
+            let file = delta
+                .new_file()
+                .path()
+                .and_then(|x| x.strip_prefix(&store_in_repo).ok());
+            if let Some(file) = file {
+                changes
+                    .entry(file.to_owned())
+                    .or_insert(commit.time().seconds());
+            }
+        }
+    }
+    Ok(changes)
 }
-fn stage_all(path: &OsStr) -> Result<()> {
-    Command::new("git")
-        .arg("-C")
-        .arg(path)
-        .arg("add")
-        .arg("--all")
-        .output()?;
+
+/// Commit changes of `index` with `message`.
+/// Does nothing, if there are no changes.
+/// `index` is written only after commit, so on error index of the repository isn't changed.
+fn commit(
+    config: &Config,
+    repo: &Repository,
+    index: &mut git2::Index,
+    message: &str,
+) -> Result<()> {
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let parent = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit()?),
+        Err(err) if err.code() == git2::ErrorCode::UnbornBranch => None,
+        Err(err) => return Err(err.into()),
+    };
+    if parent.as_ref().is_some_and(|x| x.tree_id() == tree.id()) {
+        index.write()?;
+        return Ok(());
+    }
+    let signature = repo.signature()?;
+    let parents = parent.iter().collect::<Vec<_>>();
+
+    if !should_sign_commits(config, repo)? {
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )?;
+        index.write()?;
+        return Ok(());
+    }
+    let buffer = repo.commit_create_buffer(&signature, &signature, message, &tree, &parents)?;
+    let buffer = buffer
+        .as_str()
+        .ok_or_else(|| git2::Error::from_str("commit buffer is not valid utf-8"))?;
+    let gpg_signature = sign(config, repo, buffer)?;
+    let commit_id = repo.commit_signed(buffer, &gpg_signature, None)?;
+    let head = repo.find_reference("HEAD")?;
+    match head.symbolic_target() {
+        Some(branch) => {
+            repo.reference(branch, commit_id, true, message)?;
+        }
+        None => repo.set_head_detached(commit_id)?,
+    }
+    index.write()?;
     Ok(())
 }
-fn commit(path: &OsStr, message: &str) -> Result<()> {
+/// Armored detached signature of `buffer` with `user.signingkey` or default key.
+fn sign(config: &Config, repo: &Repository, buffer: &str) -> Result<String> {
+    let signing_key = repo.config()?.get_string("user.signingkey").ok();
+    crate::crypto::sign_detached(config, buffer.as_bytes(), signing_key.as_deref())
+}
+pub fn commit_all(config: &Config, path: &OsStr, message: &str) -> Result<()> {
     verify_git_initialized(path)?;
-    let mut binding = Command::new("git");
-    let cmd = binding.arg("-C").arg(path).arg("commit");
-    if should_sign_commits(path)? {
-        cmd.arg("-S");
+    if !config.git_auto_commit {
+        return Ok(());
     }
-    let cmd = cmd.args(["-m", message]).output()?;
-
-    if cmd.status.success() {
-        Ok(())
+    let repo = open(path)?;
+    let mut index = repo.index()?;
+    // store can be subdirectory of repository
+    let store_in_repo = path_in_repo(&repo, path, "")?;
+    let pathspec = if store_in_repo.as_os_str().is_empty() {
+        PathBuf::from("*")
     } else {
-        eprintln!("{}", String::from_utf8_lossy(&cmd.stderr));
-        Err(crate::Error::CantCommit)
-    }
+        store_in_repo
+    };
+    let mut skip_scratch = |path: &Path, _: &[u8]| {
+        i32::from(
+            path.components()
+                .any(|x| x.as_os_str().to_string_lossy().starts_with(SCRATCH_PREFIX)),
+        )
+    };
+    index.add_all(
+        [&pathspec],
+        IndexAddOption::DEFAULT,
+        Some(&mut skip_scratch),
+    )?;
+    index.update_all([&pathspec], None)?;
+    commit(config, &repo, &mut index, message)
 }
-pub fn commit_all(path: &OsStr, message: &str) -> Result<()> {
+/// Commit at HEAD of repository that contains `path`, `None` if branch is unborn.
+pub fn head(path: &OsStr) -> Result<Option<git2::Oid>> {
     verify_git_initialized(path)?;
-    stage_all(path)?;
-    commit(path, message)?;
+    let repo = open(path)?;
+    let head = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit()?.id()),
+        Err(err) if err.code() == git2::ErrorCode::UnbornBranch => None,
+        Err(err) => return Err(err.into()),
+    };
+    Ok(head)
+}
+/// Undo commits made after [`head`] returned `head`, like `git reset --mixed`.
+/// Files in the working directory are left as they are.
+pub fn reset(path: &OsStr, head: Option<git2::Oid>) -> Result<()> {
+    if self::head(path)? == head {
+        return Ok(());
+    }
+    let repo = open(path)?;
+    if let Some(head) = head {
+        repo.reset(&repo.find_object(head, None)?, git2::ResetType::Mixed, None)?;
+        return Ok(());
+    }
+    // branch was unborn, so it's removed with its first commit
+    let reference = repo.find_reference("HEAD")?;
+    if let Some(branch) = reference.symbolic_target() {
+        repo.find_reference(branch)?.delete()?;
+    }
+    let mut index = repo.index()?;
+    index.clear()?;
+    index.write()?;
     Ok(())
 }
-pub fn commit_file(path: &OsStr, file_name: &str, message: &str) -> Result<()> {
+/// Commit only `file_name`, changes staged by user are unstaged.
+pub fn commit_file(config: &Config, path: &OsStr, file_name: &str, message: &str) -> Result<()> {
     verify_git_initialized(path)?;
-    stage_file(path, file_name)?;
-    commit(path, message)?;
-    Ok(())
+    if !config.git_auto_commit {
+        return Ok(());
+    }
+    let repo = open(path)?;
+    let mut index = repo.index()?;
+    if let Ok(head) = repo.head() {
+        index.read_tree(&head.peel_to_tree()?)?;
+    } else {
+        index.clear()?;
+    }
+    let file_in_repo = path_in_repo(&repo, path, file_name)?;
+    if Path::new(path).join(file_name).exists() {
+        index.add_path(&file_in_repo)?;
+    } else {
+        index.remove_path(&file_in_repo)?;
+    }
+    commit(config, &repo, &mut index, message)
 }
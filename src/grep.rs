@@ -0,0 +1,264 @@
+use std::io::Write;
+
+use regex::{Regex, RegexBuilder};
+
+use serde::Serialize;
+
+use crate::{
+    api,
+    args::{GrepOptions, OutputFormat},
+    output, Error, Result, Store,
+};
+
+const PASS_DIR_COLOR: &str = "\x1b[94m";
+const PASS_NAME_COLOR: &str = "\x1b[1m";
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const LINE_NUMBER_COLOR: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Pass file with selected lines, for `grep --json`.
+#[derive(Serialize)]
+struct GrepJson {
+    name: String,
+    count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lines: Option<Vec<LineJson>>,
+}
+
+#[derive(Serialize)]
+struct LineJson {
+    number: usize,
+    text: String,
+}
+
+/// Search `search_string` in decrypted content of every pass file and write results to `out`.
+/// Content is decrypted only in memory.
+/// Pass files that can't be decrypted or aren't UTF-8 are skipped with a warning.
+pub fn grep(
+    out: &mut impl Write,
+    store: &Store,
+    search_string: &str,
+    options: &GrepOptions,
+) -> Result<()> {
+    let pattern = if options.extended_regexp {
+        search_string.to_owned()
+    } else {
+        regex::escape(search_string)
+    };
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(options.ignore_case)
+        .build()?;
+
+    let json = options.format.get(OutputFormat::Plain) == OutputFormat::Json;
+    let mut json_output = Vec::new();
+    for pass_name in api::get_pass_names(store, store.root())? {
+        // like pass, one unreadable file doesn't stop the search
+        let content = match store
+            .get(&pass_name)
+            .and_then(|x| String::from_utf8(x).map_err(Error::from))
+        {
+            Ok(content) => content,
+            Err(err) => {
+                eprintln!("Warning: skipped {pass_name}: {err}");
+                continue;
+            }
+        };
+        let selected = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| regex.is_match(line) != options.invert_match)
+            .collect::<Vec<_>>();
+        if selected.is_empty() {
+            continue;
+        }
+
+        if json {
+            json_output.push(GrepJson {
+                count: selected.len(),
+                lines: (!options.count).then(|| {
+                    selected
+                        .iter()
+                        .map(|(line_index, line)| LineJson {
+                            number: line_index + 1,
+                            text: (*line).to_owned(),
+                        })
+                        .collect()
+                }),
+                name: pass_name,
+            });
+            continue;
+        }
+
+        let colored_name = colored_pass_name(&pass_name);
+        if options.count {
+            writeln!(out, "{colored_name}: {}", selected.len())?;
+            continue;
+        }
+        writeln!(out, "{colored_name}:")?;
+        for (line_index, line) in selected {
+            if options.line_number {
+                write!(out, "{LINE_NUMBER_COLOR}{}{RESET}:", line_index + 1)?;
+            }
+            if options.invert_match {
+                writeln!(out, "{line}")?;
+            } else {
+                writeln!(out, "{}", highlight_matches(&regex, line))?;
+            }
+        }
+    }
+    if json {
+        output::write_json(out, &json_output)?;
+    }
+    Ok(())
+}
+
+fn colored_pass_name(pass_name: &str) -> String {
+    let (dir, name) = match pass_name.rsplit_once('/') {
+        Some((dir, name)) => (dir.to_owned() + "/", name),
+        None => (String::new(), pass_name),
+    };
+    format!("{PASS_DIR_COLOR}{dir}{PASS_NAME_COLOR}{name}{RESET}")
+}
+
+fn highlight_matches(regex: &Regex, line: &str) -> String {
+    regex
+        .replace_all(line, |captures: &regex::Captures| {
+            format!("{MATCH_COLOR}{}{RESET}", &captures[0])
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use spass::Store;
+
+    use super::*;
+    use crate::fixtures::age_store;
+
+    #[derive(Parser)]
+    struct Cli {
+        search_string: String,
+        #[command(flatten)]
+        options: GrepOptions,
+    }
+
+    fn store() -> (tempfile::TempDir, Store) {
+        let (dir, mut store) = age_store();
+        store
+            .put("bank", b"hunter2\nLOGIN: me\nurl: bank.example\n")
+            .unwrap();
+        store
+            .put(
+                "email/work",
+                b"secret\nlogin: me@example.com\nurl: mail.example\n",
+            )
+            .unwrap();
+        store.commit("Add passwords.").unwrap();
+        (dir, store)
+    }
+
+    /// Output of `spass grep args` without colors.
+    fn grep_output(store: &Store, args: &[&str]) -> String {
+        let cli = Cli::parse_from(std::iter::once("grep").chain(args.iter().copied()));
+        let mut out = Vec::new();
+        grep(&mut out, store, &cli.search_string, &cli.options).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        Regex::new("\x1b\\[[0-9;]*m")
+            .unwrap()
+            .replace_all(&out, "")
+            .into_owned()
+    }
+
+    #[test]
+    fn fixed_string() {
+        let (_dir, store) = store();
+        assert_eq!(
+            grep_output(&store, &["login"]),
+            "email/work:\nlogin: me@example.com\n"
+        );
+        // not a regex
+        assert_eq!(grep_output(&store, &["l.gin"]), "");
+    }
+
+    #[test]
+    fn ignore_case() {
+        let (_dir, store) = store();
+        assert_eq!(
+            grep_output(&store, &["-i", "login"]),
+            "bank:\nLOGIN: me\nemail/work:\nlogin: me@example.com\n"
+        );
+    }
+
+    #[test]
+    fn extended_regexp() {
+        let (_dir, store) = store();
+        assert_eq!(
+            grep_output(&store, &["-E", "^[a-z]+[0-9]$"]),
+            "bank:\nhunter2\n"
+        );
+    }
+
+    #[test]
+    fn invert_match_with_line_numbers() {
+        let (_dir, store) = store();
+        assert_eq!(
+            grep_output(&store, &["-v", "-n", "example"]),
+            "bank:\n1:hunter2\n2:LOGIN: me\nemail/work:\n1:secret\n"
+        );
+    }
+
+    #[test]
+    fn count() {
+        let (_dir, store) = store();
+        assert_eq!(
+            grep_output(&store, &["-c", "example"]),
+            "bank: 1\nemail/work: 2\n"
+        );
+    }
+
+    #[test]
+    fn matches_are_highlighted() {
+        let (_dir, store) = store();
+        let cli = Cli::parse_from(["grep", "hunter"]);
+        let mut out = Vec::new();
+        grep(&mut out, &store, &cli.search_string, &cli.options).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains(&format!("{MATCH_COLOR}hunter{RESET}2\n")));
+    }
+
+    #[test]
+    fn json() {
+        let (_dir, store) = store();
+        let lines: serde_json::Value =
+            serde_json::from_str(&grep_output(&store, &["--json", "-i", "login"])).unwrap();
+        assert_eq!(
+            lines,
+            serde_json::json!([
+                {"name": "bank", "count": 1, "lines": [{"number": 2, "text": "LOGIN: me"}]},
+                {
+                    "name": "email/work",
+                    "count": 1,
+                    "lines": [{"number": 2, "text": "login: me@example.com"}],
+                },
+            ])
+        );
+        let counts: serde_json::Value =
+            serde_json::from_str(&grep_output(&store, &["--json", "-c", "url"])).unwrap();
+        assert_eq!(
+            counts,
+            serde_json::json!([
+                {"name": "bank", "count": 1},
+                {"name": "email/work", "count": 1},
+            ])
+        );
+    }
+
+    #[test]
+    fn unreadable_pass_files_are_skipped() {
+        let (_dir, store) = store();
+        std::fs::write(store.root().join("broken.age"), b"not age").unwrap();
+        assert_eq!(grep_output(&store, &["hunter"]), "bank:\nhunter2\n");
+    }
+}
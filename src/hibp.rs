@@ -0,0 +1,262 @@
+use std::{
+    cmp::Ordering,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use memmap2::Mmap;
+use sha1::{Digest, Sha1};
+use spass::{Error, Result};
+
+/// Length of SHA-1 in hex.
+const HASH_LENGTH: usize = 40;
+/// Length of hash prefix in range files and requests.
+const PREFIX_LENGTH: usize = 5;
+
+/// Source of Pwned Passwords from <https://haveibeenpwned.com/Passwords>.
+pub enum Pwned {
+    /// Single file with `HASH:COUNT` lines sorted by hash,
+    /// like from `haveibeenpwned-downloader`.
+    Dump { path: PathBuf, map: Mmap },
+    /// Directory with `PREFIX.txt` files of `SUFFIX:COUNT` lines,
+    /// like from `haveibeenpwned-downloader -s false`.
+    Ranges(PathBuf),
+    /// Range API of a mirror, `{url}/range/PREFIX` is requested.
+    #[cfg(feature = "hibp-api")]
+    Api(String),
+}
+
+impl Pwned {
+    /// Dump file or directory of range files.
+    pub fn open(path: &Path) -> Result<Self> {
+        if path.is_dir() {
+            return Ok(Self::Ranges(path.to_owned()));
+        }
+        let file = File::open(path)?;
+        // SAFETY: file is only read, and it's not expected to change while spass runs
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self::Dump {
+            path: path.to_owned(),
+            map,
+        })
+    }
+
+    #[cfg(feature = "hibp-api")]
+    pub fn api(url: &str) -> Self {
+        Self::Api(url.trim_end_matches('/').to_owned())
+    }
+
+    /// How many times `password` was seen in breaches, 0 if never.
+    /// Only first 5 chars of its SHA-1 leave the process with [`Self::Api`].
+    pub fn count(&self, password: &str) -> Result<u64> {
+        let hash = data_encoding::HEXUPPER.encode(&Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+        match self {
+            Self::Dump { path, map } => {
+                search(map, hash.as_bytes()).map_err(|reason| Error::InvalidPwnedPasswords {
+                    location: path.display().to_string(),
+                    reason,
+                })
+            }
+            Self::Ranges(dir) => {
+                let path = dir.join(format!("{prefix}.txt"));
+                let range = match std::fs::read_to_string(&path) {
+                    Ok(range) => range,
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                        return Err(Error::InvalidPwnedPasswords {
+                            location: dir.display().to_string(),
+                            reason: "range file is missing",
+                        })
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                find_suffix(&range, suffix).map_err(|reason| Error::InvalidPwnedPasswords {
+                    location: path.display().to_string(),
+                    reason,
+                })
+            }
+            #[cfg(feature = "hibp-api")]
+            Self::Api(url) => {
+                use std::io::Read;
+
+                let url = format!("{url}/range/{prefix}");
+                let mut range = String::new();
+                // padding hides the number of suffixes in the response
+                ureq::get(&url)
+                    .header("Add-Padding", "true")
+                    .call()
+                    .map_err(std::io::Error::other)?
+                    .into_body()
+                    .into_reader()
+                    .read_to_string(&mut range)?;
+                find_suffix(&range, suffix).map_err(|reason| Error::InvalidPwnedPasswords {
+                    location: url,
+                    reason,
+                })
+            }
+        }
+    }
+}
+
+/// Binary search of `hash` in `HASH:COUNT` lines sorted by hash.
+fn search(dump: &[u8], hash: &[u8]) -> std::result::Result<u64, &'static str> {
+    let mut low = 0;
+    let mut high = dump.len();
+    while low < high {
+        let middle = low + (high - low) / 2;
+        // line containing `middle`, so every step drops it
+        let start = dump[..middle]
+            .iter()
+            .rposition(|x| *x == b'\n')
+            .map_or(0, |x| x + 1);
+        let end = dump[middle..]
+            .iter()
+            .position(|x| *x == b'\n')
+            .map_or(dump.len(), |x| middle + x);
+        let line = &dump[start..end];
+        let line_hash = line.get(..HASH_LENGTH).ok_or("line is too short")?;
+        match line_hash
+            .iter()
+            .map(u8::to_ascii_uppercase)
+            .cmp(hash.iter().copied())
+        {
+            Ordering::Less => low = end + 1,
+            Ordering::Greater => high = start,
+            Ordering::Equal => return parse_count(&line[HASH_LENGTH..]),
+        }
+    }
+    Ok(0)
+}
+
+/// Count of `suffix` in `SUFFIX:COUNT` lines of a range.
+fn find_suffix(range: &str, suffix: &str) -> std::result::Result<u64, &'static str> {
+    for line in range.lines() {
+        let (line_suffix, count) = line.split_once(':').ok_or("line without count")?;
+        if line_suffix.eq_ignore_ascii_case(suffix) {
+            return parse_count(count.as_bytes());
+        }
+    }
+    Ok(0)
+}
+
+/// `:COUNT` or `COUNT`, with optional `\r`.
+fn parse_count(count: &[u8]) -> std::result::Result<u64, &'static str> {
+    let count = count.strip_prefix(b":").unwrap_or(count);
+    let count = count.strip_suffix(b"\r").unwrap_or(count);
+    std::str::from_utf8(count)
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .ok_or("invalid count")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use super::*;
+
+    const HASHES: [&str; 4] = [
+        "0000000A0E4A3F2E9DC9B9D4B8F4C6C5B2E5C1B0",
+        "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8",
+        "7C4A8D09CA3762AF61E59520943DC26494F8941B",
+        "FFFFFFF1D4C9E2F6A3B3C0D1E2F3A4B5C6D7E8F9",
+    ];
+
+    /// Lines of [`HASHES`] without first `skip` chars, counts are 1, 2, ...
+    fn lines(skip: usize, line_end: &str) -> String {
+        let mut lines = String::new();
+        for (count, hash) in (1..).zip(HASHES) {
+            write!(lines, "{}:{count}{line_end}", &hash[skip..]).unwrap();
+        }
+        lines
+    }
+
+    #[test]
+    fn search_dump() {
+        let dumps = [
+            lines(0, "\n"),
+            lines(0, "\r\n"),
+            lines(0, "\n").to_lowercase(),
+        ];
+        for dump in dumps {
+            for (count, hash) in (1..).zip(HASHES) {
+                assert_eq!(search(dump.as_bytes(), hash.as_bytes()), Ok(count));
+            }
+            let missing = b"5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD9";
+            assert_eq!(search(dump.as_bytes(), missing), Ok(0));
+            assert_eq!(search(dump.as_bytes(), &[b'0'; HASH_LENGTH]), Ok(0));
+            assert_eq!(search(dump.as_bytes(), &[b'F'; HASH_LENGTH]), Ok(0));
+        }
+        // without newline at the end
+        let dump = lines(0, "\n");
+        let dump = dump.trim_end().as_bytes();
+        assert_eq!(search(dump, HASHES[3].as_bytes()), Ok(4));
+        assert_eq!(search(b"", HASHES[0].as_bytes()), Ok(0));
+        assert!(search(b"5BAA61E4:1\n", HASHES[1].as_bytes()).is_err());
+    }
+
+    #[test]
+    fn find_suffix_in_range() {
+        for range in [
+            lines(PREFIX_LENGTH, "\r\n"),
+            lines(PREFIX_LENGTH, "\n").to_lowercase(),
+        ] {
+            for (count, hash) in (1..).zip(HASHES) {
+                assert_eq!(find_suffix(&range, &hash[PREFIX_LENGTH..]), Ok(count));
+            }
+            assert_eq!(find_suffix(&range, &"0".repeat(35)), Ok(0));
+        }
+        assert_eq!(find_suffix("", &HASHES[0][PREFIX_LENGTH..]), Ok(0));
+        assert!(find_suffix("A0E4A3F2E9\r\n", &HASHES[0][PREFIX_LENGTH..]).is_err());
+    }
+
+    #[cfg(feature = "hibp-api")]
+    #[test]
+    fn api_range() {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut request = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim_end().is_empty() {
+                    break;
+                }
+                request.push(line.trim_end().to_owned());
+            }
+            // padding entries have count 0
+            let body = format!(
+                "{}:0\r\n{}{}:0\r\n",
+                "0".repeat(35),
+                lines(PREFIX_LENGTH, "\r\n"),
+                "F".repeat(35)
+            );
+            write!(
+                &stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            request
+        });
+
+        // SHA-1 of "password" is HASHES[1]
+        assert_eq!(Pwned::api(&url).count("password").unwrap(), 2);
+        let request = server.join().unwrap();
+        assert_eq!(request[0], "GET /range/5BAA6 HTTP/1.1");
+        assert!(
+            request
+                .iter()
+                .any(|x| x.eq_ignore_ascii_case("add-padding: true")),
+            "{request:?}"
+        );
+    }
+}
@@ -0,0 +1,725 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs::File,
+    io::Read,
+    path::Path,
+};
+
+use serde::Deserialize;
+use spass::{api, Error, Result, Store};
+
+use crate::{
+    args::{CsvField, ImportFormat, ImportOptions},
+    item::Item,
+};
+
+/// Import all items of `path` in `format` into the store, in one commit.
+/// Existing passwords are skipped, unless `--force` is given.
+///
+/// # Returns
+/// Whether some passwords were skipped.
+pub fn import(
+    store: &mut Store,
+    format: ImportFormat,
+    path: &Path,
+    options: &ImportOptions,
+) -> Result<bool> {
+    let items = match format {
+        ImportFormat::Kdbx => read_kdbx(path, options)?,
+        ImportFormat::KeepassXml => read_keepass_xml(path)?,
+        ImportFormat::Bitwarden => read_bitwarden(path)?,
+        ImportFormat::Onepux => read_1pux(path)?,
+        ImportFormat::Csv => read_csv(path, &options.columns)?,
+    };
+
+    let mut skipped = false;
+    let mut imported = 0;
+    let mut names = HashSet::new();
+    for item in &items {
+        let mut pass_name = item.pass_name(options.prefix.as_deref());
+        // same name in the same folder
+        let base_name = pass_name.clone();
+        for i in 2.. {
+            if names.insert(pass_name.clone()) {
+                break;
+            }
+            pass_name = format!("{base_name}-{i}");
+        }
+        if !options.force && api::get_pass_path(store, &pass_name).exists() {
+            eprintln!("{pass_name} already exists, skipped.");
+            skipped = true;
+            continue;
+        }
+        if options.dry_run {
+            let fields = item.entry();
+            let fields = fields.fields().map(|(key, _)| key).collect::<Vec<_>>();
+            if fields.is_empty() {
+                println!("{pass_name}");
+            } else {
+                println!("{pass_name} ({})", fields.join(", "));
+            }
+        } else {
+            if item.has_multiline_password() {
+                eprintln!("Warning: password of {pass_name} has line breaks, its other lines are in notes.");
+            }
+            store.put(&pass_name, item.entry().to_string().as_bytes())?;
+        }
+        imported += 1;
+    }
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    if options.dry_run {
+        println!("Would import {imported} of {} passwords.", items.len());
+    } else {
+        store.commit(&format!("Import {imported} passwords from {file_name}."))?;
+        println!("Imported {imported} of {} passwords.", items.len());
+    }
+    Ok(skipped)
+}
+
+fn invalid(path: &Path, reason: &dyn Display) -> Error {
+    Error::InvalidImport {
+        path: path.to_owned(),
+        reason: reason.to_string(),
+    }
+}
+
+/// `KeePass` database, password is asked unless only keyfile is used.
+fn read_kdbx(path: &Path, options: &ImportOptions) -> Result<Vec<Item>> {
+    let mut key = keepass::DatabaseKey::new();
+    if let Some(keyfile) = &options.keyfile {
+        key = key.with_keyfile(&mut File::open(keyfile)?)?;
+    }
+    if options.keyfile.is_none() || options.ask_password {
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        key = key.with_password(&rpassword::prompt_password(format!(
+            "Enter password for {file_name}: "
+        ))?);
+    }
+    let database = keepass::Database::open(&mut File::open(path)?, key)?;
+    let recycle_bin = database.recycle_bin().map(|x| x.id());
+
+    let mut items = Vec::new();
+    // groups with their path, without root group
+    let mut groups = vec![(database.root().id(), Vec::new())];
+    while let Some((id, folders)) = groups.pop() {
+        let Some(group) = database.group(id) else {
+            continue;
+        };
+        for entry in group.entries() {
+            let mut item = Item {
+                folders: folders.clone(),
+                ..Item::default()
+            };
+            let mut keys = entry.fields.keys().collect::<Vec<_>>();
+            keys.sort();
+            for key in keys {
+                set_keepass_field(&mut item, key, entry.fields[key].get());
+            }
+            items.push(item);
+        }
+        for child in group.groups() {
+            if Some(child.id()) == recycle_bin {
+                continue;
+            }
+            let mut folders = folders.clone();
+            folders.push(child.name.clone());
+            groups.push((child.id(), folders));
+        }
+    }
+    Ok(items)
+}
+
+/// Standard and custom strings of `KeePass` entry.
+fn set_keepass_field(item: &mut Item, key: &str, value: &str) {
+    use keepass::db::fields::{NOTES, OTP, PASSWORD, TITLE, URL, USERNAME};
+    match key {
+        TITLE => value.clone_into(&mut item.name),
+        PASSWORD => value.clone_into(&mut item.password),
+        USERNAME => value.clone_into(&mut item.login),
+        URL => value.clone_into(&mut item.url),
+        NOTES => value.clone_into(&mut item.notes),
+        OTP if value.starts_with("otpauth://") => {
+            value.clone_into(&mut item.otp);
+        }
+        _ if !value.is_empty() => item.fields.push((key.to_owned(), value.to_owned())),
+        _ => (),
+    }
+}
+
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|x| x.has_tag_name(name))
+}
+
+fn text(node: Option<roxmltree::Node<'_, '_>>) -> String {
+    node.and_then(|x| x.text()).unwrap_or_default().to_owned()
+}
+
+/// Unencrypted XML export of `KeePass`.
+fn read_keepass_xml(path: &Path) -> Result<Vec<Item>> {
+    let xml = std::fs::read_to_string(path)?;
+    let document = roxmltree::Document::parse(&xml).map_err(|err| invalid(path, &err))?;
+    let root = document.root_element();
+    let recycle_bin = child(root, "Meta").map(|x| text(child(x, "RecycleBinUUID")));
+    let root_group = child(root, "Root")
+        .and_then(|x| child(x, "Group"))
+        .ok_or_else(|| invalid(path, &"no root group"))?;
+
+    let mut items = Vec::new();
+    let mut groups = vec![(root_group, Vec::new())];
+    while let Some((group, folders)) = groups.pop() {
+        for node in group.children().filter(|x| x.is_element()) {
+            if node.has_tag_name("Entry") {
+                let mut item = Item {
+                    folders: folders.clone(),
+                    ..Item::default()
+                };
+                for string in node.children().filter(|x| x.has_tag_name("String")) {
+                    let key = text(child(string, "Key"));
+                    set_keepass_field(&mut item, &key, &text(child(string, "Value")));
+                }
+                items.push(item);
+            } else if node.has_tag_name("Group")
+                && recycle_bin.as_ref() != Some(&text(child(node, "UUID")))
+            {
+                let mut folders = folders.clone();
+                folders.push(text(child(node, "Name")));
+                groups.push((node, folders));
+            }
+        }
+    }
+    Ok(items)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BitwardenExport {
+    encrypted: bool,
+    #[serde(default)]
+    folders: Vec<BitwardenFolder>,
+    items: Vec<BitwardenItem>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenFolder {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BitwardenItem {
+    folder_id: Option<String>,
+    name: String,
+    notes: Option<String>,
+    login: Option<BitwardenLogin>,
+    #[serde(default)]
+    fields: Vec<BitwardenField>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenLogin {
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+    #[serde(default)]
+    uris: Vec<BitwardenUri>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenUri {
+    uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BitwardenField {
+    name: Option<String>,
+    value: Option<String>,
+}
+
+/// Unencrypted JSON export of Bitwarden.
+pub(crate) fn read_bitwarden(path: &Path) -> Result<Vec<Item>> {
+    let export: BitwardenExport =
+        serde_json::from_slice(&std::fs::read(path)?).map_err(|err| invalid(path, &err))?;
+    if export.encrypted {
+        return Err(invalid(path, &"encrypted exports are not supported"));
+    }
+    let folders = export
+        .folders
+        .into_iter()
+        .map(|x| (x.id, x.name))
+        .collect::<HashMap<_, _>>();
+    Ok(export
+        .items
+        .into_iter()
+        .map(|x| {
+            let login = x.login.unwrap_or(BitwardenLogin {
+                username: None,
+                password: None,
+                totp: None,
+                uris: Vec::new(),
+            });
+            Item {
+                // nested folders are named like `a/b`
+                folders: x
+                    .folder_id
+                    .and_then(|id| folders.get(&id))
+                    .map(|x| x.split('/').map(str::to_owned).collect())
+                    .unwrap_or_default(),
+                name: x.name,
+                password: login.password.unwrap_or_default(),
+                login: login.username.unwrap_or_default(),
+                url: login
+                    .uris
+                    .into_iter()
+                    .find_map(|x| x.uri)
+                    .unwrap_or_default(),
+                otp: login
+                    .totp
+                    .filter(|x| x.starts_with("otpauth://"))
+                    .unwrap_or_default(),
+                fields: x
+                    .fields
+                    .into_iter()
+                    .filter_map(|x| Some((x.name?, x.value?)))
+                    .collect(),
+                notes: x.notes.unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct OnePuxExport {
+    accounts: Vec<OnePuxAccount>,
+}
+
+#[derive(Deserialize)]
+struct OnePuxAccount {
+    vaults: Vec<OnePuxVault>,
+}
+
+#[derive(Deserialize)]
+struct OnePuxVault {
+    attrs: OnePuxVaultAttrs,
+    items: Vec<OnePuxItem>,
+}
+
+#[derive(Deserialize)]
+struct OnePuxVaultAttrs {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct OnePuxItem {
+    state: String,
+    overview: OnePuxOverview,
+    details: OnePuxDetails,
+}
+
+#[derive(Deserialize)]
+struct OnePuxOverview {
+    title: String,
+    #[serde(default)]
+    url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OnePuxDetails {
+    #[serde(default)]
+    login_fields: Vec<OnePuxLoginField>,
+    notes_plain: Option<String>,
+    /// Password of items without login fields, like passwords and databases.
+    password: Option<String>,
+    #[serde(default)]
+    sections: Vec<OnePuxSection>,
+}
+
+#[derive(Deserialize)]
+struct OnePuxLoginField {
+    value: String,
+    designation: Option<String>,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct OnePuxSection {
+    #[serde(default)]
+    fields: Vec<OnePuxField>,
+}
+
+#[derive(Deserialize)]
+struct OnePuxField {
+    title: String,
+    /// Single key like `string`, `concealed` or `totp`.
+    value: HashMap<String, serde_json::Value>,
+}
+
+/// 1Password unencrypted export, zip with `export.data`.
+/// Archived and deleted items are skipped.
+fn read_1pux(path: &Path) -> Result<Vec<Item>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?).map_err(|err| invalid(path, &err))?;
+    let mut data = Vec::new();
+    archive
+        .by_name("export.data")
+        .map_err(|err| invalid(path, &err))?
+        .read_to_end(&mut data)?;
+    let export: OnePuxExport = serde_json::from_slice(&data).map_err(|err| invalid(path, &err))?;
+
+    let mut items = Vec::new();
+    for vault in export.accounts.into_iter().flat_map(|x| x.vaults) {
+        for x in vault.items.into_iter().filter(|x| x.state == "active") {
+            let mut item = Item {
+                folders: vec![vault.attrs.name.clone()],
+                name: x.overview.title,
+                url: x.overview.url,
+                password: x.details.password.unwrap_or_default(),
+                notes: x.details.notes_plain.unwrap_or_default(),
+                ..Item::default()
+            };
+            for field in x.details.login_fields {
+                match field.designation.as_deref() {
+                    Some("username") => item.login = field.value,
+                    Some("password") => item.password = field.value,
+                    _ if !field.value.is_empty() => item.fields.push((field.name, field.value)),
+                    _ => (),
+                }
+            }
+            for field in x.details.sections.into_iter().flat_map(|x| x.fields) {
+                let Some((kind, serde_json::Value::String(value))) = field.value.into_iter().next()
+                else {
+                    continue;
+                };
+                if kind == "totp" && value.starts_with("otpauth://") {
+                    item.otp = value;
+                } else if !value.is_empty() {
+                    item.fields.push((field.title, value));
+                }
+            }
+            items.push(item);
+        }
+    }
+    Ok(items)
+}
+
+/// Header names of fields in CSV exports of browsers and password managers.
+const CSV_HEADERS: [(CsvField, &[&str]); 7] = [
+    (CsvField::Name, &["name", "title", "account", "item"]),
+    (CsvField::Folder, &["folder", "group", "grouping", "vault"]),
+    (CsvField::Password, &["password", "pass"]),
+    (
+        CsvField::Login,
+        &["login", "username", "user", "login_username", "email"],
+    ),
+    (
+        CsvField::Url,
+        &["url", "uri", "website", "login_uri", "web site"],
+    ),
+    (CsvField::Otp, &["otp", "totp", "otpauth", "login_totp"]),
+    (
+        CsvField::Notes,
+        &["notes", "note", "extra", "comment", "comments"],
+    ),
+];
+
+/// CSV with header, columns are found by [`CSV_HEADERS`] or by `columns`.
+/// Other columns are fields.
+pub(crate) fn read_csv(path: &Path, columns: &[(CsvField, String)]) -> Result<Vec<Item>> {
+    let mut reader = csv::Reader::from_path(path).map_err(|err| invalid(path, &err))?;
+    let headers = reader
+        .headers()
+        .map_err(|err| invalid(path, &err))?
+        .iter()
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    let mut mapping = HashMap::new();
+    for (field, names) in CSV_HEADERS {
+        let explicit = columns.iter().find(|(x, _)| *x == field);
+        let index = match explicit {
+            Some((_, header)) => Some(
+                headers
+                    .iter()
+                    .position(|x| x == header)
+                    .ok_or_else(|| invalid(path, &format!("no column {header:?}")))?,
+            ),
+            None => headers
+                .iter()
+                .position(|x| names.iter().any(|name| x.trim().eq_ignore_ascii_case(name))),
+        };
+        if let Some(index) = index {
+            mapping.insert(index, field);
+        }
+    }
+    if !mapping.values().any(|x| *x == CsvField::Name) {
+        return Err(invalid(
+            path,
+            &"no name column, set it with --column name=...",
+        ));
+    }
+
+    let mut items = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|err| invalid(path, &err))?;
+        let mut item = Item::default();
+        for (index, value) in record.iter().enumerate() {
+            let value = value.to_owned();
+            match mapping.get(&index) {
+                Some(CsvField::Name) => item.name = value,
+                Some(CsvField::Folder) => {
+                    item.folders = value
+                        .split('/')
+                        .filter(|x| !x.is_empty())
+                        .map(str::to_owned)
+                        .collect();
+                }
+                Some(CsvField::Password) => item.password = value,
+                Some(CsvField::Login) => item.login = value,
+                Some(CsvField::Url) => item.url = value,
+                Some(CsvField::Otp) if value.starts_with("otpauth://") => item.otp = value,
+                Some(CsvField::Notes) => item.notes = value,
+                // other OTP secrets are kept as is
+                Some(CsvField::Otp) | None if !value.is_empty() => {
+                    item.fields.push((headers[index].clone(), value));
+                }
+                Some(CsvField::Otp) | None => (),
+            }
+        }
+        items.push(item);
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// File named `name` with `content` in temporary directory.
+    fn fixture(name: &str, content: &[u8]) -> (tempfile::TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        (dir, path)
+    }
+
+    fn options(keyfile: Option<std::path::PathBuf>) -> ImportOptions {
+        ImportOptions {
+            dry_run: false,
+            force: false,
+            prefix: None,
+            columns: Vec::new(),
+            keyfile,
+            ask_password: false,
+        }
+    }
+
+    #[test]
+    fn kdbx() {
+        use keepass::db::fields::{NOTES, PASSWORD, TITLE, USERNAME};
+
+        let mut database = keepass::Database::new();
+        database.config.kdf_config = keepass::config::KdfConfig::Aes { rounds: 1 };
+        let mut root = database.root_mut();
+        let mut group = root.add_group();
+        group.name = "email".to_owned();
+        let mut entry = group.add_entry();
+        entry.set_unprotected(TITLE, "work");
+        entry.set_unprotected(USERNAME, "me@example.com");
+        entry.set_protected(PASSWORD, " secret ");
+        entry.set_unprotected(NOTES, "first\nsecond");
+        entry.set_unprotected("PIN", "1234");
+
+        let (dir, keyfile) = fixture("keyfile", b"0123456789abcdef0123456789abcdef");
+        let key = keepass::DatabaseKey::new()
+            .with_keyfile(&mut File::open(&keyfile).unwrap())
+            .unwrap();
+        let path = dir.path().join("passwords.kdbx");
+        database
+            .save(&mut File::create(&path).unwrap(), key)
+            .unwrap();
+
+        let items = read_kdbx(&path, &options(Some(keyfile))).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].pass_name(None), "email/work");
+        assert_eq!(items[0].login, "me@example.com");
+        assert_eq!(items[0].password, " secret ");
+        assert_eq!(items[0].notes, "first\nsecond");
+        assert_eq!(items[0].fields, [("PIN".to_owned(), "1234".to_owned())]);
+    }
+
+    #[test]
+    fn keepass_xml() {
+        let (_dir, path) = fixture(
+            "passwords.xml",
+            br#"<?xml version="1.0" encoding="utf-8"?>
+<KeePassFile>
+  <Meta><RecycleBinUUID>bin</RecycleBinUUID></Meta>
+  <Root>
+    <Group>
+      <UUID>root</UUID>
+      <Name>Root</Name>
+      <Entry>
+        <String><Key>Title</Key><Value>bank</Value></String>
+        <String><Key>Password</Key><Value>pass word</Value></String>
+        <String><Key>URL</Key><Value>https://bank.example.com</Value></String>
+        <String><Key>otp</Key><Value>otpauth://totp/bank?secret=ABC</Value></String>
+      </Entry>
+      <Group>
+        <UUID>web</UUID>
+        <Name>web</Name>
+        <Entry>
+          <String><Key>Title</Key><Value>forum</Value></String>
+          <String><Key>UserName</Key><Value>me</Value></String>
+        </Entry>
+      </Group>
+      <Group>
+        <UUID>bin</UUID>
+        <Name>Recycle Bin</Name>
+        <Entry><String><Key>Title</Key><Value>deleted</Value></String></Entry>
+      </Group>
+    </Group>
+  </Root>
+</KeePassFile>
+"#,
+        );
+        let items = read_keepass_xml(&path).unwrap();
+        let names = items.iter().map(|x| x.pass_name(None)).collect::<Vec<_>>();
+        assert_eq!(names, ["bank", "web/forum"]);
+        assert_eq!(items[0].password, "pass word");
+        assert_eq!(items[0].url, "https://bank.example.com");
+        assert_eq!(items[0].otp, "otpauth://totp/bank?secret=ABC");
+        assert_eq!(items[1].login, "me");
+    }
+
+    #[test]
+    fn bitwarden() {
+        let (_dir, path) = fixture(
+            "bitwarden.json",
+            br#"{
+  "encrypted": false,
+  "folders": [{ "id": "f1", "name": "social/chat" }],
+  "items": [
+    {
+      "folderId": "f1",
+      "name": "matrix",
+      "notes": null,
+      "login": {
+        "username": "me",
+        "password": "secret\t",
+        "totp": "JBSWY3DP",
+        "uris": [{ "uri": null }, { "uri": "https://matrix.org" }]
+      },
+      "fields": [{ "name": "recovery", "value": "a\nb" }, { "name": null, "value": "x" }]
+    },
+    { "folderId": null, "name": "note", "notes": "text", "login": null }
+  ]
+}"#,
+        );
+        let items = read_bitwarden(&path).unwrap();
+        let names = items.iter().map(|x| x.pass_name(None)).collect::<Vec<_>>();
+        assert_eq!(names, ["social/chat/matrix", "note"]);
+        assert_eq!(items[0].password, "secret\t");
+        assert_eq!(items[0].url, "https://matrix.org");
+        // not an URI, so it's not used as OTP
+        assert_eq!(items[0].otp, "");
+        assert_eq!(
+            items[0].fields,
+            [("recovery".to_owned(), "a\nb".to_owned())]
+        );
+        assert_eq!(items[1].notes, "text");
+
+        let (_dir, path) = fixture("encrypted.json", br#"{"encrypted": true, "items": []}"#);
+        assert!(matches!(
+            read_bitwarden(&path),
+            Err(Error::InvalidImport { .. })
+        ));
+    }
+
+    #[test]
+    fn onepux() {
+        let data = br#"{
+  "accounts": [{
+    "vaults": [{
+      "attrs": { "name": "Personal" },
+      "items": [
+        {
+          "state": "active",
+          "overview": { "title": "mail", "url": "https://mail.example.com" },
+          "details": {
+            "loginFields": [
+              { "value": "me", "designation": "username", "name": "username" },
+              { "value": "secret", "designation": "password", "name": "password" },
+              { "value": "", "name": "remember" }
+            ],
+            "notesPlain": "notes",
+            "sections": [{
+              "fields": [
+                { "title": "", "value": { "totp": "otpauth://totp/mail?secret=ABC" } },
+                { "title": "PIN", "value": { "concealed": "1234" } },
+                { "title": "date", "value": { "date": 1700000000 } }
+              ]
+            }]
+          }
+        },
+        {
+          "state": "archived",
+          "overview": { "title": "old" },
+          "details": { "password": "old" }
+        }
+      ]
+    }]
+  }]
+}"#;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.1pux");
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        zip.start_file("export.data", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(data).unwrap();
+        zip.finish().unwrap();
+
+        let items = read_1pux(&path).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].pass_name(None), "Personal/mail");
+        assert_eq!(items[0].login, "me");
+        assert_eq!(items[0].password, "secret");
+        assert_eq!(items[0].url, "https://mail.example.com");
+        assert_eq!(items[0].otp, "otpauth://totp/mail?secret=ABC");
+        assert_eq!(items[0].fields, [("PIN".to_owned(), "1234".to_owned())]);
+        assert_eq!(items[0].notes, "notes");
+    }
+
+    #[test]
+    fn csv() {
+        let (_dir, path) = fixture(
+            "passwords.csv",
+            b"Title,Group,Password,Username,Web Site,Comments,PIN\n\
+              shop,web/stores, pass ,me,https://shop.example.com,\"line 1\nline 2\",\n\
+              router,,admin,,,,0000\n",
+        );
+        let items = read_csv(&path, &[]).unwrap();
+        assert_eq!(items[0].pass_name(None), "web/stores/shop");
+        assert_eq!(items[0].password, " pass ");
+        assert_eq!(items[0].login, "me");
+        assert_eq!(items[0].url, "https://shop.example.com");
+        assert_eq!(items[0].notes, "line 1\nline 2");
+        assert!(items[0].fields.is_empty());
+        assert_eq!(items[1].pass_name(None), "router");
+        assert_eq!(items[1].fields, [("PIN".to_owned(), "0000".to_owned())]);
+
+        let (_dir, path) = fixture("passwords.csv", b"site,secret\nshop,pass\n");
+        assert!(read_csv(&path, &[]).is_err());
+        let columns = [
+            (CsvField::Name, "site".to_owned()),
+            (CsvField::Password, "secret".to_owned()),
+        ];
+        let items = read_csv(&path, &columns).unwrap();
+        assert_eq!(items[0].name, "shop");
+        assert_eq!(items[0].password, "pass");
+    }
+}
@@ -0,0 +1,183 @@
+use spass::{Entry, OTPAUTH_FIELD, PASSWORD_FIELD};
+
+/// Field names of login in pass files, the first one is written by import.
+const LOGIN_FIELDS: [&str; 4] = ["login", "username", "user", "email"];
+/// Field names of URL in pass files, the first one is written by import.
+const URL_FIELDS: [&str; 3] = ["url", "website", "uri"];
+
+/// Item of other password manager, the same as pass file.
+#[derive(Debug, Default)]
+pub struct Item {
+    /// Groups, vault or folder of the item, outermost first.
+    pub folders: Vec<String>,
+    pub name: String,
+    pub password: String,
+    pub login: String,
+    pub url: String,
+    /// `otpauth://` URI.
+    pub otp: String,
+    /// Other fields in order, like custom fields of `KeePass`.
+    pub fields: Vec<(String, String)>,
+    pub notes: String,
+}
+
+impl Item {
+    /// Item of `entry`, fields like `login` and `url` go to their own places.
+    pub fn from_entry(pass_name: &str, entry: &Entry) -> Self {
+        let (folders, name) = match pass_name.rsplit_once('/') {
+            Some((dir, name)) => (dir.split('/').map(str::to_owned).collect(), name),
+            None => (Vec::new(), pass_name),
+        };
+        let mut item = Item {
+            folders,
+            name: name.to_owned(),
+            password: entry.password().to_owned(),
+            notes: entry.notes().collect::<Vec<_>>().join("\n"),
+            ..Item::default()
+        };
+        for (key, value) in entry.fields() {
+            let is = |names: &[&str]| names.iter().any(|x| x.eq_ignore_ascii_case(key));
+            if item.login.is_empty() && is(&LOGIN_FIELDS) {
+                value.clone_into(&mut item.login);
+            } else if item.url.is_empty() && is(&URL_FIELDS) {
+                value.clone_into(&mut item.url);
+            } else if item.otp.is_empty() && key == OTPAUTH_FIELD {
+                value.clone_into(&mut item.otp);
+            } else {
+                item.fields.push((key.to_owned(), value.to_owned()));
+            }
+        }
+        item
+    }
+
+    /// Pass name from folders and name, `/` in them is replaced.
+    pub fn pass_name(&self, prefix: Option<&str>) -> String {
+        prefix
+            .into_iter()
+            .map(|x| x.trim_matches('/').to_owned())
+            .chain(self.folders.iter().chain([&self.name]).map(|x| {
+                let x = x.trim().replace('/', "-");
+                if x.is_empty() || x == "." || x == ".." {
+                    "untitled".to_owned()
+                } else {
+                    x
+                }
+            }))
+            .filter(|x| !x.is_empty())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Content in pass convention, fields in `key: value` lines.
+    /// Password is kept as is, values with line breaks (and lines of password
+    /// after the first one) go to notes after `[key]` line.
+    pub fn entry(&self) -> Entry {
+        let mut password = self.password.lines();
+        let mut lines = vec![password.next().unwrap_or_default().to_owned()];
+        let mut multiline = Vec::new();
+        let mut push_multiline = |key: &str, value_lines: Vec<&str>| {
+            if !value_lines.is_empty() {
+                multiline.push(format!("[{key}]"));
+                multiline.extend(value_lines.into_iter().map(str::to_owned));
+            }
+        };
+        push_multiline(PASSWORD_FIELD, password.collect());
+        for (key, value) in [(LOGIN_FIELDS[0], &self.login), (URL_FIELDS[0], &self.url)]
+            .into_iter()
+            .chain(self.fields.iter().map(|(key, value)| (key.as_str(), value)))
+        {
+            let key = field_key(key);
+            let value = value.trim();
+            if value.contains(['\n', '\r']) {
+                push_multiline(&key, value.lines().collect());
+            } else if !value.is_empty() {
+                lines.push(format!("{key}: {value}"));
+            }
+        }
+        lines.extend(
+            self.otp
+                .lines()
+                .next()
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(str::to_owned),
+        );
+        lines.extend(self.notes.lines().map(str::to_owned));
+        lines.extend(multiline);
+        Entry::parse(&(lines.join("\n") + "\n"))
+    }
+
+    /// Password has line breaks, so only its first line is the password of entry.
+    pub fn has_multiline_password(&self) -> bool {
+        self.password.contains(['\n', '\r'])
+    }
+}
+
+/// Key without spaces and `:`, so it's parsed as field.
+fn field_key(key: &str) -> String {
+    let key = key
+        .trim()
+        .replace(|x: char| x.is_whitespace() || x == ':', "_")
+        .to_lowercase();
+    if key.is_empty() || key == OTPAUTH_FIELD {
+        "field".to_owned()
+    } else {
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_keeps_password() {
+        let item = Item {
+            name: "shop".to_owned(),
+            password: " pass word\t".to_owned(),
+            login: " me ".to_owned(),
+            otp: "otpauth://totp/shop?secret=ABC".to_owned(),
+            notes: "note".to_owned(),
+            ..Item::default()
+        };
+        let entry = item.entry();
+        assert_eq!(entry.password(), " pass word\t");
+        assert_eq!(
+            entry.fields().collect::<Vec<_>>(),
+            [
+                ("login", "me"),
+                (OTPAUTH_FIELD, "otpauth://totp/shop?secret=ABC")
+            ]
+        );
+        assert_eq!(entry.notes().collect::<Vec<_>>(), ["note"]);
+        assert!(!item.has_multiline_password());
+    }
+
+    #[test]
+    fn multiline_values_are_notes() {
+        let item = Item {
+            password: "first\r\nsecond".to_owned(),
+            fields: vec![
+                ("recovery codes".to_owned(), "1111\n2222\n".to_owned()),
+                ("PIN".to_owned(), "1234".to_owned()),
+            ],
+            notes: "note".to_owned(),
+            ..Item::default()
+        };
+        assert!(item.has_multiline_password());
+        let entry = item.entry();
+        assert_eq!(entry.password(), "first");
+        assert_eq!(entry.fields().collect::<Vec<_>>(), [("pin", "1234")]);
+        assert_eq!(
+            entry.notes().collect::<Vec<_>>(),
+            [
+                "note",
+                "[password]",
+                "second",
+                "[recovery_codes]",
+                "1111",
+                "2222"
+            ]
+        );
+    }
+}
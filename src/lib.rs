@@ -0,0 +1,190 @@
+#![warn(clippy::pedantic)]
+#![allow(
+    clippy::missing_errors_doc,
+    clippy::redundant_closure_for_method_calls,
+    clippy::unnecessary_debug_formatting
+)]
+#![deny(clippy::missing_panics_doc)]
+
+//! Password manager compatible with [pass](https://www.passwordstore.org/).
+//!
+//! Use [`Store`] to read and change the password store.
+
+use std::string::FromUtf8Error;
+
+use thiserror::Error;
+
+// shared test fixtures use `spass::` paths, like the binary
+extern crate self as spass;
+
+pub mod api;
+pub mod config;
+pub mod crypto;
+mod entry;
+#[cfg(test)]
+mod fixtures;
+pub mod generate;
+pub mod git;
+pub mod otp;
+pub mod policy;
+mod store;
+mod transaction;
+pub mod utils;
+
+pub use entry::{Entry, OTPAUTH_FIELD, PASSWORD_FIELD};
+pub use store::Store;
+pub use transaction::Interrupt;
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[cfg(feature = "gpgme")]
+    #[error(transparent)]
+    GPG(#[from] gpgme::Error),
+    #[error(transparent)]
+    FromUtf(#[from] FromUtf8Error),
+    #[error(transparent)]
+    Clipboard(#[from] arboard::Error),
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+    #[error(transparent)]
+    QrCode(#[from] qrcode::types::QrError),
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+    #[error(transparent)]
+    AgeEncrypt(#[from] age::EncryptError),
+    #[error(transparent)]
+    AgeDecrypt(#[from] age::DecryptError),
+    #[error(transparent)]
+    KeePass(#[from] keepass::db::DatabaseOpenError),
+    #[error(transparent)]
+    KeePassSave(#[from] keepass::db::DatabaseSaveError),
+
+    #[error(
+        "You must run:\n    {} init ...\n before you may use th password store",
+        utils::how_i_invoked()
+    )]
+    PasswordStoreUninitialized,
+    #[error("There is no password at line {line_number}")]
+    NoPasswordAtLine { line_number: usize },
+    #[error("There is no field {field:?} in {pass_name}")]
+    NoSuchField { pass_name: String, field: String },
+    #[error("Value of field {field:?} can't have line breaks")]
+    MultilineField { field: String },
+    #[error("Tree command not found. Try install one of [{}]", supported_commands.join(", "))]
+    TreeCommandNotFound { supported_commands: Vec<String> },
+    #[error("Interactive selection requires a terminal")]
+    NotATerminal,
+    #[error("the entered passwords do not match")]
+    PasswordsDontMatch,
+    #[error("{pass_name} is not in the password store.\nNote: Your pass will have path {path:?}")]
+    PassDoesNotExist {
+        pass_name: String,
+        path: std::path::PathBuf,
+    },
+    #[error("Can't read {pass_name}")]
+    ReadPass {
+        pass_name: String,
+        #[source]
+        source: Box<Error>,
+    },
+    #[error("Invalid pass name {pass_name:?}: it should be a relative path inside the store")]
+    InvalidPassName { pass_name: String },
+    #[error("There is no otpauth:// URI in {pass_name}")]
+    NoOtpUri { pass_name: String },
+    #[error("Invalid otpauth:// URI: {reason}")]
+    InvalidOtpUri { reason: String },
+    #[error("{path:?} exists but is not a directory")]
+    NotADirectory { path: std::path::PathBuf },
+    #[error("{path:?} does not exist and so cannot be removed")]
+    GpgIdDoesNotExist { path: std::path::PathBuf },
+    #[error("{recipient:?} is not in {path:?}")]
+    RecipientNotListed {
+        recipient: String,
+        path: std::path::PathBuf,
+    },
+    #[error("Can't remove all recipients of {path:?}")]
+    NoRecipientsLeft { path: std::path::PathBuf },
+
+    #[error("Can't find password store: use --store, set PASSWORD_STORE_DIR or HOME")]
+    StoreDirUnknown,
+
+    #[error("Invalid value {value:?} of {name}: {reason}")]
+    InvalidEnvVar {
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
+
+    #[error("Invalid config file {path:?}: {reason}")]
+    InvalidConfigFile {
+        path: std::path::PathBuf,
+        reason: String,
+    },
+    #[error("Invalid config value for {key:?}: {reason}")]
+    InvalidConfigValue { key: String, reason: String },
+    #[error("Password policy can't be met: {reason}")]
+    UnsatisfiablePolicy { reason: String },
+    #[error("Invalid wordlist {path:?}: {reason}")]
+    InvalidWordlist {
+        path: std::path::PathBuf,
+        reason: &'static str,
+    },
+    #[error("Invalid Pwned Passwords in {location}: {reason}")]
+    InvalidPwnedPasswords {
+        location: String,
+        reason: &'static str,
+    },
+    #[error("Can't import {path:?}: {reason}")]
+    InvalidImport {
+        path: std::path::PathBuf,
+        reason: String,
+    },
+    #[error("No key found for recipient {recipient:?}")]
+    RecipientKeyNotFound { recipient: String },
+    #[error("No secret key found to decrypt or sign")]
+    SecretKeyNotFound,
+    #[error("OpenPGP error: {reason}")]
+    OpenPgp { reason: String },
+    #[error("Can't read OpenPGP keys from {path:?}: {reason}")]
+    Keyring {
+        path: std::path::PathBuf,
+        reason: String,
+    },
+    #[error("Invalid age recipient {recipient:?}: {reason}")]
+    InvalidAgeRecipient {
+        recipient: String,
+        reason: &'static str,
+    },
+    #[error("Can't read age identities from {path:?}: {source}")]
+    AgeIdentities {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Can't encrypt for both age recipient {age:?} and OpenPGP recipient {openpgp:?}")]
+    MixedRecipients { age: String, openpgp: String },
+    #[error("No gpg-ids specified and `recipients` is not set in config")]
+    NoRecipients,
+
+    #[error("Unknown command {name:?}")]
+    UnknownCommand { name: String },
+    #[error("Unknown command {name:?}. Note: extensions are enabled with PASSWORD_STORE_ENABLE_EXTENSIONS=true or enable_extensions in user config")]
+    ExtensionsDisabled { name: String },
+
+    #[error("Can't copy or move {old_pass} into itself ({new_pass})")]
+    MoveIntoItself { old_pass: String, new_pass: String },
+    #[error("Interrupted, all changes are rolled back")]
+    Interrupted,
+
+    #[error("Password store is not a git repository")]
+    PassStoreShouldBeGitRepo,
+    #[error("Can't initialize git repository: {reason}")]
+    CantInitGitRepo { reason: String },
+    #[error("Git repository already initialized")]
+    GitRepoAlreadyInitialized,
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+pub type Result<T> = std::result::Result<T, Error>;
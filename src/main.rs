@@ -1,163 +1,301 @@
 #![warn(clippy::pedantic)]
-#![allow(clippy::missing_errors_doc, clippy::redundant_closure_for_method_calls)]
+#![allow(
+    clippy::missing_errors_doc,
+    clippy::redundant_closure_for_method_calls,
+    clippy::unnecessary_debug_formatting
+)]
 #![deny(clippy::missing_panics_doc)]
 
 use std::{
     fmt::Display,
-    io::{stderr, stdin, stdout, Write},
+    io::{stdin, stdout, Write},
     path::Path,
     process::{exit, ExitCode},
-    ptr::drop_in_place,
-    string::FromUtf8Error,
-    sync::{Arc, LazyLock, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
     thread::sleep,
     time::Duration,
 };
 
 use anyhow::anyhow;
 use arboard::Clipboard;
-use args::{Args, Command};
+use args::{
+    Args, Command, ConfigCommand, OtpCommand, OutputFormat, RecipientsCommand, StoreCommand,
+};
 use clap::Parser;
-use thiserror::Error;
-
-// TODO: load it smart
-static CLIP_TIME: usize = 45;
-static EDITOR_NAME: LazyLock<String> =
-    LazyLock::new(|| std::env::var("EDITOR").unwrap_or("vi".to_string()));
-static DEFAULT_GENERATED_LENGTH: usize = 25;
+use spass::{
+    api::{self, check_uninitialized_store},
+    config, generate, git, otp,
+    policy::{self, Policy},
+    utils, Entry, Error, Result, Store,
+};
 
 #[allow(clippy::option_option)]
 mod args;
-mod git;
-mod utils;
-
-#[derive(Error, Debug)]
-pub enum Error {
-    #[error(transparent)]
-    IO(#[from] std::io::Error),
-    #[error(transparent)]
-    GPG(#[from] gpgme::Error),
-    #[error(transparent)]
-    FromUtf(#[from] FromUtf8Error),
-    #[error(transparent)]
-    Clipboard(#[from] arboard::Error),
-
-    #[error(
-        "You must run:\n    {} init ...\n before you may use th password store",
-        utils::how_i_invoked()
-    )]
-    PasswordStoreUninitialized,
-    #[error("There is no password to put on the clipboard at line {line_number}")]
-    NoPasswordAtLine { line_number: usize },
-    #[error("Tree command not found. Try install one of [{}]", supported_commands.join(", "))]
-    TreeCommandNotFound { supported_commands: Vec<String> },
-    #[error("the entered passwords do not match")]
-    PasswordsDontMatch,
-    #[error("{pass_name} is not in the password store.\nNote: Your pass will have path {:?}", api::PASS_DIR_ROOT.join(pass_name).to_str().unwrap().to_string() + ".gpg")]
-    PassDoesNotExist { pass_name: String },
+mod audit;
+mod export;
+mod extensions;
+#[cfg(test)]
+mod fixtures;
+mod fuzzy;
+mod grep;
+mod hibp;
+mod import;
+mod item;
+mod output;
+mod pick;
+mod prompt;
+mod qr;
+mod recipients;
+mod tree;
 
-    #[error("Password store is not a git repository")]
-    PassStoreShouldBeGitRepo,
-    #[error("Can't stage file {file_name:?}")]
-    CantStageFile { file_name: String },
-    #[error("Can't initialize git repository")]
-    CantInitGitRepo,
-    #[error("Git repository already initialized")]
-    GitRepoAlreadyInitialized,
-    #[error("Can't commit changes")]
-    CantCommit,
-}
-type Result<T> = std::result::Result<T, Error>;
-
-mod api;
-pub use api::*;
+/// Clipboard set by [`clipboard_copy`], to clear it on exit.
+static CLIPBOARD: Mutex<Option<(Clipboard, config::Selection)>> = Mutex::new(None);
+/// Ctrl-C handler is set, so clipboard is cleared on Ctrl-C.
+static HANDLES_INTERRUPT: AtomicBool = AtomicBool::new(false);
 
 #[allow(clippy::too_many_lines)]
 fn main() -> anyhow::Result<ExitCode> {
     let args = Args::parse();
     // dbg!(&args);
-    match git::unstage_all(api::PASS_DIR_ROOT.as_os_str()) {
-        Ok(()) => (),
-        Err(x) => match x {
-            Error::PassStoreShouldBeGitRepo => (),
-            x => return Err(x.into()),
-        },
-    };
+    let mut store = Store::open(args.store)?;
+    #[allow(clippy::cast_possible_truncation/* Reason: umask is validated on config load */)]
+    // SAFETY: umask has no preconditions
+    unsafe {
+        libc::umask(store.config().umask as libc::mode_t);
+    }
+    set_interrupt_handler(&store);
 
     match args.command {
-        Command::Init { subfolder, gpg_ids } => api::init(subfolder, gpg_ids),
-        Command::List { subfolder } => {
-            check_uninitialized_store()?;
-            let output = run_tree_cmd(&api::PASS_DIR_ROOT.join(subfolder))?;
-            if output.status.success() {
-                let stdout = String::from_utf8(output.stdout)?;
-                println!("Password Store");
-                for line in stdout.lines().skip(1) {
-                    println!("{}", line.replace(".gpg", ""));
+        Command::Init {
+            subfolder,
+            age,
+            gpg_ids,
+        } => {
+            let gpg_ids = if gpg_ids.is_empty() {
+                store.config().default_recipients.clone()
+            } else {
+                gpg_ids
+            };
+            if gpg_ids.is_empty() {
+                return Err(Error::NoRecipients.into());
+            }
+            let backends = store.backends();
+            let backend = if age {
+                backends.age()
+            } else {
+                backends.openpgp()
+            };
+            api::init(&store, &subfolder, &gpg_ids, backend)?;
+        }
+        Command::Recipients { command } => {
+            check_uninitialized_store(&store)?;
+            match command {
+                RecipientsCommand::List { subfolder, format } => {
+                    recipients::list(
+                        &mut stdout().lock(),
+                        &store,
+                        &subfolder,
+                        format.get(OutputFormat::Plain),
+                    )?;
+                }
+                RecipientsCommand::Add { subfolder, ids } => {
+                    recipients::add(&store, &subfolder, &ids)?;
                 }
+                RecipientsCommand::Remove { subfolder, ids } => {
+                    recipients::remove(&store, &subfolder, &ids)?;
+                }
+            }
+        }
+        Command::List {
+            subfolder,
+            format,
+            tree,
+        } => {
+            check_uninitialized_store(&store)?;
+            let root = api::store_path(&store, &subfolder);
+            match format.get(OutputFormat::Tree) {
+                OutputFormat::Plain => output::print_pass_names(&api::walk(&store, &root)?)?,
+                OutputFormat::Json => output::print_json(&api::walk(&store, &root)?)?,
+                OutputFormat::Tree => tree::print(&store, &root, "Password Store", &tree, None)?,
             }
         }
-        Command::Find { pass_names } => {
-            check_uninitialized_store()?;
-            println!("Search Terms: {}", pass_names.join(","));
-            display_matches(pass_names)?;
+        Command::Find {
+            pass_names,
+            fuzzy,
+            format,
+            tree,
+        } => {
+            check_uninitialized_store(&store)?;
+            if fuzzy {
+                let names = store.list()?;
+                let matches = fuzzy::rank(&names, &pass_names);
+                match format.get(OutputFormat::Tree) {
+                    OutputFormat::Json => output::print_json(&matches)?,
+                    OutputFormat::Plain => fuzzy::print(&matches, false)?,
+                    OutputFormat::Tree => fuzzy::print(&matches, tree.color.enabled())?,
+                }
+                return Ok(ExitCode::SUCCESS);
+            }
+            match format.get(OutputFormat::Tree) {
+                OutputFormat::Plain => output::print_pass_names(&find_items(&store, &pass_names)?)?,
+                OutputFormat::Json => output::print_json(&find_items(&store, &pass_names)?)?,
+                OutputFormat::Tree => tree::print(
+                    &store,
+                    store.root(),
+                    &format!("Search Terms: {}", pass_names.join(",")),
+                    &tree,
+                    Some(&pass_names),
+                )?,
+            }
         }
         Command::Show {
             pass_name,
             copy_line,
+            qrcode_line,
+            field,
+            qrcode_format,
+            output,
+            format,
         } => {
-            check_uninitialized_store()?;
-            let copy_line = copy_line.map(|x| x.unwrap_or(1));
-            let pass = std::io::read_to_string(
-                // Safety: we drop `PassFile` after read all content
-                unsafe { api::PassFile::open(pass_name.clone()) }?.content_reader()?,
-            )?;
+            check_uninitialized_store(&store)?;
+            let pass = String::from_utf8(store.get(&pass_name)?)?;
+            let entry = Entry::parse(&pass);
+            let field_value = field
+                .map(|field| {
+                    entry.field(&field).ok_or(Error::NoSuchField {
+                        pass_name: pass_name.clone(),
+                        field,
+                    })
+                })
+                .transpose()?;
+            let selected = |line_number: Option<usize>| match field_value {
+                Some(value) => Ok(value),
+                None => nth_line(&pass, line_number.unwrap_or(1)),
+            };
             if let Some(line_number) = copy_line {
-                if line_number == 0 {
-                    eprintln!("line numbers start from 1, but you write 0");
-                    std::process::exit(1);
+                clipboard_copy(store.config(), selected(line_number)?, &pass_name)?;
+            } else if let Some(line_number) = qrcode_line {
+                let content = selected(line_number)?;
+                match (qrcode_format, output) {
+                    (Some(format), Some(output)) => qr::save(content, format, &output)?,
+                    _ => print!("{}", qr::render_unicode(content)?),
+                }
+            } else if format.get(OutputFormat::Plain) == OutputFormat::Json {
+                match field_value {
+                    Some(value) => output::print_json(&value)?,
+                    None => output::print_json(&output::ShowJson::new(&pass_name, &entry))?,
                 }
-                let content = pass
-                    .lines()
-                    .nth(line_number - 1)
-                    .ok_or(Error::NoPasswordAtLine { line_number })?;
-                clipboard_copy(content, &pass_name)?;
+            } else if let Some(value) = field_value {
+                println!("{value}");
             } else {
                 print!("{pass}");
             }
         }
+        Command::Pick { query, clip, otp } => {
+            check_uninitialized_store(&store)?;
+            let names = store.list()?;
+            let Some(pass_name) = pick::pick(&names, &query)? else {
+                return Ok(ExitCode::FAILURE);
+            };
+            if otp {
+                let code = otp::code(&mut store, &pass_name)?;
+                if clip {
+                    clipboard_copy(store.config(), &code, &pass_name)?;
+                } else {
+                    println!("{code}");
+                }
+            } else {
+                let pass = String::from_utf8(store.get(&pass_name)?)?;
+                if clip {
+                    clipboard_copy(store.config(), nth_line(&pass, 1)?, &pass_name)?;
+                } else {
+                    print!("{pass}");
+                }
+            }
+        }
+        Command::Set {
+            pass_name,
+            field,
+            value,
+        } => {
+            check_uninitialized_store(&store)?;
+            let mut entry = store.get_entry(&pass_name)?;
+            entry.set_field(&field, &value)?;
+            store.put(&pass_name, entry.to_string().as_bytes())?;
+            store.commit(&format!("Set {field} of {pass_name}."))?;
+        }
         Command::Grep {
-            search_string: _,
-            grep_options: _,
-        } => todo!("use grep command"),
+            search_string,
+            options,
+        } => {
+            check_uninitialized_store(&store)?;
+            grep::grep(&mut stdout().lock(), &store, &search_string, &options)?;
+        }
+        Command::Import {
+            format,
+            file,
+            options,
+        } => {
+            check_uninitialized_store(&store)?;
+            if import::import(&mut store, format, &file, &options)? {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Export { options } => {
+            check_uninitialized_store(&store)?;
+            let subfolder = options.subfolder.as_deref().unwrap_or_default();
+            let (root, recursive) = find_pass_root(&store, subfolder)?;
+            let pass_names = if recursive {
+                api::get_pass_names(&store, &root)?
+            } else {
+                vec![subfolder.to_owned()]
+            };
+            export::export(&store, &pass_names, &options)?;
+        }
+        Command::Audit { options } => {
+            check_uninitialized_store(&store)?;
+            if audit::audit(&mut stdout().lock(), &store, &options)? {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
         Command::Insert {
             echo,
             multiline,
             force,
             pass_name,
         } => {
-            check_uninitialized_store()?;
-            // Safety: we don't call to functions that may exit and doesn't drop everything
-            let mut pass_file = unsafe { api::PassFile::create(pass_name.clone(), force) }?;
-            let password = get_password_from_user(&pass_name, echo, multiline)?;
-
-            pass_file.set_commit_msg(format!("Add given password for {pass_name} to store."));
-            pass_file.content_writer()?.write_all(password.as_bytes())?;
+            check_uninitialized_store(&store)?;
+            if !confirm_overwrite(&store, &pass_name, force)? {
+                return Ok(ExitCode::FAILURE);
+            }
+            let password = get_password_from_user(store.config(), &pass_name, echo, multiline)?;
+            store.put(&pass_name, password.as_bytes())?;
+            store.commit(&format!("Add given password for {pass_name} to store."))?;
         }
         Command::Edit { pass_name } => {
-            check_uninitialized_store()?;
+            check_uninitialized_store(&store)?;
 
-            // Safety: we don't call to functions that may exit and doesn't drop everything
-            let mut pass_file = unsafe { api::PassFile::open(pass_name.clone()) }?;
-            pass_file.set_commit_msg(format!(
-                "Edit password for {pass_name} using {}.",
-                &*EDITOR_NAME
-            ));
-            let temp_path = pass_file.get_path_to_unencrypted();
-            std::process::Command::new(&*EDITOR_NAME)
-                .arg(temp_path)
+            let content = store.get(&pass_name)?;
+            let mut tempfile = prompt::create_temp_file()?;
+            tempfile.write_all(&content)?;
+            std::process::Command::new(&store.config().editor)
+                .arg(tempfile.path())
                 .spawn()?
                 .wait()?;
+            let new_content = std::fs::read(tempfile.path())?;
+            tempfile.close()?;
+            if new_content == content {
+                println!("Password for {pass_name} unchanged.");
+            } else {
+                store.put(&pass_name, &new_content)?;
+                store.commit(&format!(
+                    "Edit password for {pass_name} using {}.",
+                    &store.config().editor
+                ))?;
+            }
         }
         Command::Generate {
             length,
@@ -166,30 +304,73 @@ fn main() -> anyhow::Result<ExitCode> {
             in_place,
             force,
             clip,
+            passphrase,
+            policy,
         } => {
-            check_uninitialized_store()?;
-            let password = generate_password(length, no_symbols);
+            check_uninitialized_store(&store)?;
+            let (password, entropy) = if let Some(words) = passphrase.words {
+                let wordlist =
+                    generate::load_wordlist(store.config(), passphrase.wordlist.as_deref())?;
+                let options = generate::PassphraseOptions {
+                    words,
+                    separator: passphrase
+                        .separator
+                        .unwrap_or_else(|| store.config().passphrase_separator.clone()),
+                    capitalize: passphrase.capitalize,
+                    number: passphrase.number,
+                };
+                (
+                    generate::generate_passphrase(&wordlist, &options),
+                    generate::passphrase_entropy(wordlist.len(), &options),
+                )
+            } else {
+                let file_policy = if policy.no_policy {
+                    None
+                } else {
+                    policy::find(&store, &pass_name)?
+                };
+                let mut effective_policy = Policy::default();
+                if let Some((path, file_policy)) = file_policy {
+                    println!(
+                        "Using password policy {}from {}.",
+                        file_policy
+                            .name
+                            .as_ref()
+                            .map_or(String::new(), |x| format!("{x} ")),
+                        api::name_of(&store, &path).unwrap_or_else(|| path.display().to_string())
+                    );
+                    effective_policy = file_policy;
+                }
+                let effective_policy = effective_policy.merge(Policy {
+                    length,
+                    charset: policy.charset,
+                    require: policy.require,
+                    exclude: policy.exclude,
+                    ..Policy::default()
+                });
+                let length = effective_policy.length(store.config())?;
+                let chars = effective_policy.chars(store.config(), no_symbols)?;
+                (
+                    generate::generate_constrained(length, &chars, &effective_policy.require)?,
+                    generate::constrained_entropy(length, &chars, &effective_policy.require),
+                )
+            };
 
             if in_place {
-                // Safety: dropped in the end of if block
-                let mut pass_file = unsafe { api::PassFile::open(pass_name.clone()) }?;
-                pass_file.set_commit_msg(format!("Replace generated password for {pass_name}."));
-                let old_content = std::io::read_to_string(pass_file.content_reader()?)?;
-                let old_content_tail = remove_first_line(&old_content);
-                let new_content = password.clone() + "\n" + &old_content_tail;
-                pass_file
-                    .content_writer()?
-                    .write_all(new_content.as_bytes())?;
+                let mut entry = store.get_entry(&pass_name)?;
+                entry.set_password(&password);
+                store.put(&pass_name, entry.to_string().as_bytes())?;
+                store.commit(&format!("Replace generated password for {pass_name}."))?;
             } else {
-                // Safety: dropped in the end of else block
-                let mut pass_file = unsafe { api::PassFile::create(pass_name.clone(), force) }?;
-                pass_file.set_commit_msg(format!("Add generated password for {pass_name}"));
-                pass_file
-                    .content_writer()?
-                    .write_all((password.clone() + "\n").as_bytes())?;
+                if !confirm_overwrite(&store, &pass_name, force)? {
+                    return Ok(ExitCode::FAILURE);
+                }
+                store.put(&pass_name, (password.clone() + "\n").as_bytes())?;
+                store.commit(&format!("Add generated password for {pass_name}"))?;
             }
+            println!("Generated password for {pass_name} has {entropy:.1} bits of entropy.");
             if clip {
-                clipboard_copy(&password, &pass_name)?;
+                clipboard_copy(store.config(), &password, &pass_name)?;
             }
         }
         Command::Remove {
@@ -197,35 +378,19 @@ fn main() -> anyhow::Result<ExitCode> {
             force,
             recursive,
         } => {
-            check_uninitialized_store()?;
-            let path = if recursive {
-                api::PASS_DIR_ROOT.join(&pass_name)
+            check_uninitialized_store(&store)?;
+            if recursive {
+                store.remove_dir(&pass_name)?;
             } else {
-                api::get_pass_path(&pass_name)
-            };
-            if !path.exists() {
-                return Err(Error::PassDoesNotExist {
-                    pass_name: pass_name.clone(),
-                }
-                .into());
+                store.remove(&pass_name)?;
             }
-            assert_ne!(path, *api::PASS_DIR_ROOT);
-
             // to make it lazy
             let agreement = || -> Result<bool> {
                 print!("Are you sure you would like to delete {pass_name}? ");
-                Ok(utils::yesno(false)?)
+                Ok(prompt::yesno(false)?)
             };
             if force || agreement()? {
-                if recursive {
-                    std::fs::remove_dir_all(path)?;
-                } else {
-                    std::fs::remove_file(path)?;
-                }
-                git::commit_all(
-                    api::PASS_DIR_ROOT.as_os_str(),
-                    &format!("Remove {pass_name} from store."),
-                )?;
+                store.commit(&format!("Remove {pass_name} from store."))?;
             }
         }
         Command::Rename {
@@ -233,78 +398,160 @@ fn main() -> anyhow::Result<ExitCode> {
             old_path: old_pass,
             new_path: new_pass,
         } => {
-            check_uninitialized_store()?;
-            let (old_root, recursive, new_root) = find_recursion(&old_pass, &new_pass)?;
-            copy_move(
-                CopyMove::Move,
-                recursive,
-                &old_root,
-                &new_root,
-                force,
-                &old_pass,
-                &new_pass,
-            )?;
+            check_uninitialized_store(&store)?;
+            copy_move(&mut store, CopyMove::Move, force, &old_pass, &new_pass)?;
         }
         Command::Copy {
             force,
             old_path: old_pass,
             new_path: new_pass,
         } => {
-            check_uninitialized_store()?;
-            let (old_root, recursive, new_root) = find_recursion(&old_pass, &new_pass)?;
-            copy_move(
-                CopyMove::Copy,
-                recursive,
-                &old_root,
-                &new_root,
-                force,
-                &old_pass,
-                &new_pass,
-            )?;
+            check_uninitialized_store(&store)?;
+            copy_move(&mut store, CopyMove::Copy, force, &old_pass, &new_pass)?;
         }
-        Command::Git { git_command_args } => {
+        Command::Otp {
+            command:
+                Some(OtpCommand::Insert {
+                    echo,
+                    force,
+                    append,
+                    pass_name,
+                }),
+            ..
+        } => {
+            check_uninitialized_store(&store)?;
+            if !append && !confirm_overwrite(&store, &pass_name, force)? {
+                return Ok(ExitCode::FAILURE);
+            }
+            let uri = get_otp_uri_from_user(&pass_name, echo)?;
+            otp::insert(&mut store, &pass_name, &uri, append)?;
+        }
+        Command::Otp {
+            command: None,
+            clip,
+            pass_name,
+        } => {
+            check_uninitialized_store(&store)?;
+            let pass_name = pass_name.expect("clap requires pass-name without subcommand");
+            let code = otp::code(&mut store, &pass_name)?;
+            if clip {
+                clipboard_copy(store.config(), &code, &pass_name)?;
+            } else {
+                println!("{code}");
+            }
+        }
+        Command::Config { command } => match command {
+            ConfigCommand::Get { key } => {
+                let (_, value) = config::list(store.config())
+                    .into_iter()
+                    .find(|(x, _)| *x == key)
+                    .ok_or(Error::InvalidConfigValue {
+                        key,
+                        reason: "unknown or unset key".to_owned(),
+                    })?;
+                println!("{value}");
+            }
+            ConfigCommand::Set { local, key, value } => {
+                if local {
+                    config::set(&config::store_config_path(store.root()), &key, &value)?;
+                    match git::commit_file(
+                        store.config(),
+                        store.root().as_os_str(),
+                        config::STORE_CONFIG_NAME,
+                        &format!("Set {key} to {value} in store config."),
+                    ) {
+                        Ok(()) | Err(Error::PassStoreShouldBeGitRepo) => (),
+                        Err(err) => return Err(err.into()),
+                    }
+                } else {
+                    let path = config::user_config_path().ok_or(Error::StoreDirUnknown)?;
+                    config::set(&path, &key, &value)?;
+                }
+            }
+            ConfigCommand::List => {
+                for (key, value) in config::list(store.config()) {
+                    println!("{key} = {value}");
+                }
+            }
+        },
+        Command::Store { command } => match command {
+            StoreCommand::Add { name, path } => {
+                let root = path.canonicalize()?;
+                if !root.is_dir() {
+                    return Err(Error::NotADirectory { path: root }.into());
+                }
+                let config_path = config::user_config_path().ok_or(Error::StoreDirUnknown)?;
+                config::set_mount(&config_path, &name, Some(&root))?;
+                if store.root().join(&name).is_dir() {
+                    eprintln!("Warning: folder {name} of the main store is hidden by the mount.");
+                }
+                println!("Mounted {root:?} as {name}.");
+            }
+            StoreCommand::Remove { name } => {
+                let config_path = config::user_config_path().ok_or(Error::StoreDirUnknown)?;
+                config::set_mount(&config_path, &name, None)?;
+                println!("Unmounted {name}.");
+            }
+            StoreCommand::List => {
+                for (name, root) in &store.config().mounts {
+                    println!("{name}: {}", root.display());
+                }
+            }
+        },
+        Command::Git {
+            mut git_command_args,
+        } => {
+            // `spass git team log` runs in mounted store `team`
+            let root = match git_command_args
+                .first()
+                .and_then(|x| store.config().mounts.get(x))
+            {
+                Some(root) => {
+                    let root = root.clone();
+                    git_command_args.remove(0);
+                    root
+                }
+                None => store.root().to_owned(),
+            };
             if git_command_args
                 .first()
                 .ok_or(anyhow!("You should provide at least 1 argument for git"))?
                 == "init"
             {
                 git::init(
-                    api::PASS_DIR_ROOT.as_os_str(),
+                    store.config(),
+                    root.as_os_str(),
                     git_command_args.into_iter().skip(1),
                 )?;
             } else {
-                let exit_code =
-                    git::command(api::PASS_DIR_ROOT.as_os_str(), git_command_args.into_iter())?
-                        .code();
+                let exit_code = git::command(root.as_os_str(), git_command_args)?.code();
                 if exit_code.is_some_and(|c| c != 0) {
                     return Ok(ExitCode::FAILURE);
                 }
             }
         }
-    };
+        Command::Extension(args) => {
+            let (name, args) = args
+                .split_first()
+                .expect("clap gives at least subcommand name");
+            if !extensions::run(&store, name, args)?.success() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
     Ok(ExitCode::SUCCESS)
 }
 
 fn find_recursion(
-    old_pass: &String,
-    new_pass: &String,
+    store: &Store,
+    old_pass: &str,
+    new_pass: &str,
 ) -> Result<(std::path::PathBuf, bool, std::path::PathBuf)> {
-    let old_path_dir = api::PASS_DIR_ROOT.join(old_pass);
-    let (old_root, recursive) = if old_path_dir.is_dir() {
-        (old_path_dir, true)
-    } else {
-        let old_path_file = api::get_pass_path(old_pass);
-        if !old_path_file.exists() {
-            return Err(Error::PassDoesNotExist {
-                pass_name: old_pass.clone(),
-            });
-        }
-        (old_path_file, false)
-    };
+    let (old_root, recursive) = find_pass_root(store, old_pass)?;
     let new_root = if recursive {
-        api::PASS_DIR_ROOT.join(new_pass)
+        api::store_path(store, new_pass)
     } else {
-        api::get_pass_path(new_pass)
+        api::get_pass_path(store, &new_pass)
     };
     Ok((old_root, recursive, new_root))
 }
@@ -314,133 +561,88 @@ enum CopyMove {
     Copy,
     Move,
 }
+/// Directory `pass` with `true`, or pass file `pass` with `false`.
+fn find_pass_root(store: &Store, pass: &str) -> Result<(std::path::PathBuf, bool)> {
+    let path_dir = api::store_path(store, pass);
+    if path_dir.is_dir() {
+        return Ok((path_dir, true));
+    }
+    let path_file = api::get_pass_path(store, &pass);
+    if !path_file.exists() {
+        return Err(Error::PassDoesNotExist {
+            pass_name: pass.to_owned(),
+            path: path_file,
+        });
+    }
+    Ok((path_file, false))
+}
+
+/// Copy or move pass files atomically, reencrypting them for new location.
 fn copy_move(
+    store: &mut Store,
     copy_move: CopyMove,
-    recursive: bool,
-    old_root: &Path,
-    new_root: &Path,
     force: bool,
     old_pass: &str,
     new_pass: &str,
 ) -> Result<()> {
+    let (old_root, recursive, new_root) = find_recursion(store, old_pass, new_pass)?;
+    let confirm = |path: &Path| confirm_overwrite_path(path, force);
     if recursive {
-        assert_ne!(old_root, *api::PASS_DIR_ROOT);
-        let mut pass_files = get_pass_files_recursively(old_root, new_root)?;
         match copy_move {
-            CopyMove::Copy => {
-                for (pass_file, new_name) in &mut pass_files {
-                    pass_file.copy(new_name.to_owned(), force)?;
-                }
-            }
-            CopyMove::Move => {
-                for (pass_file, new_name) in &mut pass_files {
-                    pass_file.rename(new_name.to_owned(), force)?;
-                }
-                // NOTE: required to run before drop
-                std::fs::remove_dir_all(old_root)?;
-            }
+            CopyMove::Copy => store.copy_dir(old_pass, new_pass, confirm)?,
+            CopyMove::Move => store.rename_dir(old_pass, new_pass, confirm)?,
         }
-        drop(pass_files);
-        git::commit_all(
-            api::PASS_DIR_ROOT.as_os_str(),
-            &format!(
-                "{operation} {old_pass} to {new_pass}.",
-                operation = match copy_move {
-                    CopyMove::Copy => "Copy",
-                    CopyMove::Move => "Move",
-                },
-            ),
-        )?;
-    } else {
-        // Safety: pass file will be dropped at the and of else block.
-        let mut pass_file = unsafe { api::PassFile::open(old_pass.to_string()) }?;
+    } else if old_root != new_root && (!new_root.exists() || confirm(&new_root)?) {
         match copy_move {
-            CopyMove::Copy => {
-                pass_file.set_commit_msg(format!("Copy {old_pass} to {new_pass}."));
-                pass_file.copy(new_pass.to_string(), force)?;
-            }
-            CopyMove::Move => {
-                pass_file.set_commit_msg(format!("Rename {old_pass} to {new_pass}."));
-                pass_file.rename(new_pass.to_string(), force)?;
-            }
+            CopyMove::Copy => store.copy(old_pass, new_pass)?,
+            CopyMove::Move => store.rename(old_pass, new_pass)?,
         }
-        drop(pass_file);
     }
-    Ok(())
+    store.commit(&format!(
+        "{operation} {old_pass} to {new_pass}.",
+        operation = match (copy_move, recursive) {
+            (CopyMove::Copy, _) => "Copy",
+            (CopyMove::Move, true) => "Move",
+            (CopyMove::Move, false) => "Rename",
+        },
+    ))
 }
 
-/// # Returns
-/// Vec<(pass file, new pass name if `old_root` change to `new_root`)>
-fn get_pass_files_recursively(old_root: &Path, new_root: &Path) -> Result<Vec<(PassFile, String)>> {
-    let files = walkdir::WalkDir::new(old_root).contents_first(true);
-    let pass_files = files
-        .into_iter()
-        .filter_entry(|x| {
-            x.file_type().is_file() && x.path().extension().is_some_and(|ext| ext == "gpg")
-        })
-        .filter_map(|x| x.ok())
-        .map(|x| x.into_path())
-        .map(|x| {
-            (
-                x.strip_prefix(&*api::PASS_DIR_ROOT)
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .strip_suffix(".gpg")
-                    .unwrap()
-                    .to_string(),
-                new_root
-                    .join(x.strip_prefix(old_root).unwrap().to_str().unwrap())
-                    .strip_prefix(&*api::PASS_DIR_ROOT)
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .strip_suffix(".gpg")
-                    .unwrap()
-                    .to_string(),
-            )
-        })
-        .map(|(old_pass_name, new_pass_name)| -> Result<_> {
-            // Safety: pass files will be dropped at the and of else block.
-            Ok((
-                unsafe { api::PassFile::open(old_pass_name) }?,
-                new_pass_name,
-            ))
-        })
-        .collect::<std::result::Result<Vec<_>, _>>()?;
-    Ok(pass_files)
+/// Ask user before overwriting existing `pass_name`, unless `force`.
+fn confirm_overwrite(store: &Store, pass_name: &str, force: bool) -> Result<bool> {
+    let path = api::get_pass_path(store, &pass_name);
+    if !path.exists() {
+        return Ok(true);
+    }
+    confirm_overwrite_path(&path, force)
 }
 
-fn remove_first_line(old_content: &str) -> String {
-    let mut old_content_tail = old_content.lines().skip(1).collect::<Vec<&str>>();
-    if !old_content_tail.is_empty() {
-        old_content_tail.push(""); // for "\n" at the and
+/// Ask user before overwriting existing file at `path`, unless `force`.
+fn confirm_overwrite_path(path: &Path, force: bool) -> Result<bool> {
+    if force {
+        return Ok(true);
     }
-
-    old_content_tail.join("\n")
+    print!("An entry already exists for {path:?}. Overwrite it? ");
+    Ok(prompt::yesno(false)?)
 }
 
-fn generate_password(length: usize, no_symbols: bool) -> String {
-    use rand::prelude::*;
-    let letters = String::from("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ");
-    let numbers = String::from("0123456789");
-    let symbols = String::from(r#"!\#"$%&'()*+,-./:;<=>?@[\\]^_`{¦}~"#);
-    let chars = if no_symbols {
-        letters.chars().chain(numbers.chars()).collect::<Vec<_>>()
-    } else {
-        letters
-            .chars()
-            .chain(numbers.chars())
-            .chain(symbols.chars())
-            .collect::<Vec<_>>()
-    };
-    let a = rand::distributions::Slice::new(&chars).unwrap();
-    let rand_char = rand::rngs::StdRng::sample_iter(rand::rngs::StdRng::from_entropy(), a);
-
-    rand_char.take(length).collect()
+fn nth_line(content: &str, line_number: usize) -> Result<&str> {
+    if line_number == 0 {
+        eprintln!("line numbers start from 1, but you write 0");
+        std::process::exit(1);
+    }
+    content
+        .lines()
+        .nth(line_number - 1)
+        .ok_or(Error::NoPasswordAtLine { line_number })
 }
 
-fn get_password_from_user(pass_name: &str, echo: bool, multiline: bool) -> Result<String> {
+fn get_password_from_user(
+    config: &config::Config,
+    pass_name: &str,
+    echo: bool,
+    multiline: bool,
+) -> Result<String> {
     let password = if echo {
         print!("Enter password for {pass_name}: ");
         stdout().flush()?;
@@ -448,8 +650,8 @@ fn get_password_from_user(pass_name: &str, echo: bool, multiline: bool) -> Resul
         stdin().read_line(&mut password)?;
         password
     } else if multiline {
-        let tempfile = utils::create_temp_file()?;
-        std::process::Command::new(&*EDITOR_NAME)
+        let tempfile = prompt::create_temp_file()?;
+        std::process::Command::new(&config.editor)
             .arg(tempfile.path())
             .spawn()?
             .wait()?;
@@ -466,96 +668,144 @@ fn get_password_from_user(pass_name: &str, echo: bool, multiline: bool) -> Resul
     Ok(password)
 }
 
-fn display_matches(pass_names: Vec<String>) -> Result<()> {
-    let output = run_tree_cmd(&api::PASS_DIR_ROOT)?;
-    if output.status.success() {
-        let output = String::from_utf8(output.stdout)?;
-
-        let lines = output.lines().skip(1);
-        let matches = filter_matches(lines, pass_names)
-            .map(|str| str.replace(".gpg", ""))
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        println!("{matches}");
-    }
-
-    Ok(())
+fn get_otp_uri_from_user(pass_name: &str, echo: bool) -> Result<String> {
+    let uri = if echo {
+        print!("Enter otpauth:// URI for {pass_name}: ");
+        stdout().flush()?;
+        let mut uri = String::new();
+        stdin().read_line(&mut uri)?;
+        uri
+    } else {
+        let uri = rpassword::prompt_password(format!("Enter otpauth:// URI for {pass_name}: "))?;
+        if uri != rpassword::prompt_password(format!("Retype otpauth:// URI for {pass_name}: "))? {
+            return Err(Error::PasswordsDontMatch);
+        }
+        uri
+    };
+    Ok(uri)
 }
 
-fn filter_matches<'a>(
-    lines: impl Iterator<Item = &'a str>,
-    words: impl IntoIterator<Item = impl AsRef<[u8]>>,
-) -> impl Iterator<Item = &'a str> {
+/// Directories and pass files with any of `terms` in their name.
+fn find_items(store: &Store, terms: &[String]) -> Result<Vec<api::StoreItem>> {
     let finder = aho_corasick::AhoCorasick::builder()
         .ascii_case_insensitive(true)
-        .build(words)
+        .build(terms)
         .unwrap();
-
-    lines.filter(move |str| finder.find(str).is_some())
+    let mut items = api::walk(store, store.root())?;
+    items.retain(|x| finder.find(x.name()).is_some());
+    Ok(items)
 }
 
-fn run_tree_cmd(path: &Path) -> Result<std::process::Output> {
-    let mut cmd_id = 0;
-    let output = std::process::Command::new("eza")
-        .arg(path)
-        .args(["--tree", "--color=always", "--dereference"])
-        .stderr(stderr())
-        .output()
-        .or_else(|_| {
-            cmd_id = 1;
-            std::process::Command::new("tree")
-                .arg(path)
-                .arg("-C")
-                .arg("--noreport")
-                .arg("--prune")
-                .stderr(stderr())
-                .output()
-        })
-        .map_err(|_| Error::TreeCommandNotFound {
-            supported_commands: vec!["eza".to_string(), "tree".to_string()],
-        })?;
-    // Some genius tree developer print error to stdout
-    if cmd_id == 1 {
-        eprintln!("{}", String::from_utf8_lossy(&output.stdout));
-    }
-    Ok(output)
+/// Ctrl-C rolls back changes that are being applied to `store`.
+/// Otherwise it clears clipboard set by [`clipboard_copy`] and exits.
+/// If handler can't be set, Ctrl-C just kills process.
+fn set_interrupt_handler(store: &Store) {
+    let interrupt = store.interrupt();
+    let result = ctrlc::set_handler(move || {
+        if interrupt.request() {
+            return;
+        }
+        if CLIPBOARD.lock().is_ok_and(|x| x.is_some()) {
+            clear_and_exit();
+        }
+        exit(130);
+    });
+    HANDLES_INTERRUPT.store(result.is_ok(), Ordering::SeqCst);
 }
 
 /// # Warning
 /// On success this function doesn't return
-fn clipboard_copy(content: &str, name: &str) -> anyhow::Result<()> {
-    let clipboard = Arc::new(Mutex::new(Clipboard::new()?));
-    clipboard.lock().unwrap().set_text(content)?;
+fn clipboard_copy(config: &config::Config, content: &str, name: &str) -> anyhow::Result<()> {
+    let clip_time = config.clip_time;
+    let mut clipboard = Clipboard::new()?;
+    set_clipboard_text(&mut clipboard, config.x_selection, content)?;
+    *CLIPBOARD.lock().unwrap() = Some((clipboard, config.x_selection));
     println!("Copied {name} to clipboard.");
-    let same_clipboard = clipboard.clone();
-    match ctrlc::set_handler(move || {
-        clear_and_exit(&same_clipboard);
-    }) {
-        Ok(()) => {
-            println!("Clipboard will be cleared in {CLIP_TIME} seconds or if you cancel program.");
-        }
-        Err(_) => {
-            println!("Clipboard will be cleared in {CLIP_TIME} seconds, don't cancel program.");
-        }
-    };
-    sleep(Duration::from_secs(CLIP_TIME as u64));
-    clear_and_exit(&clipboard);
+    if HANDLES_INTERRUPT.load(Ordering::SeqCst) {
+        println!("Clipboard will be cleared in {clip_time} seconds or if you cancel program.");
+    } else {
+        println!("Clipboard will be cleared in {clip_time} seconds, don't cancel program.");
+    }
+    sleep(Duration::from_secs(clip_time));
+    clear_and_exit();
+}
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+fn linux_clipboard_kind(selection: config::Selection) -> arboard::LinuxClipboardKind {
+    match selection {
+        config::Selection::Clipboard => arboard::LinuxClipboardKind::Clipboard,
+        config::Selection::Primary => arboard::LinuxClipboardKind::Primary,
+        config::Selection::Secondary => arboard::LinuxClipboardKind::Secondary,
+    }
 }
 
-fn clear_and_exit(clipboard: &Mutex<Clipboard>) -> ! {
-    match clipboard.lock() {
+fn set_clipboard_text(
+    clipboard: &mut Clipboard,
+    selection: config::Selection,
+    content: &str,
+) -> Result<()> {
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+    ))]
+    {
+        use arboard::SetExtLinux;
+        clipboard
+            .set()
+            .clipboard(linux_clipboard_kind(selection))
+            .text(content)?;
+    }
+    #[cfg(not(all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+    )))]
+    {
+        let _ = selection;
+        clipboard.set_text(content)?;
+    }
+    Ok(())
+}
+
+fn clear_clipboard(
+    clipboard: &mut Clipboard,
+    selection: config::Selection,
+) -> std::result::Result<(), arboard::Error> {
+    #[cfg(all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+    ))]
+    {
+        use arboard::ClearExtLinux;
+        clipboard
+            .clear_with()
+            .clipboard(linux_clipboard_kind(selection))
+    }
+    #[cfg(not(all(
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+    )))]
+    {
+        let _ = selection;
+        clipboard.clear()
+    }
+}
+
+fn clear_and_exit() -> ! {
+    match CLIPBOARD.lock() {
         Ok(mut clipboard) => {
-            match clipboard.clear() {
-                Ok(()) => {
-                    // Safety: after drop we exit, so other can't get access to clipboard
-                    unsafe {
-                        drop_in_place(&mut *clipboard);
+            // lock is held until exit, so clipboard is cleared once
+            if let Some((mut clipboard, selection)) = clipboard.take() {
+                match clear_clipboard(&mut clipboard, selection) {
+                    Ok(()) => {
+                        drop(clipboard);
+                        println!("Clipboard cleared.");
                     }
-                    println!("Clipboard cleared.");
+                    Err(err) => cant_clear_clipboard(err),
                 }
-                Err(err) => cant_clear_clipboard(err),
-            };
+            }
         }
         Err(err) => cant_clear_clipboard(err),
     }
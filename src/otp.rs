@@ -0,0 +1,324 @@
+use std::{
+    ops::Range,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use url::Url;
+
+use crate::{Error, Result, Store};
+
+const URI_PREFIX: &str = "otpauth://";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpKind {
+    /// RFC 6238
+    Totp { period: u64 },
+    /// RFC 4226
+    Hotp { counter: u64 },
+}
+
+/// Parsed `otpauth://` URI.
+#[derive(Debug, Clone)]
+pub struct OtpAuth {
+    uri: Url,
+    kind: OtpKind,
+    secret: Vec<u8>,
+    algorithm: Algorithm,
+    digits: u32,
+}
+
+impl FromStr for OtpAuth {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidOtpUri {
+            reason: reason.to_owned(),
+        };
+        let uri = Url::parse(s.trim()).map_err(|err| invalid(&err.to_string()))?;
+        if uri.scheme() != "otpauth" {
+            return Err(invalid("scheme should be `otpauth`"));
+        }
+
+        let mut secret = None;
+        let mut algorithm = Algorithm::Sha1;
+        let mut digits = 6;
+        let mut period = 30;
+        let mut counter = None;
+        for (key, value) in uri.query_pairs() {
+            match &*key {
+                "secret" => secret = Some(decode_secret(&value).ok_or(invalid("bad secret"))?),
+                "algorithm" => {
+                    algorithm = match value.to_uppercase().as_str() {
+                        "SHA1" => Algorithm::Sha1,
+                        "SHA256" => Algorithm::Sha256,
+                        "SHA512" => Algorithm::Sha512,
+                        _ => return Err(invalid("unsupported algorithm")),
+                    }
+                }
+                "digits" => {
+                    digits = value.parse().map_err(|_| invalid("bad digits"))?;
+                    if !(6..=10).contains(&digits) {
+                        return Err(invalid("digits should be from 6 to 10"));
+                    }
+                }
+                "period" => {
+                    period = value.parse().map_err(|_| invalid("bad period"))?;
+                    if period == 0 {
+                        return Err(invalid("period should be positive"));
+                    }
+                }
+                "counter" => counter = Some(value.parse().map_err(|_| invalid("bad counter"))?),
+                _ => (),
+            }
+        }
+
+        let kind = match uri.host_str() {
+            Some("totp") => OtpKind::Totp { period },
+            Some("hotp") => OtpKind::Hotp {
+                counter: counter.ok_or(invalid("hotp requires counter"))?,
+            },
+            _ => return Err(invalid("type should be `totp` or `hotp`")),
+        };
+        Ok(Self {
+            uri,
+            kind,
+            secret: secret.ok_or(invalid("secret is missing"))?,
+            algorithm,
+            digits,
+        })
+    }
+}
+
+impl OtpAuth {
+    #[must_use]
+    pub fn kind(&self) -> OtpKind {
+        self.kind
+    }
+    /// Code for current time (TOTP) or current counter (HOTP).
+    #[allow(clippy::missing_panics_doc/* Reason: system time is after unix epoch */)]
+    #[must_use]
+    pub fn code(&self) -> String {
+        match self.kind {
+            OtpKind::Totp { period } => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                self.code_at(now / period)
+            }
+            OtpKind::Hotp { counter } => self.code_at(counter),
+        }
+    }
+    /// HOTP value for `counter`, see RFC 4226 section 5.3.
+    #[must_use]
+    pub fn code_at(&self, counter: u64) -> String {
+        let digest = match self.algorithm {
+            Algorithm::Sha1 => hmac_digest::<Hmac<sha1::Sha1>>(&self.secret, counter),
+            Algorithm::Sha256 => hmac_digest::<Hmac<sha2::Sha256>>(&self.secret, counter),
+            Algorithm::Sha512 => hmac_digest::<Hmac<sha2::Sha512>>(&self.secret, counter),
+        };
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            digest[offset] & 0x7f,
+            digest[offset + 1],
+            digest[offset + 2],
+            digest[offset + 3],
+        ]);
+        let code = u64::from(binary) % 10u64.pow(self.digits);
+        format!("{code:0width$}", width = self.digits as usize)
+    }
+    /// Only HOTP has counter, for TOTP it does nothing.
+    pub fn increment_counter(&mut self) {
+        if let OtpKind::Hotp { ref mut counter } = self.kind {
+            *counter += 1;
+            // other parameters are kept as they are written, not encoded again
+            let query = self
+                .uri
+                .query()
+                .unwrap_or_default()
+                .split('&')
+                .map(|pair| match pair.split_once('=') {
+                    Some(("counter", _)) => format!("counter={counter}"),
+                    _ => pair.to_owned(),
+                })
+                .collect::<Vec<_>>()
+                .join("&");
+            self.uri.set_query(Some(&query));
+        }
+    }
+    #[must_use]
+    pub fn uri(&self) -> &str {
+        self.uri.as_str()
+    }
+}
+
+fn hmac_digest<M: Mac + hmac::digest::KeyInit>(key: &[u8], counter: u64) -> Vec<u8> {
+    let mut mac =
+        <M as hmac::digest::KeyInit>::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Secrets are base32 without padding, but people write them in
+/// lowercase and with spaces.
+fn decode_secret(secret: &str) -> Option<Vec<u8>> {
+    let secret = secret
+        .chars()
+        .filter(|x| !x.is_whitespace() && *x != '=' && *x != '-')
+        .collect::<String>()
+        .to_uppercase();
+    data_encoding::BASE32_NOPAD.decode(secret.as_bytes()).ok()
+}
+
+/// # Returns
+/// Byte range of first otpauth URI in `content`, it ends at whitespace.
+/// Only this range is replaced on update, so `otp: ` prefixes and
+/// trailing comments on the same line are kept.
+fn find_uri(content: &str) -> Option<Range<usize>> {
+    let start = content.find(URI_PREFIX)?;
+    let end = content[start..]
+        .find(char::is_whitespace)
+        .map_or(content.len(), |x| start + x);
+    Some(start..end)
+}
+
+/// Generate OTP code from URI stored in `pass_name`.
+/// For HOTP counter is incremented, reencrypted and commited
+/// together with other staged changes of `store`.
+pub fn code(store: &mut Store, pass_name: &str) -> Result<String> {
+    let content = String::from_utf8(store.get(pass_name)?)?;
+    let range = find_uri(&content).ok_or(Error::NoOtpUri {
+        pass_name: pass_name.to_owned(),
+    })?;
+    let mut otp = content[range.clone()].parse::<OtpAuth>()?;
+    let code = otp.code();
+
+    if let OtpKind::Hotp { .. } = otp.kind() {
+        otp.increment_counter();
+        let mut new_content = content;
+        new_content.replace_range(range, otp.uri());
+        store.put(pass_name, new_content.as_bytes())?;
+        store.commit(&format!("Increment HOTP counter for {pass_name}."))?;
+    }
+    Ok(code)
+}
+
+/// Validate `uri` and save it to `pass_name`, existing one is overwritten.
+/// If `append` is true, add it to the end of existing pass file.
+pub fn insert(store: &mut Store, pass_name: &str, uri: &str, append: bool) -> Result<()> {
+    let uri = uri.trim();
+    uri.parse::<OtpAuth>()?;
+
+    if append {
+        let mut content = String::from_utf8(store.get(pass_name)?)?;
+        if let Some(range) = find_uri(&content) {
+            content.replace_range(range, uri);
+        } else {
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content.push_str(uri);
+            content.push('\n');
+        }
+        store.put(pass_name, content.as_bytes())?;
+        store.commit(&format!("Append OTP secret for {pass_name} to store."))
+    } else {
+        store.put(pass_name, (uri.to_owned() + "\n").as_bytes())?;
+        store.commit(&format!("Add OTP secret for {pass_name} to store."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn otp(kind: &str, secret: &[u8], algorithm: &str, digits: u32) -> OtpAuth {
+        let secret = data_encoding::BASE32_NOPAD.encode(secret);
+        format!(
+            "otpauth://{kind}/test?secret={secret}&algorithm={algorithm}&digits={digits}&counter=0"
+        )
+        .parse()
+        .unwrap()
+    }
+
+    /// RFC 4226 appendix D.
+    #[test]
+    fn hotp_vectors() {
+        let otp = otp("hotp", b"12345678901234567890", "SHA1", 6);
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+        for (counter, code) in (0..).zip(expected) {
+            assert_eq!(otp.code_at(counter), code);
+        }
+    }
+
+    /// RFC 6238 appendix B.
+    #[test]
+    fn totp_vectors() {
+        let sha1 = otp("totp", b"12345678901234567890", "SHA1", 8);
+        let sha256 = otp("totp", b"12345678901234567890123456789012", "SHA256", 8);
+        let sha512 = otp(
+            "totp",
+            b"1234567890123456789012345678901234567890123456789012345678901234",
+            "SHA512",
+            8,
+        );
+        let expected = [
+            (59, "94287082", "46119246", "90693936"),
+            (1_111_111_109, "07081804", "68084774", "25091201"),
+            (1_111_111_111, "14050471", "67062674", "99943326"),
+            (1_234_567_890, "89005924", "91819424", "93441116"),
+            (2_000_000_000, "69279037", "90698825", "38618901"),
+            (20_000_000_000, "65353130", "77737706", "47863826"),
+        ];
+        for (time, code1, code256, code512) in expected {
+            assert_eq!(sha1.code_at(time / 30), code1);
+            assert_eq!(sha256.code_at(time / 30), code256);
+            assert_eq!(sha512.code_at(time / 30), code512);
+        }
+    }
+
+    #[test]
+    fn increment_counter_replaces_only_uri() {
+        let content = "password\notp: otpauth://hotp/a?secret=GEZDGNBV&counter=7 # backup\n";
+        let range = find_uri(content).unwrap();
+        let mut otp = content[range.clone()].parse::<OtpAuth>().unwrap();
+        otp.increment_counter();
+        let mut new_content = content.to_owned();
+        new_content.replace_range(range, otp.uri());
+        assert_eq!(
+            new_content,
+            "password\notp: otpauth://hotp/a?secret=GEZDGNBV&counter=8 # backup\n"
+        );
+    }
+
+    #[test]
+    fn increment_counter_keeps_encoding_of_parameters() {
+        let uri =
+            "otpauth://hotp/ACME%20Co:alice?secret=GEZDGNBV&issuer=ACME%20Co&counter=7&digits=6";
+        let mut otp = uri.parse::<OtpAuth>().unwrap();
+        otp.increment_counter();
+        assert_eq!(
+            otp.uri(),
+            "otpauth://hotp/ACME%20Co:alice?secret=GEZDGNBV&issuer=ACME%20Co&counter=8&digits=6"
+        );
+    }
+
+    #[test]
+    fn uri_at_end_of_content() {
+        let content = "otpauth://totp/a?secret=GEZDGNBV";
+        assert_eq!(find_uri(content), Some(0..content.len()));
+        assert_eq!(find_uri("no uri\n"), None);
+    }
+}
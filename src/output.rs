@@ -0,0 +1,83 @@
+use std::io::Write;
+
+use serde::Serialize;
+use spass::{api::StoreItem, Entry, Result};
+
+/// Pretty JSON of `value` on stdout.
+pub fn print_json(value: &impl Serialize) -> Result<()> {
+    write_json(&mut std::io::stdout().lock(), value)
+}
+
+/// Pretty JSON of `value` with newline.
+pub fn write_json(out: &mut impl Write, value: &impl Serialize) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *out, value).map_err(std::io::Error::from)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Pass names one per line, directories are implied by them.
+pub fn print_pass_names(items: &[StoreItem]) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    for item in items {
+        if let StoreItem::Pass(pass_name) = item {
+            writeln!(stdout, "{pass_name}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Parsed entry for `show --json`.
+#[derive(Serialize)]
+pub struct ShowJson<'a> {
+    name: &'a str,
+    password: &'a str,
+    /// In order of lines, fields with the same name are all kept.
+    fields: Vec<Field<'a>>,
+    notes: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Field<'a> {
+    name: &'a str,
+    value: &'a str,
+}
+
+impl<'a> ShowJson<'a> {
+    pub fn new(name: &'a str, entry: &'a Entry) -> Self {
+        Self {
+            name,
+            password: entry.password(),
+            fields: entry
+                .fields()
+                .map(|(name, value)| Field { name, value })
+                .collect(),
+            notes: entry.notes().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn show_json_keeps_duplicate_fields() {
+        let entry = Entry::parse(
+            "secret\nurl: https://a.example\nlogin: me\nURL: https://b.example\nnote\n",
+        );
+        let json = serde_json::to_value(ShowJson::new("site", &entry)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "name": "site",
+                "password": "secret",
+                "fields": [
+                    {"name": "url", "value": "https://a.example"},
+                    {"name": "login", "value": "me"},
+                    {"name": "URL", "value": "https://b.example"},
+                ],
+                "notes": ["note"],
+            })
+        );
+    }
+}
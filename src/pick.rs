@@ -0,0 +1,137 @@
+use std::io::{stderr, IsTerminal, Stderr, Write};
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Attribute, Color, Print, SetAttribute, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use spass::{Error, Result};
+
+use crate::fuzzy::{self, Match};
+
+/// Full screen on stderr in raw mode, so stdout can be piped.
+/// Terminal is restored on drop, also after errors.
+struct Screen(Stderr);
+
+impl Screen {
+    fn new() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut stderr = stderr();
+        queue!(stderr, terminal::EnterAlternateScreen)?;
+        Ok(Self(stderr))
+    }
+
+    fn draw(
+        &mut self,
+        query: &str,
+        matches: &[Match],
+        selected: usize,
+        total: usize,
+    ) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let rows = usize::from(height).saturating_sub(2);
+        let scroll = (selected + 1).saturating_sub(rows);
+        let out = &mut self.0;
+        queue!(
+            out,
+            terminal::Clear(ClearType::All),
+            cursor::MoveTo(0, 1),
+            SetForegroundColor(Color::DarkGrey),
+            Print(format!("  {}/{total}", matches.len())),
+            SetForegroundColor(Color::Reset),
+        )?;
+        for (row, (i, x)) in (2..height).zip(matches.iter().enumerate().skip(scroll)) {
+            queue!(out, cursor::MoveTo(0, row))?;
+            if i == selected {
+                queue!(out, SetAttribute(Attribute::Bold), Print("> "))?;
+            } else {
+                queue!(out, Print("  "))?;
+            }
+            for (position, char) in x
+                .name
+                .chars()
+                .enumerate()
+                .take(usize::from(width).saturating_sub(2))
+            {
+                if x.positions.contains(&position) {
+                    queue!(
+                        out,
+                        SetForegroundColor(Color::Red),
+                        Print(char),
+                        SetForegroundColor(Color::Reset)
+                    )?;
+                } else {
+                    queue!(out, Print(char))?;
+                }
+            }
+            queue!(out, SetAttribute(Attribute::Reset))?;
+        }
+        let cursor_column = u16::try_from(query.chars().count() + 2).unwrap_or(width);
+        queue!(
+            out,
+            cursor::MoveTo(0, 0),
+            Print("> "),
+            Print(query),
+            cursor::MoveTo(cursor_column, 0),
+        )?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        // nothing to do if terminal can't be restored
+        let _ = queue!(self.0, terminal::LeaveAlternateScreen);
+        let _ = self.0.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Let user choose one of `names` with fuzzy search, starting with `query`.
+/// `None` if user cancels it with Esc or Ctrl-C.
+pub fn pick(names: &[String], query: &str) -> Result<Option<String>> {
+    if !stderr().is_terminal() {
+        return Err(Error::NotATerminal);
+    }
+    let mut screen = Screen::new()?;
+    let mut query = query.to_owned();
+    let mut selected = 0;
+    loop {
+        let matches = fuzzy::rank(names, &[&query]);
+        selected = selected.min(matches.len().saturating_sub(1));
+        screen.draw(&query, &matches, selected, names.len())?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(x) = matches.get(selected) {
+                    return Ok(Some(x.name.to_owned()));
+                }
+            }
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c' | 'g' | 'd') if ctrl => return Ok(None),
+            KeyCode::Up | KeyCode::BackTab => selected = selected.saturating_sub(1),
+            KeyCode::Char('p' | 'k') if ctrl => selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Tab => selected += 1,
+            KeyCode::Char('n' | 'j') if ctrl => selected += 1,
+            KeyCode::Char('u') if ctrl => query.clear(),
+            KeyCode::Backspace => {
+                query.pop();
+            }
+            KeyCode::Char(char) if !ctrl => {
+                query.push(char);
+                selected = 0;
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{api, config::Config, generate::expand_charset, Error, Result, Store};
+
+/// Name of file with [`Policy`] for passwords in its directory and subdirectories.
+pub const POLICY_FILE_NAME: &str = ".spass-policy";
+
+/// Class of characters that [`Policy::require`] can ask for.
+/// Every character belongs to exactly one class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CharClass {
+    Upper,
+    Lower,
+    Digit,
+    /// Everything else.
+    Symbol,
+}
+
+impl CharClass {
+    /// In order of [`Self::index`].
+    pub const ALL: [CharClass; 4] = [Self::Upper, Self::Lower, Self::Digit, Self::Symbol];
+
+    #[must_use]
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    #[must_use]
+    pub fn of(char: char) -> Self {
+        if char.is_uppercase() {
+            Self::Upper
+        } else if char.is_lowercase() {
+            Self::Lower
+        } else if char.is_numeric() {
+            Self::Digit
+        } else {
+            Self::Symbol
+        }
+    }
+}
+
+impl FromStr for CharClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "upper" => Ok(Self::Upper),
+            "lower" => Ok(Self::Lower),
+            "digit" => Ok(Self::Digit),
+            "symbol" => Ok(Self::Symbol),
+            _ => Err(format!(
+                "unknown character class {s:?}, expected one of upper, lower, digit, symbol"
+            )),
+        }
+    }
+}
+
+impl Display for CharClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Upper => "upper",
+            Self::Lower => "lower",
+            Self::Digit => "digit",
+            Self::Symbol => "symbol",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Rules for generated passwords, like `work/.spass-policy`:
+///
+/// ```toml
+/// name = "work"
+/// max_length = 16
+/// require = ["upper", "digit", "symbol"]
+/// exclude = "\"\\"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// For messages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    /// In `tr` syntax, see [`expand_charset`].
+    /// Character set from config is used, if it's not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub charset: Option<String>,
+    /// At least one character of every class.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub require: Vec<CharClass>,
+    /// Characters removed from the character set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<String>,
+}
+
+impl Policy {
+    /// `other` overrides values of `self`,
+    /// required classes and excluded characters are added up.
+    #[must_use]
+    pub fn merge(mut self, other: Policy) -> Policy {
+        self.name = other.name.or(self.name);
+        self.length = other.length.or(self.length);
+        self.max_length = other.max_length.or(self.max_length);
+        self.charset = other.charset.or(self.charset);
+        for class in other.require {
+            if !self.require.contains(&class) {
+                self.require.push(class);
+            }
+        }
+        self.exclude = match (self.exclude, other.exclude) {
+            (Some(a), Some(b)) => Some(a + &b),
+            (a, b) => a.or(b),
+        };
+        self
+    }
+
+    /// Length of generated password: explicit one, checked against `max_length`,
+    /// or default length from config, capped by `max_length`.
+    pub fn length(&self, config: &Config) -> Result<usize> {
+        match (self.length, self.max_length) {
+            (Some(length), Some(max_length)) if length > max_length => {
+                Err(Error::UnsatisfiablePolicy {
+                    reason: format!("length {length} is more than max_length {max_length}"),
+                })
+            }
+            (Some(length), _) => Ok(length),
+            (None, max_length) => {
+                Ok(max_length.map_or(config.generated_length, |x| x.min(config.generated_length)))
+            }
+        }
+    }
+
+    /// Characters to generate from: [`Self::charset`] or character set from config,
+    /// without excluded characters.
+    /// Symbols are also removed with `no_symbols`.
+    #[allow(clippy::missing_panics_doc/* Reason: character sets are validated on config load */)]
+    pub fn chars(&self, config: &Config, no_symbols: bool) -> Result<Vec<char>> {
+        let mut chars = match &self.charset {
+            Some(charset) => {
+                expand_charset(charset).map_err(|reason| Error::UnsatisfiablePolicy {
+                    reason: format!("charset {reason}"),
+                })?
+            }
+            None if no_symbols => expand_charset(&config.character_set_no_symbols).unwrap(),
+            None => expand_charset(&config.character_set).unwrap(),
+        };
+        if no_symbols {
+            chars.retain(|x| CharClass::of(*x) != CharClass::Symbol);
+        }
+        if let Some(exclude) = &self.exclude {
+            chars.retain(|x| !exclude.contains(*x));
+        }
+        if chars.is_empty() {
+            return Err(Error::UnsatisfiablePolicy {
+                reason: "no characters left to generate from".to_owned(),
+            });
+        }
+        Ok(chars)
+    }
+}
+
+/// Policy from the nearest [`POLICY_FILE_NAME`] in directories of `pass_name`,
+/// with path of the file.
+pub fn find(store: &Store, pass_name: &str) -> Result<Option<(PathBuf, Policy)>> {
+    let mut path: &Path = &api::store_path(store, pass_name);
+    let root = api::store_root(store, path);
+    while let Some(parent) = path.parent() {
+        if !parent.starts_with(root) {
+            break;
+        }
+        path = parent;
+        let file = path.join(POLICY_FILE_NAME);
+        let content = match std::fs::read_to_string(&file) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let policy = toml::from_str(&content).map_err(|err| Error::InvalidConfigFile {
+            path: file.clone(),
+            reason: err.to_string(),
+        })?;
+        return Ok(Some((file, policy)));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn length_respects_max_length() {
+        let config = Config::default();
+        let policy = |length, max_length| Policy {
+            length,
+            max_length,
+            ..Policy::default()
+        };
+        assert_eq!(policy(Some(30), None).length(&config).unwrap(), 30);
+        assert_eq!(policy(None, Some(12)).length(&config).unwrap(), 12);
+        assert_eq!(
+            policy(None, Some(100)).length(&config).unwrap(),
+            config.generated_length
+        );
+        assert_eq!(policy(Some(12), Some(12)).length(&config).unwrap(), 12);
+        assert!(matches!(
+            policy(Some(13), Some(12)).length(&config),
+            Err(Error::UnsatisfiablePolicy { .. })
+        ));
+    }
+
+    #[test]
+    fn chars_without_excluded() {
+        let config = Config::default();
+        let policy = Policy {
+            charset: Some("a-d0-2".to_owned()),
+            exclude: Some("b1".to_owned()),
+            ..Policy::default()
+        };
+        assert_eq!(
+            policy.chars(&config, false).unwrap(),
+            ['a', 'c', 'd', '0', '2']
+        );
+        let policy = Policy {
+            exclude: Some("a-d0-2".to_owned()),
+            ..policy
+        };
+        // exclude is a plain list of characters
+        assert_eq!(policy.chars(&config, false).unwrap(), ['b', 'c', '1']);
+        let policy = Policy {
+            exclude: Some("abcd-012".to_owned()),
+            ..policy
+        };
+        assert!(matches!(
+            policy.chars(&config, false),
+            Err(Error::UnsatisfiablePolicy { .. })
+        ));
+    }
+}
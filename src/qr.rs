@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use qrcode::{
+    render::{svg, unicode::Dense1x2},
+    QrCode,
+};
+
+use crate::{args::QrCodeFormat, Result};
+
+/// Render `content` as QR code using unicode half blocks.
+/// Colors are inverted, so code is readable on dark terminals.
+pub fn render_unicode(content: &str) -> Result<String> {
+    let code = QrCode::new(content)?;
+    let mut rendered = code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .build();
+    rendered.push('\n');
+    Ok(rendered)
+}
+
+pub fn save(content: &str, format: QrCodeFormat, path: &Path) -> Result<()> {
+    let code = QrCode::new(content)?;
+    match format {
+        QrCodeFormat::Png => {
+            code.render::<image::Luma<u8>>()
+                .min_dimensions(256, 256)
+                .build()
+                .save_with_format(path, image::ImageFormat::Png)?;
+        }
+        QrCodeFormat::Svg => {
+            let rendered = code.render::<svg::Color>().min_dimensions(256, 256).build();
+            std::fs::write(path, rendered)?;
+        }
+    }
+    println!("Saved QR code to {path:?}.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    /// Modules of version 1 code and quiet zone on both sides.
+    const WIDTH: usize = 21 + 2 * 4;
+
+    #[test]
+    fn unicode_has_quiet_zone() {
+        let rendered = render_unicode("hello").unwrap();
+        let lines = rendered.lines().collect::<Vec<_>>();
+        // two rows of modules per line
+        assert_eq!(lines.len(), WIDTH.div_ceil(2));
+        let (last, lines) = lines.split_last().unwrap();
+        for line in lines {
+            assert_eq!(line.chars().count(), WIDTH);
+            assert!(line.starts_with("████") && line.ends_with("████"), "{line}");
+        }
+        assert_eq!(lines[0], "█".repeat(WIDTH));
+        assert_eq!(lines[1], "█".repeat(WIDTH));
+        assert_ne!(lines[2], "█".repeat(WIDTH));
+        // the last row of quiet zone is the upper half
+        assert_eq!(*last, "▀".repeat(WIDTH));
+    }
+
+    #[test]
+    fn png_is_at_least_256_pixels() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("code.png");
+        save("secret", QrCodeFormat::Png, &path).unwrap();
+        let image = image::open(&path).unwrap();
+        assert_eq!(image.width(), image.height());
+        assert!(image.width() >= 256);
+    }
+
+    #[test]
+    fn too_long_content_is_error() {
+        let content = "x".repeat(8000);
+        assert!(matches!(
+            render_unicode(&content),
+            Err(Error::QrCode(qrcode::types::QrError::DataTooLong))
+        ));
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use spass::{api, crypto::Recipient, Error, Result, Store};
+
+use crate::{args::OutputFormat, output};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(Serialize)]
+struct RecipientsJson<'a> {
+    file: &'a Path,
+    backend: &'static str,
+    /// Recipients without found key have no `key_ids`.
+    recipients: &'a [Recipient],
+}
+
+/// Write recipients used for `name`, a folder or a pass file, with their keys to `out`.
+/// Recipients without keys are written too, so they can be fixed.
+pub fn list(out: &mut impl Write, store: &Store, name: &str, format: OutputFormat) -> Result<()> {
+    let is_pass_file =
+        !api::store_path(store, name).is_dir() && api::get_pass_path(store, &name).is_file();
+    let subfolder = if is_pass_file {
+        name.rsplit_once('/').map_or("", |(dir, _)| dir)
+    } else {
+        name
+    };
+    let (path, backend, ids) = api::get_recipients_file(store, subfolder)?;
+    let mut recipients = Vec::new();
+    for id in ids {
+        match backend.resolve_recipients(std::slice::from_ref(&id)) {
+            Ok(resolved) => recipients.extend(resolved),
+            Err(Error::RecipientKeyNotFound { .. }) => recipients.push(Recipient {
+                id,
+                key_ids: Vec::new(),
+                name: None,
+                fingerprint: None,
+                expires: None,
+            }),
+            Err(err) => return Err(err),
+        }
+    }
+    if format == OutputFormat::Json {
+        return output::write_json(
+            out,
+            &RecipientsJson {
+                file: &path,
+                backend: backend.name(),
+                recipients: &recipients,
+            },
+        );
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| i64::try_from(x.as_secs()).unwrap_or(i64::MAX));
+    writeln!(out, "{} from {}:", backend.name(), path.display())?;
+    for recipient in &recipients {
+        writeln!(out, "{}", recipient.id)?;
+        if recipient.key_ids.is_empty() {
+            writeln!(out, "  key not found")?;
+            continue;
+        }
+        if let Some(name) = &recipient.name {
+            writeln!(out, "  uid:         {name}")?;
+        }
+        if let Some(fingerprint) = &recipient.fingerprint {
+            writeln!(out, "  fingerprint: {fingerprint}")?;
+        }
+        writeln!(out, "  keys:        {}", recipient.key_ids.join(", "))?;
+        let expires = match recipient.expires {
+            None => "never".to_owned(),
+            Some(x) if x <= now => format!("expired {} days ago", (now - x) / SECONDS_PER_DAY),
+            Some(x) => format!("in {} days", (x - now) / SECONDS_PER_DAY),
+        };
+        writeln!(out, "  expires:     {expires}")?;
+    }
+    Ok(())
+}
+
+/// Add `ids` to recipients of `subfolder` and reencrypt pass files under it, with one commit.
+/// Folder without own recipients file gets one with inherited recipients.
+pub fn add(store: &Store, subfolder: &str, ids: &[String]) -> Result<()> {
+    let (_, backend, mut recipients) = api::get_recipients_file(store, subfolder)?;
+    let mut added = Vec::new();
+    for id in ids {
+        if recipients.contains(id) {
+            println!("{id} is already a recipient.");
+        } else {
+            recipients.push(id.clone());
+            added.push(id.as_str());
+        }
+    }
+    if added.is_empty() {
+        return Ok(());
+    }
+    let message = format!(
+        "Add {} to {}{}.",
+        added.join(", "),
+        backend.name(),
+        path_note(subfolder)
+    );
+    api::init_with_message(store, subfolder, &recipients, backend, Some(&message))
+}
+
+/// Remove `ids` from recipients of `subfolder` and reencrypt pass files under it, with one commit.
+/// Folder without own recipients file gets one with the rest of inherited recipients.
+pub fn remove(store: &Store, subfolder: &str, ids: &[String]) -> Result<()> {
+    let (path, backend, mut recipients) = api::get_recipients_file(store, subfolder)?;
+    for id in ids {
+        let Some(index) = recipients.iter().position(|x| x == id) else {
+            return Err(Error::RecipientNotListed {
+                recipient: id.clone(),
+                path,
+            });
+        };
+        recipients.remove(index);
+    }
+    if recipients.is_empty() {
+        return Err(Error::NoRecipientsLeft { path });
+    }
+    let message = format!(
+        "Remove {} from {}{}.",
+        ids.join(", "),
+        backend.name(),
+        path_note(subfolder)
+    );
+    api::init_with_message(store, subfolder, &recipients, backend, Some(&message))
+}
+
+fn path_note(subfolder: &str) -> String {
+    if subfolder.is_empty() {
+        String::new()
+    } else {
+        format!(" ({subfolder})")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use spass::git;
+
+    use super::*;
+    use crate::fixtures::{age_store, git_init};
+
+    fn new_recipient() -> (age::x25519::Identity, String) {
+        let identity = age::x25519::Identity::generate();
+        let recipient = identity.to_public().to_string();
+        (identity, recipient)
+    }
+
+    fn read_recipients(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// Number of commits in the repository at `root`.
+    fn commits(root: &Path) -> usize {
+        let repo = git2::Repository::open(root).unwrap();
+        let mut revwalk = repo.revwalk().unwrap();
+        revwalk.push_head().unwrap();
+        revwalk.count()
+    }
+
+    #[test]
+    fn add_copies_inherited_recipients() {
+        let (_dir, mut store) = age_store();
+        git_init(store.root());
+        store.put("team/server", b"secret\n").unwrap();
+        store.put("email", b"other\n").unwrap();
+        store.commit("Add passwords.").unwrap();
+        let email = std::fs::read(store.root().join("email.age")).unwrap();
+        let inherited = read_recipients(&store.root().join(".age-recipients"));
+        let (colleague, id) = new_recipient();
+        let head = git::head(store.root().as_os_str()).unwrap();
+        let before = commits(store.root());
+
+        // already listed one is skipped
+        add(&store, "team", &[id.clone(), inherited[0].clone()]).unwrap();
+        let mut expected = inherited.clone();
+        expected.push(id.clone());
+        assert_eq!(
+            read_recipients(&store.root().join("team/.age-recipients")),
+            expected
+        );
+        assert_eq!(
+            read_recipients(&store.root().join(".age-recipients")),
+            inherited
+        );
+        let server = std::fs::read(store.root().join("team/server.age")).unwrap();
+        assert_eq!(age::decrypt(&colleague, &server).unwrap(), b"secret\n");
+        assert_eq!(
+            std::fs::read(store.root().join("email.age")).unwrap(),
+            email
+        );
+        // recipients file and reencrypted pass file in one commit
+        assert_ne!(git::head(store.root().as_os_str()).unwrap(), head);
+        assert_eq!(commits(store.root()), before + 1);
+
+        // nothing to add, nothing to commit
+        add(&store, "team", &[id]).unwrap();
+        assert_eq!(commits(store.root()), before + 1);
+    }
+
+    #[test]
+    fn remove_recipients() {
+        let (_dir, mut store) = age_store();
+        let (colleague, id) = new_recipient();
+        add(&store, "team", std::slice::from_ref(&id)).unwrap();
+        store.put("team/server", b"secret\n").unwrap();
+        store.commit("Add server.").unwrap();
+        let path = store.root().join("team/.age-recipients");
+        let owner = read_recipients(&path)[0].clone();
+
+        let (_, stranger) = new_recipient();
+        assert!(matches!(
+            remove(&store, "team", &[stranger]),
+            Err(Error::RecipientNotListed { path: x, .. }) if x == path
+        ));
+        assert!(matches!(
+            remove(&store, "team", &[id.clone(), owner.clone()]),
+            Err(Error::NoRecipientsLeft { path: x }) if x == path
+        ));
+        assert_eq!(read_recipients(&path), [owner.clone(), id.clone()]);
+
+        remove(&store, "team", &[id]).unwrap();
+        assert_eq!(read_recipients(&path), [owner]);
+        let server = std::fs::read(store.root().join("team/server.age")).unwrap();
+        assert!(age::decrypt(&colleague, &server).is_err());
+        assert_eq!(store.get("team/server").unwrap(), b"secret\n");
+    }
+
+    /// Recipients file from `recipients list --json name`.
+    fn listed_file(store: &Store, name: &str) -> (PathBuf, Vec<String>) {
+        let mut out = Vec::new();
+        list(&mut out, store, name, OutputFormat::Json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["backend"], "age recipients");
+        let ids = json["recipients"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| x["id"].as_str().unwrap().to_owned())
+            .collect();
+        (PathBuf::from(json["file"].as_str().unwrap()), ids)
+    }
+
+    #[test]
+    fn list_pass_file_and_folder() {
+        let (_dir, mut store) = age_store();
+        let (_, id) = new_recipient();
+        add(&store, "team", std::slice::from_ref(&id)).unwrap();
+        store.put("team", b"file next to folder\n").unwrap();
+        store.put("team/server", b"secret\n").unwrap();
+        store.commit("Add passwords.").unwrap();
+        let root_file = store.root().join(".age-recipients");
+        let team_file = store.root().join("team/.age-recipients");
+
+        // folder goes before pass file with the same name
+        assert_eq!(listed_file(&store, "team").0, team_file);
+        assert_eq!(listed_file(&store, "team/server").0, team_file);
+        assert_eq!(listed_file(&store, "").0, root_file);
+        let (_, ids) = listed_file(&store, "team");
+        assert_eq!(ids.last(), Some(&id));
+
+        std::fs::remove_dir_all(store.root().join("team")).unwrap();
+        assert_eq!(listed_file(&store, "team").0, root_file);
+    }
+}
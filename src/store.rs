@@ -0,0 +1,412 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    api,
+    config::Config,
+    crypto::Backends,
+    transaction::{Interrupt, Transaction},
+    Entry, Error, Result,
+};
+
+/// Handle to the password store.
+///
+/// Reads go straight to the store. Changes are staged and applied all at once
+/// by [`Store::commit`], or discarded by [`Store::abort`] (or by drop).
+///
+/// # Example
+/// ```no_run
+/// let mut store = spass::Store::open(None)?;
+/// let password = store.get("email/work")?;
+/// store.put("email/home", &password)?;
+/// store.commit("Copy email password.")?;
+/// # Ok::<(), spass::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct Store {
+    root: PathBuf,
+    config: Config,
+    backends: Backends,
+    /// Created on first change, so reading doesn't touch the store.
+    transaction: Option<Transaction>,
+    interrupt: Arc<Interrupt>,
+}
+
+impl Store {
+    /// Open store at `store_dir` or at `$HOME/.password-store`, and load its config.
+    pub fn open(store_dir: Option<PathBuf>) -> Result<Self> {
+        let root = match store_dir {
+            Some(root) => root,
+            None => PathBuf::from(std::env::var_os("HOME").ok_or(Error::StoreDirUnknown)?)
+                .join(".password-store"),
+        };
+        let root = std::path::absolute(&root).unwrap_or(root);
+        let config = Config::load(&root)?;
+        Self::with_config(root, config)
+    }
+    /// Open store at `root` with `config`, without reading config files and environment.
+    pub fn with_config(root: PathBuf, config: Config) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            root,
+            backends: Backends::new(&config),
+            config,
+            transaction: None,
+            interrupt: Arc::default(),
+        })
+    }
+    /// Root directory of the store, mounted stores are in [`Config::mounts`].
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    #[must_use]
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+    #[must_use]
+    pub fn backends(&self) -> &Backends {
+        &self.backends
+    }
+    /// Decrypted content of `pass_name`.
+    /// Staged changes are not visible until [`Store::commit`].
+    pub fn get(&self, pass_name: &str) -> Result<Vec<u8>> {
+        api::decrypt(self, pass_name)
+    }
+    /// Content of `pass_name` parsed as [`Entry`].
+    pub fn get_entry(&self, pass_name: &str) -> Result<Entry> {
+        Ok(Entry::parse(&String::from_utf8(self.get(pass_name)?)?))
+    }
+    /// All pass names in sorted order.
+    pub fn list(&self) -> Result<Vec<String>> {
+        api::check_uninitialized_store(self)?;
+        api::get_pass_names(self, &self.root)
+    }
+    /// Stage `pass_name` with `content`, existing one is overwritten.
+    pub fn put(&mut self, pass_name: &str, content: &[u8]) -> Result<()> {
+        api::check_pass_name(pass_name)?;
+        api::check_uninitialized_store(self)?;
+        self.stage(|transaction, store| transaction.write(store, pass_name, content))
+    }
+    /// Stage removal of `pass_name`.
+    pub fn remove(&mut self, pass_name: &str) -> Result<()> {
+        api::check_pass_name(pass_name)?;
+        let path = existing_pass_path(self, pass_name)?;
+        self.stage(|transaction, store| transaction.remove(store, &path))
+    }
+    /// Stage removal of `subfolder` with everything in it.
+    pub fn remove_dir(&mut self, subfolder: &str) -> Result<()> {
+        api::check_pass_name(subfolder)?;
+        let path = api::store_path(self, subfolder);
+        // root of mounted store is unmounted, not removed
+        if subfolder.is_empty() || path == api::store_root(self, &path) || !path.is_dir() {
+            return Err(Error::PassDoesNotExist {
+                pass_name: subfolder.to_owned(),
+                path,
+            });
+        }
+        self.stage(|transaction, store| transaction.remove(store, &path))
+    }
+    /// Stage copy of `old_name` to `new_name`, reencrypted for its recipients.
+    pub fn copy(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        let content = self.get(old_name)?;
+        self.put(new_name, &content)
+    }
+    /// Stage move of `old_name` to `new_name`, reencrypted for its recipients.
+    pub fn rename(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        if old_name == new_name {
+            return Ok(());
+        }
+        self.copy(old_name, new_name)?;
+        self.remove(old_name)
+    }
+    /// Stage copy of `old_subfolder` to `new_subfolder`, see [`Store::rename_dir`].
+    pub fn copy_dir(
+        &mut self,
+        old_subfolder: &str,
+        new_subfolder: &str,
+        confirm: impl FnMut(&Path) -> Result<bool>,
+    ) -> Result<()> {
+        self.copy_dir_files(old_subfolder, new_subfolder, confirm)
+            .map(|_| ())
+    }
+    /// Stage move of `old_subfolder` with everything in it to `new_subfolder`.
+    ///
+    /// Recipients and policy files are moved too. Pass files keep their content,
+    /// if their recipients file is moved with them, others are reencrypted
+    /// for recipients of the new location.
+    /// Existing files are overwritten only if `confirm` with their path returns `true`,
+    /// skipped files stay in `old_subfolder`.
+    pub fn rename_dir(
+        &mut self,
+        old_subfolder: &str,
+        new_subfolder: &str,
+        confirm: impl FnMut(&Path) -> Result<bool>,
+    ) -> Result<()> {
+        let copied = self.copy_dir_files(old_subfolder, new_subfolder, confirm)?;
+        match copied {
+            None => {
+                let path = api::store_path(self, old_subfolder);
+                self.stage(|transaction, store| transaction.remove(store, &path))
+            }
+            // skipped pass files still need recipients file
+            Some(pass_names) => pass_names
+                .iter()
+                .try_for_each(|pass_name| self.remove(pass_name)),
+        }
+    }
+    /// There are staged changes.
+    #[must_use]
+    pub fn is_modified(&self) -> bool {
+        self.transaction.as_ref().is_some_and(|x| !x.is_empty())
+    }
+    /// Apply all staged changes and commit them to git with `message`.
+    /// On error nothing is changed and staged changes are discarded.
+    pub fn commit(&mut self, message: &str) -> Result<()> {
+        match self.transaction.take() {
+            Some(transaction) if !transaction.is_empty() => transaction.commit(self, message),
+            _ => Ok(()),
+        }
+    }
+    /// Ctrl-C state of this store, for signal handler.
+    /// Without it, Ctrl-C during commit can leave the store half changed.
+    #[must_use]
+    pub fn interrupt(&self) -> Arc<Interrupt> {
+        self.interrupt.clone()
+    }
+    /// Discard all staged changes.
+    pub fn abort(&mut self) {
+        self.transaction = None;
+    }
+
+    /// Stage changes with `change`, transaction is created on first change.
+    fn stage(&mut self, change: impl FnOnce(&mut Transaction, &Self) -> Result<()>) -> Result<()> {
+        let mut transaction = match self.transaction.take() {
+            Some(transaction) => transaction,
+            None => Transaction::new(&self.root, self.interrupt.clone())?,
+        };
+        let result = change(&mut transaction, self);
+        self.transaction = Some(transaction);
+        result
+    }
+    /// Stage copies for [`Store::rename_dir`].
+    ///
+    /// # Returns
+    /// `None` if everything is copied, otherwise copied pass names.
+    fn copy_dir_files(
+        &mut self,
+        old_subfolder: &str,
+        new_subfolder: &str,
+        mut confirm: impl FnMut(&Path) -> Result<bool>,
+    ) -> Result<Option<Vec<String>>> {
+        api::check_pass_name(old_subfolder)?;
+        api::check_pass_name(new_subfolder)?;
+        let old_dir = api::store_path(self, old_subfolder);
+        let new_dir = api::store_path(self, new_subfolder);
+        if old_subfolder.is_empty()
+            || old_dir == api::store_root(self, &old_dir)
+            || !old_dir.is_dir()
+        {
+            return Err(Error::PassDoesNotExist {
+                pass_name: old_subfolder.to_owned(),
+                path: old_dir,
+            });
+        }
+        if new_dir.starts_with(&old_dir) {
+            return Err(Error::MoveIntoItself {
+                old_pass: old_subfolder.to_owned(),
+                new_pass: new_subfolder.to_owned(),
+            });
+        }
+        let (pass_files, other_files) = files(&old_dir)?
+            .into_iter()
+            .partition::<Vec<_>, _>(|x| api::pass_name_of(self, x).is_some());
+        let new_path = |path: &Path| new_dir.join(path.strip_prefix(&old_dir).unwrap_or(path));
+
+        let mut skipped = false;
+        let mut copied_files = Vec::new();
+        for path in other_files {
+            let destination = new_path(&path);
+            if destination.exists() && !confirm(&destination)? {
+                skipped = true;
+                continue;
+            }
+            let content = std::fs::read(&path)?;
+            self.stage(|transaction, store| transaction.write_file(store, destination, &content))?;
+            copied_files.push(path);
+        }
+        let mut copied = Vec::new();
+        for path in pass_files {
+            let old_name = api::pass_name_of(self, &path).unwrap_or_default();
+            let destination = new_path(&path);
+            let new_name =
+                api::pass_name_of(self, &destination).ok_or_else(|| Error::PassDoesNotExist {
+                    pass_name: new_subfolder.to_owned(),
+                    path: destination.clone(),
+                })?;
+            let existing = api::get_pass_path(self, &new_name);
+            if existing.exists() && !confirm(&existing)? {
+                skipped = true;
+                continue;
+            }
+            let dir = old_name.rsplit_once('/').map_or("", |(dir, _)| dir);
+            let (recipients_file, _, _) = api::get_recipients_file(self, dir)?;
+            if copied_files.contains(&recipients_file) {
+                // recipients are the same in new location
+                let content = std::fs::read(&path)?;
+                self.stage(|transaction, store| {
+                    transaction.write_pass_file(store, &new_name, destination, &content)
+                })?;
+            } else {
+                self.copy(&old_name, &new_name)?;
+            }
+            copied.push(old_name);
+        }
+        Ok(skipped.then_some(copied))
+    }
+}
+
+/// Regular files under `dir`, including hidden ones (recipients, policy),
+/// but not in hidden directories (scratch, `.git`).
+fn files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = walkdir::WalkDir::new(dir)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|x| {
+            x.depth() == 0
+                || !x.file_type().is_dir()
+                || !x.file_name().to_string_lossy().starts_with('.')
+        });
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            // symlink to parent directory
+            Err(err) if err.loop_ancestor().is_some() => continue,
+            Err(err) => return Err(std::io::Error::from(err).into()),
+        };
+        if entry.file_type().is_file() {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+fn existing_pass_path(store: &Store, pass_name: &str) -> Result<PathBuf> {
+    let path = api::get_pass_path(store, &pass_name);
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(Error::PassDoesNotExist {
+            pass_name: pass_name.to_owned(),
+            path,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        crypto::CryptoBackend,
+        fixtures::{age_store, git_init},
+        git,
+    };
+
+    use super::*;
+
+    #[test]
+    fn put_is_visible_after_commit() {
+        let (_dir, mut store) = age_store();
+        store.put("email/work", b"secret\n").unwrap();
+        assert!(store.is_modified());
+        assert!(store.get("email/work").is_err());
+        store.commit("Add email.").unwrap();
+        assert!(!store.is_modified());
+        assert_eq!(store.get("email/work").unwrap(), b"secret\n");
+        assert!(store.root().join("email/work.age").is_file());
+        assert_eq!(store.list().unwrap(), ["email/work"]);
+
+        store.put("email/home", b"other\n").unwrap();
+        store.abort();
+        store.commit("Nothing.").unwrap();
+        assert!(store.get("email/home").is_err());
+    }
+
+    #[test]
+    fn names_outside_store_are_rejected() {
+        let (dir, mut store) = age_store();
+        for name in ["../outside", "email/../../outside", "/tmp/outside"] {
+            let invalid = |result: Result<()>| matches!(result, Err(Error::InvalidPassName { .. }));
+            assert!(invalid(store.put(name, b"secret\n")), "{name}");
+            assert!(invalid(store.get(name).map(|_| ())), "{name}");
+            assert!(invalid(store.remove(name)), "{name}");
+            assert!(invalid(store.remove_dir(name)), "{name}");
+        }
+        store.commit("Nothing.").unwrap();
+        assert!(!dir.path().join("outside.age").exists());
+    }
+
+    #[test]
+    fn rename_dir_moves_recipients() {
+        let (_dir, mut store) = age_store();
+        let recipients = std::fs::read_to_string(store.root().join(".age-recipients")).unwrap();
+        let recipients = recipients.lines().map(str::to_owned).collect::<Vec<_>>();
+        api::init(&store, "team", &recipients, store.backends().age()).unwrap();
+        store.put("team/server", b"secret\n").unwrap();
+        store.commit("Add server.").unwrap();
+        let ciphertext = std::fs::read(store.root().join("team/server.age")).unwrap();
+
+        store.rename_dir("team", "moved", |_| Ok(true)).unwrap();
+        store.commit("Move team.").unwrap();
+        assert!(!store.root().join("team").exists());
+        assert!(store.root().join("moved/.age-recipients").is_file());
+        // recipients are the same, so pass file isn't reencrypted
+        let moved = std::fs::read(store.root().join("moved/server.age")).unwrap();
+        assert_eq!(moved, ciphertext);
+        assert_eq!(store.get("moved/server").unwrap(), b"secret\n");
+    }
+
+    #[test]
+    fn stores_are_independent() {
+        let (_first_dir, mut first) = age_store();
+        let (_second_dir, mut second) = age_store();
+        first.put("email", b"first\n").unwrap();
+        second.put("email", b"second\n").unwrap();
+        first.commit("Add email.").unwrap();
+        assert!(second.get("email").is_err());
+        second.commit("Add email.").unwrap();
+        assert_eq!(first.get("email").unwrap(), b"first\n");
+        assert_eq!(second.get("email").unwrap(), b"second\n");
+        // identities of one store can't decrypt the other
+        let ciphertext = std::fs::read(second.root().join("email.age")).unwrap();
+        assert!(first.backends().age().decrypt(&ciphertext).is_err());
+    }
+
+    #[test]
+    fn failed_commit_resets_other_stores() {
+        let (dir, store) = age_store();
+        let team = dir.path().join("team");
+        let mut config = store.config().clone();
+        config.mounts.insert("team".to_owned(), team.clone());
+        let mut store = Store::with_config(store.root().to_owned(), config).unwrap();
+        let recipients = std::fs::read_to_string(store.root().join(".age-recipients")).unwrap();
+        let recipients = [recipients.trim().to_owned()];
+        api::init(&store, "team", &recipients, store.backends().age()).unwrap();
+        git_init(store.root());
+        git_init(&team);
+        git::commit_all(store.config(), store.root().as_os_str(), "Init.").unwrap();
+        let head = git::head(store.root().as_os_str()).unwrap();
+        // index of mounted store can't be read, so its commit fails
+        std::fs::write(team.join(".git/index"), b"broken").unwrap();
+
+        store.put("email", b"secret\n").unwrap();
+        store.put("team/email", b"shared\n").unwrap();
+        assert!(store.commit("Add emails.").is_err());
+        assert_eq!(git::head(store.root().as_os_str()).unwrap(), head);
+        assert!(!store.root().join("email.age").exists());
+        assert!(!team.join("email.age").exists());
+    }
+}
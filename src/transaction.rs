@@ -0,0 +1,368 @@
+use std::{
+    cell::Cell,
+    collections::BTreeMap,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use crate::{api, git, Error, Result, Store};
+
+/// Scratch directories are created in the store roots with this prefix,
+/// so renames from them are atomic. Git ignores them.
+pub const SCRATCH_PREFIX: &str = ".spass-transaction";
+
+/// Ctrl-C state, shared between [`Store`](crate::Store) and a signal handler.
+///
+/// Staged changes are only in scratch directories, so the process can just exit.
+/// While they are applied, handler should let the transaction roll back instead.
+#[derive(Debug, Default)]
+pub struct Interrupt {
+    requested: AtomicBool,
+    applying: AtomicBool,
+}
+
+impl Interrupt {
+    /// Ask transaction that is being applied to roll back.
+    ///
+    /// # Returns
+    /// `false` if no transaction is being applied, so process can exit right away.
+    pub fn request(&self) -> bool {
+        self.requested.store(true, Ordering::SeqCst);
+        self.applying.load(Ordering::SeqCst)
+    }
+    fn check(&self) -> Result<()> {
+        if self.requested.load(Ordering::SeqCst) {
+            Err(Error::Interrupted)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Operation {
+    /// Move staged file from scratch to `destination`.
+    Write {
+        staged: PathBuf,
+        destination: PathBuf,
+    },
+    /// Remove file or directory.
+    Remove { path: PathBuf },
+}
+
+/// What was done to the store, to undo it.
+#[derive(Debug)]
+enum Applied {
+    /// `path` didn't exist before.
+    Created { path: PathBuf },
+    /// `path` was moved to `backup`.
+    Replaced { path: PathBuf, backup: PathBuf },
+    /// Directory `path` was created for new files.
+    CreatedDir { path: PathBuf },
+}
+
+/// Set of changes to the store, that are applied all at once with one commit.
+///
+/// All files are encrypted into scratch directory first, and then renamed into
+/// the store. If something fails, or user press Ctrl-C (see [`Interrupt`]),
+/// all changes are rolled back.
+/// Dropping transaction without [`Transaction::commit`] discards staged changes.
+/// Every touched store (main one or mounted) gets its own scratch directory and commit,
+/// either all stores are committed or none.
+#[derive(Debug)]
+#[must_use]
+pub struct Transaction {
+    /// Scratch directory of every touched store, by store root.
+    scratch: BTreeMap<PathBuf, tempfile::TempDir>,
+    /// For unique file names in `scratch`.
+    scratch_files: Cell<usize>,
+    operations: Vec<Operation>,
+    interrupt: Arc<Interrupt>,
+}
+
+impl Transaction {
+    /// Transaction for store at `root`, it's created, if it doesn't exist.
+    pub fn new(root: &Path, interrupt: Arc<Interrupt>) -> Result<Self> {
+        std::fs::create_dir_all(root)?;
+        Ok(Self {
+            scratch: BTreeMap::new(),
+            scratch_files: Cell::new(0),
+            operations: Vec::new(),
+            interrupt,
+        })
+    }
+    /// Encrypt `content` for recipients of `pass_name` and stage it.
+    pub fn write(&mut self, store: &Store, pass_name: &str, content: &[u8]) -> Result<()> {
+        let encrypted = api::encrypt(store, pass_name, content)?;
+        let destination = api::get_new_pass_path(store, pass_name)?;
+        self.write_pass_file(store, pass_name, destination, &encrypted)
+    }
+    /// Stage already encrypted `content` of `pass_name` at `destination`.
+    pub fn write_pass_file(
+        &mut self,
+        store: &Store,
+        pass_name: &str,
+        destination: PathBuf,
+        content: &[u8],
+    ) -> Result<()> {
+        let old_path = api::get_pass_path(store, &pass_name);
+        // pass file is moved to another backend
+        if old_path != destination && old_path.exists() {
+            self.operations.push(Operation::Remove { path: old_path });
+        }
+        self.write_file(store, destination, content)
+    }
+    /// Stage file with `content` at `destination`, e.g. recipients file.
+    pub fn write_file(
+        &mut self,
+        store: &Store,
+        destination: PathBuf,
+        content: &[u8],
+    ) -> Result<()> {
+        self.touch(store, &destination)?;
+        let staged = self.scratch_path(&destination);
+        File::create_new(&staged)?.write_all(content)?;
+        self.operations.push(Operation::Write {
+            staged,
+            destination,
+        });
+        Ok(())
+    }
+    /// Stage removal of pass file or directory at `path`.
+    pub fn remove(&mut self, store: &Store, path: &Path) -> Result<()> {
+        self.touch(store, path)?;
+        self.operations.push(Operation::Remove {
+            path: path.to_owned(),
+        });
+        Ok(())
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+    /// Apply all staged changes and commit them with `message`.
+    /// On error nothing is changed.
+    pub fn commit(self, store: &Store, message: &str) -> Result<()> {
+        // handler sees it before we check for requests, so Ctrl-C either
+        // exits before anything is applied, or is handled here
+        self.interrupt.applying.store(true, Ordering::SeqCst);
+        let mut applied = Vec::new();
+        let result = self
+            .apply(&mut applied)
+            .and_then(|()| self.interrupt.check())
+            .and_then(|()| self.commit_stores(store, message));
+        if result.is_err() {
+            rollback(applied);
+        }
+        self.interrupt.applying.store(false, Ordering::SeqCst);
+        // request is handled, or it's too late to roll back committed changes
+        self.interrupt.requested.store(false, Ordering::SeqCst);
+        result?;
+        for operation in &self.operations {
+            if let Operation::Remove { path } = operation {
+                remove_empty_parents(self.root_of(path), path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Commit every touched store that is git repository, or none of them:
+    /// if one commit fails, already made ones are reset.
+    fn commit_stores(&self, store: &Store, message: &str) -> Result<()> {
+        let mut heads = Vec::new();
+        for root in self.scratch.keys() {
+            match git::head(root.as_os_str()) {
+                Ok(head) => heads.push((root, head)),
+                Err(Error::PassStoreShouldBeGitRepo) => (),
+                Err(err) => return Err(err),
+            }
+        }
+        for (i, (root, _)) in heads.iter().enumerate() {
+            if let Err(err) = git::commit_all(store.config(), root.as_os_str(), message) {
+                for (root, head) in heads[..i].iter().rev() {
+                    if let Err(err) = git::reset(root.as_os_str(), *head) {
+                        eprintln!("Error: can't reset commit of {root:?}: {err}");
+                    }
+                }
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply(&self, applied: &mut Vec<Applied>) -> Result<()> {
+        for operation in &self.operations {
+            self.interrupt.check()?;
+            match operation {
+                Operation::Write {
+                    staged,
+                    destination,
+                } => {
+                    let parent = destination.parent().unwrap_or(self.root_of(destination));
+                    let missing = parent
+                        .ancestors()
+                        .take_while(|x| !x.exists())
+                        .map(Path::to_owned)
+                        .collect::<Vec<_>>();
+                    std::fs::create_dir_all(parent)?;
+                    // outer first, so rollback removes inner ones before them
+                    applied.extend(
+                        missing
+                            .into_iter()
+                            .rev()
+                            .map(|path| Applied::CreatedDir { path }),
+                    );
+                    let backup = self.backup(destination)?;
+                    if let Err(err) = std::fs::rename(staged, destination) {
+                        if let Applied::Replaced { .. } = backup {
+                            applied.push(backup);
+                        }
+                        return Err(err.into());
+                    }
+                    applied.push(backup);
+                }
+                Operation::Remove { path } => {
+                    if path.exists() {
+                        applied.push(self.backup(path)?);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Move existing `path` to scratch directory of its store.
+    fn backup(&self, path: &Path) -> Result<Applied> {
+        if !path.exists() {
+            return Ok(Applied::Created {
+                path: path.to_owned(),
+            });
+        }
+        let backup = self.scratch_path(path);
+        std::fs::rename(path, &backup)?;
+        Ok(Applied::Replaced {
+            path: path.to_owned(),
+            backup,
+        })
+    }
+    /// Create scratch directory in the store of `path`, if there is none yet.
+    fn touch(&mut self, store: &Store, path: &Path) -> Result<()> {
+        let root = api::store_root(store, path);
+        if !self.scratch.contains_key(root) {
+            let scratch = tempfile::Builder::new()
+                .prefix(SCRATCH_PREFIX)
+                .tempdir_in(root)?;
+            self.scratch.insert(root.to_owned(), scratch);
+        }
+        Ok(())
+    }
+    /// Root of the touched store of `path`.
+    /// The longest one, because mounted store can be inside another one.
+    fn root_of(&self, path: &Path) -> &Path {
+        self.scratch
+            .keys()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .expect("store of staged path should be touched")
+    }
+    /// Unique path in scratch directory of the store of `path`.
+    fn scratch_path(&self, path: &Path) -> PathBuf {
+        let id = self.scratch_files.get();
+        self.scratch_files.set(id + 1);
+        self.scratch[self.root_of(path)].path().join(id.to_string())
+    }
+}
+
+/// Undo `applied` in reverse order.
+/// Errors are only printed, because there is no way to handle them.
+fn rollback(applied: Vec<Applied>) {
+    for applied in applied.into_iter().rev() {
+        let result = match &applied {
+            Applied::Created { path } => std::fs::remove_file(path),
+            Applied::Replaced { path, backup } => std::fs::rename(backup, path),
+            Applied::CreatedDir { path } => std::fs::remove_dir(path),
+        };
+        if let Err(err) = result {
+            eprintln!("Error: can't roll back {applied:?}: {err}");
+        }
+    }
+}
+
+/// Like `rmdir -p`, but stops at store `root`.
+fn remove_empty_parents(root: &Path, path: &Path) {
+    let mut dir = path.parent();
+    while let Some(parent) = dir {
+        if parent == root || std::fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::age_store;
+
+    /// Files under `root` with their content, except scratch directories.
+    fn snapshot(root: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        walkdir::WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|x| !x.file_name().to_string_lossy().starts_with(SCRATCH_PREFIX))
+            .map(|x| x.unwrap())
+            .map(|x| {
+                let content = if x.file_type().is_file() {
+                    std::fs::read(x.path()).unwrap()
+                } else {
+                    Vec::new()
+                };
+                (x.into_path(), content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn failed_apply_is_rolled_back() {
+        let (_dir, mut store) = age_store();
+        store.put("email", b"old\n").unwrap();
+        store.commit("Add email.").unwrap();
+        let before = snapshot(store.root());
+
+        let mut transaction = Transaction::new(store.root(), store.interrupt()).unwrap();
+        // replaced and created files, and created directories
+        transaction.write(&store, "email", b"new\n").unwrap();
+        transaction.write(&store, "team/db/main", b"new\n").unwrap();
+        // parent is a file, so it can't be written
+        let blocked = store.root().join("email.age").join("x");
+        transaction.write_file(&store, blocked, b"").unwrap();
+        assert!(transaction.commit(&store, "Change.").is_err());
+
+        assert_eq!(snapshot(store.root()), before);
+        assert_eq!(store.get("email").unwrap(), b"old\n");
+        assert!(!store.root().join("team").exists());
+    }
+
+    #[test]
+    fn interrupt_rolls_back() {
+        let (_dir, mut store) = age_store();
+        store.put("email", b"old\n").unwrap();
+        store.commit("Add email.").unwrap();
+        let before = snapshot(store.root());
+
+        store.put("email", b"new\n").unwrap();
+        store.put("team/server", b"new\n").unwrap();
+        // nothing is applied, so process can exit
+        assert!(!store.interrupt().request());
+        assert!(matches!(store.commit("Change."), Err(Error::Interrupted)));
+        assert_eq!(snapshot(store.root()), before);
+
+        // request is handled, next commit isn't interrupted
+        store.put("email", b"new\n").unwrap();
+        store.commit("Change.").unwrap();
+        assert_eq!(store.get("email").unwrap(), b"new\n");
+    }
+}
@@ -0,0 +1,479 @@
+use std::{
+    cmp::Ordering,
+    io::{stderr, Write},
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+use spass::{api, config::TreeBackend, Error, Result, Store};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::args::TreeArgs;
+
+const DIR_COLOR: &str = "\x1b[1;34m";
+const SYMLINK_COLOR: &str = "\x1b[1;36m";
+const RESET: &str = "\x1b[0m";
+
+/// Print tree of pass files under `root` with backend from config.
+/// Only pass files with any of `terms` in pass name are shown, if they are given.
+/// `header` replaces the first line with `root`.
+/// Built-in renderer is used for the main store with mounted stores.
+pub fn print(
+    store: &Store,
+    root: &Path,
+    header: &str,
+    args: &TreeArgs,
+    terms: Option<&[String]>,
+) -> Result<()> {
+    let has_mounts = root == store.root() && !store.config().mounts.is_empty();
+    let output = match store.config().tree_backend {
+        // other programs don't know about mounted stores
+        _ if has_mounts => None,
+        TreeBackend::Builtin => None,
+        TreeBackend::Auto => eza(root).or_else(|_| tree(root)).ok(),
+        TreeBackend::Eza => Some(eza(root).map_err(|_| Error::TreeCommandNotFound {
+            supported_commands: vec!["eza".to_owned()],
+        })?),
+        TreeBackend::Tree => Some(tree(root).map_err(|_| Error::TreeCommandNotFound {
+            supported_commands: vec!["tree".to_owned()],
+        })?),
+    };
+    let Some(output) = output else {
+        return print_builtin(
+            &mut std::io::stdout().lock(),
+            store,
+            root,
+            header,
+            args,
+            terms,
+        );
+    };
+    if !output.status.success() {
+        // tree prints errors to stdout
+        stderr().write_all(&output.stdout)?;
+        return Ok(());
+    }
+    let output = String::from_utf8(output.stdout)?;
+    let finder = terms.map(|terms| {
+        aho_corasick::AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build(terms)
+            .unwrap()
+    });
+    let extension = extension_regex(store);
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{header}")?;
+    for line in output.lines().skip(1) {
+        if finder.as_ref().is_none_or(|x| x.find(line).is_some()) {
+            writeln!(stdout, "{}", extension.replace(line, "$1"))?;
+        }
+    }
+    Ok(())
+}
+
+fn eza(root: &Path) -> std::io::Result<std::process::Output> {
+    std::process::Command::new("eza")
+        .arg(root)
+        .args(["--tree", "--color=always", "--dereference"])
+        .stderr(stderr())
+        .output()
+}
+
+fn tree(root: &Path) -> std::io::Result<std::process::Output> {
+    std::process::Command::new("tree")
+        .arg(root)
+        .args(["-C", "--noreport", "--prune"])
+        .stderr(stderr())
+        .output()
+}
+
+/// Extension of pass file at the end of line of external tree,
+/// names with `.gpg` in the middle are kept.
+fn extension_regex(store: &Store) -> Regex {
+    let extensions = store.backends().all().map(|x| regex::escape(x.extension()));
+    Regex::new(&format!(
+        r"\.(?:{})((?:\x1b\[[0-9;]*m)*)$",
+        extensions.join("|")
+    ))
+    .unwrap()
+}
+
+/// Directory or pass file shown in the tree.
+struct Node {
+    depth: usize,
+    name: String,
+    is_dir: bool,
+    link_target: Option<PathBuf>,
+    /// No more nodes in the same directory after this one.
+    is_last: bool,
+}
+
+fn print_builtin(
+    out: &mut impl Write,
+    store: &Store,
+    root: &Path,
+    header: &str,
+    args: &TreeArgs,
+    terms: Option<&[String]>,
+) -> Result<()> {
+    let color = args.color.enabled();
+    let mut nodes = walk(store, root, args, terms)?;
+    if let Some(level) = args.level {
+        nodes.retain(|x| x.depth <= level);
+    }
+    mark_last(&mut nodes);
+
+    writeln!(out, "{header}")?;
+    // is_last of ancestors of current node
+    let mut ancestors = Vec::new();
+    for node in &nodes {
+        ancestors.truncate(node.depth - 1);
+        for &is_last in &ancestors {
+            write!(out, "{}", if is_last { "    " } else { "│   " })?;
+        }
+        write!(
+            out,
+            "{}",
+            if node.is_last {
+                "└── "
+            } else {
+                "├── "
+            }
+        )?;
+        let name_color = match (&node.link_target, node.is_dir) {
+            (Some(_), _) => SYMLINK_COLOR,
+            (None, true) => DIR_COLOR,
+            (None, false) => "",
+        };
+        if color && !name_color.is_empty() {
+            write!(out, "{name_color}{}{RESET}", node.name)?;
+        } else {
+            write!(out, "{}", node.name)?;
+        }
+        if let Some(target) = &node.link_target {
+            write!(out, " -> {}", target.display())?;
+        }
+        writeln!(out)?;
+        ancestors.push(node.is_last);
+    }
+    Ok(())
+}
+
+/// Nodes under `root` in order of output, mounted stores are top-level
+/// directories of the main store and hide directories with the same name.
+fn walk(
+    store: &Store,
+    root: &Path,
+    args: &TreeArgs,
+    terms: Option<&[String]>,
+) -> Result<Vec<Node>> {
+    let mounts = &store.config().mounts;
+    let nodes = walk_dir(store, root, args, terms)?;
+    if root != store.root() || mounts.is_empty() {
+        return Ok(nodes);
+    }
+    // every top-level node with its content
+    let mut chunks: Vec<Vec<Node>> = Vec::new();
+    for node in nodes {
+        if node.depth == 1 {
+            chunks.push(Vec::new());
+        }
+        chunks
+            .last_mut()
+            .expect("the first node is top-level")
+            .push(node);
+    }
+    chunks.retain(|x| !(x[0].is_dir && mounts.contains_key(&x[0].name)));
+    for (name, mount_root) in mounts {
+        let mut nodes = walk_dir(store, mount_root, args, terms)?;
+        // pruned like empty directory
+        if nodes.is_empty() {
+            continue;
+        }
+        for node in &mut nodes {
+            node.depth += 1;
+        }
+        nodes.insert(
+            0,
+            Node {
+                depth: 1,
+                name: name.clone(),
+                is_dir: true,
+                link_target: None,
+                is_last: false,
+            },
+        );
+        chunks.push(nodes);
+    }
+    chunks.sort_by(|a, b| {
+        let by_kind = if args.dirs_first {
+            b[0].is_dir.cmp(&a[0].is_dir)
+        } else {
+            Ordering::Equal
+        };
+        by_kind.then_with(|| a[0].name.cmp(&b[0].name))
+    });
+    Ok(chunks.into_iter().flatten().collect())
+}
+
+/// Nodes under `root` in order of output, directories without
+/// shown pass files are pruned. Symlinks are followed, loops are skipped.
+fn walk_dir(
+    store: &Store,
+    root: &Path,
+    args: &TreeArgs,
+    terms: Option<&[String]>,
+) -> Result<Vec<Node>> {
+    let finder = terms.map(|terms| {
+        aho_corasick::AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build(terms)
+            .unwrap()
+    });
+    let dirs_first = args.dirs_first;
+    let entries = WalkDir::new(root)
+        .follow_links(true)
+        .min_depth(1)
+        .sort_by(move |a, b| {
+            // entries are sorted before symlinks are followed
+            let is_dir = |x: &DirEntry| x.file_type().is_dir() || x.path().is_dir();
+            let by_kind = if dirs_first {
+                is_dir(b).cmp(&is_dir(a))
+            } else {
+                Ordering::Equal
+            };
+            by_kind.then_with(|| a.file_name().cmp(b.file_name()))
+        })
+        .into_iter()
+        .filter_entry(|x| args.all || !x.file_name().to_string_lossy().starts_with('.'));
+
+    let mut nodes = Vec::new();
+    // index in `nodes` of every directory on the path to current entry
+    // and whether it has shown pass files
+    let mut dirs: Vec<(usize, bool)> = Vec::new();
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) if err.loop_ancestor().is_some() => continue,
+            Err(err) => return Err(std::io::Error::from(err).into()),
+        };
+        close_dirs(&mut nodes, &mut dirs, entry.depth());
+        if entry.file_type().is_dir() {
+            dirs.push((nodes.len(), false));
+            nodes.push(node(
+                &entry,
+                entry.file_name().to_string_lossy().into_owned(),
+            ));
+            continue;
+        }
+        let Some(pass_name) = api::pass_name_of(store, entry.path()) else {
+            continue;
+        };
+        if finder
+            .as_ref()
+            .is_some_and(|x| x.find(&pass_name).is_none())
+        {
+            continue;
+        }
+        for dir in &mut dirs {
+            dir.1 = true;
+        }
+        let name = entry.path().with_extension("");
+        let name = name.file_name().unwrap_or_default().to_string_lossy();
+        nodes.push(node(&entry, name.into_owned()));
+    }
+    close_dirs(&mut nodes, &mut dirs, 1);
+    Ok(nodes)
+}
+
+fn node(entry: &DirEntry, name: String) -> Node {
+    Node {
+        depth: entry.depth(),
+        name,
+        is_dir: entry.file_type().is_dir(),
+        link_target: entry
+            .path_is_symlink()
+            .then(|| std::fs::read_link(entry.path()).ok())
+            .flatten(),
+        is_last: false,
+    }
+}
+
+/// Leave directories deeper than `depth - 1`, removing ones without pass files.
+fn close_dirs(nodes: &mut Vec<Node>, dirs: &mut Vec<(usize, bool)>, depth: usize) {
+    while dirs.len() >= depth {
+        let Some((index, has_passes)) = dirs.pop() else {
+            break;
+        };
+        if !has_passes {
+            // it's empty, so it's the last node
+            nodes.truncate(index);
+        }
+    }
+}
+
+fn mark_last(nodes: &mut [Node]) {
+    // there is a later node at depth, until a shallower node is met
+    let mut has_next = Vec::new();
+    for node in nodes.iter_mut().rev() {
+        has_next.resize(node.depth + 1, false);
+        node.is_last = !has_next[node.depth];
+        has_next[node.depth] = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::fixtures::age_store;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        args: TreeArgs,
+    }
+
+    /// Store with pass files, hidden and empty directories and symlink,
+    /// pass files are empty, because tree doesn't read them.
+    fn store() -> (tempfile::TempDir, Store) {
+        let (dir, store) = age_store();
+        let root = store.root();
+        for path in [
+            "bank.age",
+            "email/work.age",
+            "email/my.gpg.notes.age",
+            "empty/readme.txt",
+            ".hidden/secret.age",
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        }
+        std::fs::create_dir_all(root.join("empty/dir")).unwrap();
+        std::os::unix::fs::symlink("email", root.join("link")).unwrap();
+        (dir, store)
+    }
+
+    /// Built-in tree of the whole store with `args`, without colors.
+    fn render(store: &Store, args: &[&str], terms: Option<&[String]>) -> String {
+        let cli = Cli::parse_from(["tree", "--color=never"].iter().chain(args));
+        let mut out = Vec::new();
+        print_builtin(
+            &mut out,
+            store,
+            store.root(),
+            "Password Store",
+            &cli.args,
+            terms,
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn prunes_empty_dirs_and_hides_hidden() {
+        let (_dir, store) = store();
+        assert_eq!(
+            render(&store, &[], None),
+            "\
+Password Store
+├── bank
+├── email
+│   ├── my.gpg.notes
+│   └── work
+└── link -> email
+    ├── my.gpg.notes
+    └── work
+"
+        );
+    }
+
+    #[test]
+    fn all_shows_hidden() {
+        let (_dir, store) = store();
+        let rendered = render(&store, &["--all"], None);
+        assert!(
+            rendered.starts_with("Password Store\n├── .hidden\n│   └── secret\n├── bank\n"),
+            "{rendered}"
+        );
+    }
+
+    #[test]
+    fn level_and_dirs_first() {
+        let (_dir, store) = store();
+        assert_eq!(
+            render(&store, &["-L", "1", "--dirs-first"], None),
+            "\
+Password Store
+├── email
+├── link -> email
+└── bank
+"
+        );
+    }
+
+    #[test]
+    fn terms_filter_pass_names() {
+        let (_dir, store) = store();
+        let terms = ["WORK".to_owned()];
+        assert_eq!(
+            render(&store, &[], Some(&terms)),
+            "\
+Password Store
+├── email
+│   └── work
+└── link -> email
+    └── work
+"
+        );
+    }
+
+    #[test]
+    fn mounts_are_top_level_dirs() {
+        let (dir, store) = store();
+        let team = dir.path().join("team");
+        std::fs::create_dir_all(team.join("db")).unwrap();
+        std::fs::write(team.join("db/main.age"), b"").unwrap();
+        let empty = dir.path().join("empty-mount");
+        std::fs::create_dir_all(&empty).unwrap();
+        // hidden by the mount
+        std::fs::create_dir_all(store.root().join("team")).unwrap();
+        std::fs::write(store.root().join("team/local.age"), b"").unwrap();
+        let mut config = store.config().clone();
+        config.mounts.insert("team".to_owned(), team);
+        config.mounts.insert("zzz".to_owned(), empty);
+        let store = Store::with_config(store.root().to_owned(), config).unwrap();
+        assert_eq!(
+            render(&store, &["-L", "3"], None),
+            "\
+Password Store
+├── bank
+├── email
+│   ├── my.gpg.notes
+│   └── work
+├── link -> email
+│   ├── my.gpg.notes
+│   └── work
+└── team
+    └── db
+        └── main
+"
+        );
+    }
+
+    #[test]
+    fn external_output_keeps_gpg_in_the_middle() {
+        let (_dir, store) = store();
+        let extension = extension_regex(&store);
+        for (line, expected) in [
+            ("├── my.gpg.notes.gpg", "├── my.gpg.notes"),
+            ("└── work.age", "└── work"),
+            ("└── \x1b[0mwork.gpg\x1b[0m", "└── \x1b[0mwork\x1b[0m"),
+            ("└── archive.gpg.d", "└── archive.gpg.d"),
+        ] {
+            assert_eq!(extension.replace(line, "$1"), expected);
+        }
+    }
+}
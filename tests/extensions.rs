@@ -0,0 +1,71 @@
+use std::{os::unix::fs::PermissionsExt, path::Path, process::Output};
+
+use secrecy::ExposeSecret;
+use spass::{api, config::Config, Store};
+
+/// Extension in pass style, it uses only functions of pass.
+const EXTENSION: &str = r#"#!/usr/bin/env bash
+[[ $# -eq 1 ]] || die "Usage: $PROGRAM $COMMAND pass-name"
+check_sneaky_paths "$1"
+cmd_show "$1" | head -n 1 | tr '[:lower:]' '[:upper:]'
+echo "${#GPG_OPTS[@]} gpg options"
+"#;
+
+/// Run `spass` with store at `root` and age identities at `identities`.
+fn spass(root: &Path, identities: &Path, args: &[&str]) -> Output {
+    std::process::Command::new(env!("CARGO_BIN_EXE_spass"))
+        .args(args)
+        .env("PASSWORD_STORE_DIR", root)
+        .env("PASSAGE_IDENTITIES_FILE", identities)
+        .env("XDG_CONFIG_HOME", root.join(".no-config"))
+        .env("PASSWORD_STORE_ENABLE_EXTENSIONS", "true")
+        .env("PASSWORD_STORE_GPG_OPTS", "--armor")
+        .env_remove("GPG_AGENT_INFO")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn bash_extension_uses_pass_functions() {
+    let dir = tempfile::tempdir().unwrap();
+    let identity = age::x25519::Identity::generate();
+    let identities = dir.path().join("identities");
+    std::fs::write(&identities, identity.to_string().expose_secret()).unwrap();
+    let root = dir.path().join("store");
+    let config = Config {
+        age_identities: identities.clone(),
+        ..Config::default()
+    };
+    let mut store = Store::with_config(root.clone(), config).unwrap();
+    let recipients = [identity.to_public().to_string()];
+    api::init(&store, "", &recipients, store.backends().age()).unwrap();
+    store.put("email/work", b"secret\nlogin: me\n").unwrap();
+    store.commit("Add email.").unwrap();
+
+    let extension = root.join(".extensions/first.bash");
+    std::fs::create_dir_all(extension.parent().unwrap()).unwrap();
+    std::fs::write(&extension, EXTENSION).unwrap();
+    std::fs::set_permissions(&extension, std::fs::Permissions::from_mode(0o700)).unwrap();
+
+    let output = spass(&root, &identities, &["first", "email/work"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // --armor, 4 options of pass, and 2 more if there is gpg2, like in pass
+    let has_gpg2 = std::process::Command::new("gpg2")
+        .arg("--version")
+        .output()
+        .is_ok();
+    let gpg_options = if has_gpg2 { 7 } else { 5 };
+    assert_eq!(stdout, format!("SECRET\n{gpg_options} gpg options\n"));
+
+    let output = spass(&root, &identities, &["first"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Usage: spass first pass-name\n"
+    );
+
+    let output = spass(&root, &identities, &["first", "../store/email/work"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}